@@ -0,0 +1,316 @@
+//! Arbitrary output variables, the render passes written next to the image for compositing and
+//! denoising
+
+use crate::vec3::Vec3;
+use exr::prelude::{
+    read_all_flat_layers_from_file, AnyChannel, AnyChannels, Encoding, FlatSamples, Image,
+    ImageAttributes, IntegerBounds, Layer, LayerAttributes, SmallVec, WritableImage,
+};
+use std::{path::Path, str::FromStr};
+
+/// Pass computed along the beauty image
+///
+/// The geometric passes describe the first surface hit by the camera rays and are 0 where
+/// nothing is hit. Like the image they are filtered, except for the IDs which keep the sample
+/// closest to the center of the pixel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Aov {
+    /// Color of the surface, see `Material::albedo`
+    Albedo,
+    /// World space shading normal
+    Normal,
+    /// Shading normal in the camera basis, x to the right, y up and z toward the camera
+    CameraNormal,
+    /// Distance from the camera
+    Depth,
+    /// World space position
+    Position,
+    /// Hash of the kind of material and its albedo
+    MaterialId,
+    /// ID given to each object of the scene when it is built, a mesh or particle set is a single
+    /// object
+    ObjectId,
+    /// Light emitted by the surfaces seen by the camera
+    Emission,
+    /// Light reaching the camera after a single bounce
+    Direct,
+    /// Light reaching the camera after more than one bounce
+    Indirect,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 10] = [
+        Aov::Albedo,
+        Aov::Normal,
+        Aov::CameraNormal,
+        Aov::Depth,
+        Aov::Position,
+        Aov::MaterialId,
+        Aov::ObjectId,
+        Aov::Emission,
+        Aov::Direct,
+        Aov::Indirect,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::CameraNormal => "camera_normal",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::MaterialId => "material_id",
+            Aov::ObjectId => "object_id",
+            Aov::Emission => "emission",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+        }
+    }
+
+    pub fn is_id(self) -> bool {
+        matches!(self, Aov::MaterialId | Aov::ObjectId)
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.to_lowercase();
+        Aov::ALL
+            .iter()
+            .copied()
+            .find(|aov| aov.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Aov::ALL.iter().map(|aov| aov.name()).collect();
+                format!("unknown AOV {}, expected one of {}", name, names.join(", "))
+            })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum AovBuffer {
+    /// Filtered values, depth is stored in every channel
+    Color(Vec<Vec3>),
+    Id(Vec<u32>),
+}
+
+/// Linear beauty image with the requested passes, rows from the top
+#[derive(Clone, Debug)]
+pub struct RenderOutput {
+    pub width: u32,
+    pub height: u32,
+    pub beauty: Vec<Vec3>,
+    pub aovs: Vec<(Aov, AovBuffer)>,
+}
+
+impl RenderOutput {
+    pub fn aov(&self, aov: Aov) -> Option<&AovBuffer> {
+        self.aovs
+            .iter()
+            .find(|(a, _)| *a == aov)
+            .map(|(_, buffer)| buffer)
+    }
+
+    /// Beauty image with a gamma of 2 for display
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.beauty
+            .iter()
+            .flat_map(|col| {
+                let col = col.max(Vec3::ZERO);
+                let col = Vec3::new(col.x.sqrt(), col.y.sqrt(), col.z.sqrt());
+
+                let vrgb = 255.99 * col;
+
+                vec![vrgb.x as u8, vrgb.y as u8, vrgb.z as u8, 0xff]
+            })
+            .collect()
+    }
+
+    /// Writes the image and its passes to a `.exr` file with one layer per pass, or see
+    /// `save_files` for any other extension
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let is_exr = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("exr"));
+        if is_exr {
+            self.save_exr(path).map_err(|e| e.to_string())
+        } else {
+            self.save_files(path)
+        }
+    }
+
+    pub fn save_exr(&self, path: &Path) -> exr::error::Result<()> {
+        let beauty = AovBuffer::Color(self.beauty.clone());
+        let mut layers = vec![self.layer("beauty", None, &beauty)];
+        for (aov, buffer) in &self.aovs {
+            layers.push(self.layer(aov.name(), Some(*aov), buffer));
+        }
+        self.write_exr(path, layers)
+    }
+
+    /// Writes the image to `path` in 8 bits like the window shows it, and each pass to
+    /// `<stem>_<pass>.exr` next to it
+    pub fn save_files(&self, path: &Path) -> Result<(), String> {
+        image::save_buffer(
+            path,
+            &self.to_rgba8(),
+            self.width,
+            self.height,
+            image::ColorType::Rgba8,
+        )
+        .map_err(|e| e.to_string())?;
+
+        let stem = path.with_extension("");
+        for (aov, buffer) in &self.aovs {
+            let layer = self.layer(aov.name(), Some(*aov), buffer);
+            let path = format!("{}_{}.exr", stem.to_string_lossy(), aov.name());
+            self.write_exr(Path::new(&path), vec![layer])
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Reads an image written by `save`, with the passes found in it or next to it
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let is_exr = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("exr"));
+        if is_exr {
+            return Self::open_exr(path).map_err(|e| e.to_string());
+        }
+
+        let image = image::open(path).map_err(|e| e.to_string())?.to_rgb();
+        let linear = |c: u8| (c as f32 / 255.).powi(2);
+        let mut output = RenderOutput {
+            width: image.width(),
+            height: image.height(),
+            beauty: image
+                .pixels()
+                .map(|p| Vec3::new(linear(p[0]), linear(p[1]), linear(p[2])))
+                .collect(),
+            aovs: Vec::new(),
+        };
+
+        let stem = path.with_extension("");
+        for aov in Aov::ALL.iter() {
+            let path = format!("{}_{}.exr", stem.to_string_lossy(), aov.name());
+            if Path::new(&path).exists() {
+                let passes = Self::open_exr(Path::new(&path)).map_err(|e| e.to_string())?;
+                if (passes.width, passes.height) != (output.width, output.height) {
+                    return Err(format!("{} does not match the size of the image", path));
+                }
+                output.aovs.extend(passes.aovs);
+            }
+        }
+        Ok(output)
+    }
+
+    fn open_exr(path: &Path) -> exr::error::Result<Self> {
+        let image = read_all_flat_layers_from_file(path)?;
+        let size = image.attributes.display_window.size;
+        let mut output = RenderOutput {
+            width: size.width() as u32,
+            height: size.height() as u32,
+            beauty: Vec::new(),
+            aovs: Vec::new(),
+        };
+
+        for layer in &image.layer_data {
+            let name = layer
+                .attributes
+                .layer_name
+                .as_ref()
+                .map_or_else(|| "beauty".to_string(), |name| name.to_string());
+            let channel = |label: &str| -> Option<Vec<f32>> {
+                layer
+                    .channel_data
+                    .list
+                    .iter()
+                    .find(|c| c.name.eq(label))
+                    .map(|c| c.sample_data.values_as_f32().collect())
+            };
+            let color = || -> Option<Vec<Vec3>> {
+                let (r, g, b) = (channel("R")?, channel("G")?, channel("B")?);
+                Some(
+                    r.iter()
+                        .zip(g.iter().zip(b.iter()))
+                        .map(|(r, (g, b))| Vec3::new(*r, *g, *b))
+                        .collect(),
+                )
+            };
+
+            if name == "beauty" {
+                output.beauty = color().unwrap_or_default();
+                continue;
+            }
+            let aov = match name.parse::<Aov>() {
+                Ok(aov) => aov,
+                Err(_) => continue,
+            };
+            let buffer = if aov.is_id() {
+                layer
+                    .channel_data
+                    .list
+                    .iter()
+                    .find_map(|c| match &c.sample_data {
+                        FlatSamples::U32(ids) if c.name.eq("id") => Some(ids.clone()),
+                        _ => None,
+                    })
+                    .map(AovBuffer::Id)
+            } else if aov == Aov::Depth {
+                channel("Z").map(|z| AovBuffer::Color(z.into_iter().map(Vec3::splat).collect()))
+            } else {
+                color().map(AovBuffer::Color)
+            };
+            if let Some(buffer) = buffer {
+                output.aovs.push((aov, buffer));
+            }
+        }
+        Ok(output)
+    }
+
+    fn layer(
+        &self,
+        name: &str,
+        aov: Option<Aov>,
+        buffer: &AovBuffer,
+    ) -> Layer<AnyChannels<FlatSamples>> {
+        let channels = match buffer {
+            AovBuffer::Color(values) if aov == Some(Aov::Depth) => vec![AnyChannel::new(
+                "Z",
+                FlatSamples::F32(values.iter().map(|v| v.x).collect()),
+            )],
+            AovBuffer::Color(values) => {
+                let channel = |label: &str, c: fn(&Vec3) -> f32| {
+                    AnyChannel::new(label, FlatSamples::F32(values.iter().map(c).collect()))
+                };
+                vec![
+                    channel("R", |v| v.x),
+                    channel("G", |v| v.y),
+                    channel("B", |v| v.z),
+                ]
+            }
+            AovBuffer::Id(ids) => vec![AnyChannel::new("id", FlatSamples::U32(ids.clone()))],
+        };
+        Layer::new(
+            (self.width as usize, self.height as usize),
+            LayerAttributes::named(name),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(SmallVec::from_vec(channels)),
+        )
+    }
+
+    fn write_exr(
+        &self,
+        path: &Path,
+        layers: Vec<Layer<AnyChannels<FlatSamples>>>,
+    ) -> exr::error::Result<()> {
+        let bounds = IntegerBounds::from_dimensions((self.width as usize, self.height as usize));
+        Image::from_layers(ImageAttributes::new(bounds), layers)
+            .write()
+            .to_file(path)
+    }
+}
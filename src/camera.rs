@@ -1,19 +1,127 @@
-use crate::{ray::Ray, vec3::Vec3};
+use crate::{ray::Ray, sampler::Sampler, vec3::Vec3};
 use derive_builder::*;
-use rand::Rng;
-use std::ops::Range;
+use glam::Vec2;
+use image::{DynamicImage, ImageResult};
+use std::{f32::consts::PI, ops::Range, path::Path, sync::Arc};
 
-fn random_in_unit_disk(rng: &mut impl Rng) -> Vec3 {
-    loop {
-        let p = 2. * Vec3::new(rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0), 0.)
-            - Vec3::new(1., 1., 0.);
+/// Closest distance the camera can focus at, nearer focus distances are clamped to it
+pub const MIN_FOCUS_DIST: f32 = 1e-3;
 
-        if p.dot(p) >= 1. {
-            return p;
+/// Tries of the rejection sampling of the cat's eye before falling back to the center of the
+/// lens
+const CAT_EYE_TRIES: usize = 32;
+
+/// Uniform point in the unit disk from a point of the unit square, with Shirley's concentric
+/// mapping which keeps the strata of the square
+fn sample_disk(u: Vec2) -> Vec2 {
+    let p = 2. * u - Vec2::ONE;
+    if p == Vec2::ZERO {
+        return p;
+    }
+
+    let (radius, theta) = if p.x.abs() > p.y.abs() {
+        (p.x, PI / 4. * (p.y / p.x))
+    } else {
+        (p.y, PI / 2. - PI / 4. * (p.x / p.y))
+    };
+    radius * Vec2::new(theta.cos(), theta.sin())
+}
+
+/// Shape of the opening of the lens, which gives its shape to out of focus highlights
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Aperture {
+    #[default]
+    Disk,
+    /// Regular polygon inscribed in the disk, `rotation` in degrees turns the first blade
+    /// counterclockwise from the right
+    Polygon {
+        blades: u32,
+        rotation: f32,
+    },
+    Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    /// Uniform point of the aperture in the unit disk
+    fn sample(&self, sampler: &mut impl Sampler) -> Vec2 {
+        match self {
+            Aperture::Disk => sample_disk(sampler.get_2d()),
+            Aperture::Polygon { blades, rotation } => {
+                let blades = (*blades).max(3);
+                // Every triangle between the center and a side has the same area
+                let u = sampler.get_2d();
+                let side = (u.x * blades as f32).floor().min(blades as f32 - 1.);
+                let s = u.x * blades as f32 - side;
+                let angle = 2. * PI / blades as f32;
+                let corner = |i: f32| {
+                    let a = rotation.to_radians() + i * angle;
+                    Vec2::new(a.cos(), a.sin())
+                };
+                let (a, b) = (corner(side), corner(side + 1.));
+
+                let s = s.sqrt();
+                s * (1. - u.y) * a + s * u.y * b
+            }
+            Aperture::Mask(mask) => mask.sample(sampler),
         }
     }
 }
 
+/// Aperture drawn in a grayscale image, white lets the light through, the image is fitted in
+/// the unit disk
+#[derive(Debug, PartialEq)]
+pub struct ApertureMask {
+    width: u32,
+    height: u32,
+    /// Running sum of the texels, row by row from the top
+    cdf: Vec<f32>,
+}
+
+impl ApertureMask {
+    pub fn open(path: impl AsRef<Path>) -> ImageResult<ApertureMask> {
+        Ok(ApertureMask::from_image(&image::open(path)?))
+    }
+
+    /// Panics for images without any light going through
+    pub fn from_image(image: &DynamicImage) -> ApertureMask {
+        let image = image.to_luma();
+        let mut total = 0.;
+        let cdf: Vec<f32> = image
+            .pixels()
+            .map(|p| {
+                total += p.0[0] as f32;
+                total
+            })
+            .collect();
+        assert!(total > 0., "an aperture mask needs some light");
+
+        ApertureMask {
+            width: image.width(),
+            height: image.height(),
+            cdf,
+        }
+    }
+
+    fn sample(&self, sampler: &mut impl Sampler) -> Vec2 {
+        let total = self.cdf[self.cdf.len() - 1];
+        let target = sampler.get_1d() * total;
+        let index = self
+            .cdf
+            .partition_point(|sum| *sum <= target)
+            .min(self.cdf.len() - 1) as u32;
+        let jitter = sampler.get_2d();
+        let x = (index % self.width) as f32 + jitter.x;
+        let y = (index / self.width) as f32 + jitter.y;
+
+        // The largest side spans the diagonal of the unit disk's square
+        let size = self.width.max(self.height) as f32 / 2.;
+        Vec2::new(
+            (x - self.width as f32 / 2.) / size,
+            (self.height as f32 / 2. - y) / size,
+        ) / std::f32::consts::SQRT_2
+    }
+}
+
 pub struct Camera {
     pub origin: Vec3,
     pub lower_left_corner: Vec3,
@@ -23,15 +131,31 @@ pub struct Camera {
     pub v: Vec3,
     pub w: Vec3,
     pub lens_radius: f32,
+    pub aperture_shape: Aperture,
+    pub cat_eye: f32,
+    /// Distance along the pinhole rays at which lens rays meet them, always positive
+    pub focus_dist: f32,
+    /// Normal of a tilted focus plane
+    pub focus_normal: Option<Vec3>,
+    pub camera_type: CameraType,
+    pub stereo: Option<Stereo>,
+    /// Aspect ratio of the view of a single eye
+    pub aspect: f32,
+    /// Angle covered by a single pixel, see `Ray::spread`
+    pub pixel_spread: f32,
     pub exposure: Range<f32>,
     pub height: u32,
     pub width: u32,
+    /// Configuration the camera was made from
+    pub config: CameraConfig,
 }
 
-#[derive(Default, Builder)]
+#[derive(Clone, Debug, Default, PartialEq, Builder)]
 pub struct CameraConfig {
     pub lookfrom: Vec3,
     pub lookat: Vec3,
+    /// Vertical field of view in degrees, derived from `lens` when not set
+    #[builder(default = "self.default_vfov()")]
     pub vfov: f32,
     pub focus_dist: f32,
     pub height: u32,
@@ -44,9 +168,195 @@ pub struct CameraConfig {
     pub aperture: f32,
     #[builder(default = "Exposure(0.0..1.0)")]
     pub exposure: Exposure,
+    #[builder(default)]
+    pub aperture_shape: Aperture,
+    /// Cat's eye vignetting, how far the aperture seen from the edges of the image is cut by
+    /// the lens barrel, 0 for none and 1 to cut half of it in the corners
+    #[builder(default = "0.0")]
+    pub cat_eye: f32,
+    /// Tilt of the focus plane in degrees around the horizontal and vertical axes of the
+    /// view. Positive angles bring it closer at the bottom and push it away on the right
+    #[builder(default = "Vec2::ZERO")]
+    pub tilt: Vec2,
+    /// Shift of the view in fractions of its size, to the right and up, for perspective and
+    /// orthographic views
+    #[builder(default = "Vec2::ZERO")]
+    pub shift: Vec2,
+    #[builder(default)]
+    pub camera_type: CameraType,
+    /// Splits the image in one view per eye, `aspect` is the one of the whole image
+    #[builder(setter(strip_option), default)]
+    pub stereo: Option<Stereo>,
+    /// Overrides `vfov`, `aperture` and `exposure` when set
+    #[builder(setter(strip_option), default)]
+    pub lens: Option<PhysicalLens>,
+}
+
+/// Projection of the scene on the image, depth of field works the same way for all of them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CameraType {
+    /// Pinhole or thin lens camera covering `vfov`
+    #[default]
+    Perspective,
+    /// Parallel rays along the view direction, for technical drawings, `height` is the height
+    /// of the view in scene units
+    Orthographic { height: f32 },
+    /// 360° by 180° panorama with the view direction at the center of the image
+    Equirectangular,
+    /// Equidistant fisheye where `fov` in degrees covers the height of the image, the corners of
+    /// a wide image see further
+    Fisheye { fov: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StereoLayout {
+    /// Left eye on the left half of the image
+    SideBySide,
+    /// Left eye on the top half of the image
+    OverUnder,
+}
+
+/// Two views of the scene for a VR headset, the eyes are on each side of `lookfrom`
+///
+/// Perspective eyes converge on the focus plane. Equirectangular eyes turn around `lookfrom`
+/// with the view direction to give an omnidirectional stereo panorama.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stereo {
+    pub layout: StereoLayout,
+    /// Distance between the eyes in scene units
+    pub eye_separation: f32,
+}
+
+impl Stereo {
+    /// Coordinates in the view of the eye seeing `(u, v)` and the offset of that eye to the
+    /// right
+    fn eye(&self, u: f32, v: f32) -> (f32, f32, f32) {
+        let half = self.eye_separation / 2.;
+        match self.layout {
+            StereoLayout::SideBySide if u < 0.5 => (2. * u, v, -half),
+            StereoLayout::SideBySide => (2. * u - 1., v, half),
+            StereoLayout::OverUnder if v >= 0.5 => (u, 2. * v - 1., -half),
+            StereoLayout::OverUnder => (u, 2. * v, half),
+        }
+    }
 }
 
-#[derive(Clone)]
+/// Physical description of the camera, in the units of a real one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhysicalLens {
+    /// Focal length in millimeters
+    pub focal_length: f32,
+    /// Height of the sensor in millimeters, 24 for a full frame sensor
+    pub sensor_height: f32,
+    pub f_stop: f32,
+    /// Time the shutter stays open from time 0, in the same unit as the times of moving objects,
+    /// must be positive
+    pub shutter: f32,
+    /// Scene units in a meter, used to size the aperture
+    pub units_per_meter: f32,
+}
+
+impl Default for PhysicalLens {
+    fn default() -> Self {
+        PhysicalLens {
+            focal_length: 50.,
+            sensor_height: 24.,
+            f_stop: 8.,
+            shutter: 1.,
+            units_per_meter: 1.,
+        }
+    }
+}
+
+impl PhysicalLens {
+    /// Vertical field of view in degrees
+    pub fn vfov(&self) -> f32 {
+        2. * (self.sensor_height / (2. * self.focal_length))
+            .atan()
+            .to_degrees()
+    }
+
+    /// Diameter of the entrance pupil in scene units
+    pub fn aperture(&self) -> f32 {
+        self.focal_length / self.f_stop / 1000. * self.units_per_meter
+    }
+}
+
+impl CameraConfig {
+    /// Sets `focus_dist` so that `point` is sharp, the focus plane is perpendicular to the view
+    /// direction so this is the distance along it and not to the point
+    pub fn focus_on(&mut self, point: Vec3) {
+        let forward = (self.lookat - self.lookfrom).normalize();
+        self.focus_dist = (point - self.lookfrom).dot(forward).max(MIN_FOCUS_DIST);
+    }
+
+    /// Builder calls giving this configuration, to paste in a scene of `scenes.rs`
+    pub fn builder_code(&self) -> String {
+        let vec3 = |v: Vec3| format!("Vec3::new({:?}, {:?}, {:?})", v.x, v.y, v.z);
+        let vec2 = |v: Vec2| format!("Vec2::new({:?}, {:?})", v.x, v.y);
+
+        let mut calls = vec![
+            ("lookfrom", vec3(self.lookfrom)),
+            ("lookat", vec3(self.lookat)),
+        ];
+        if self.vup != Vec3::new(0., 1., 0.) {
+            calls.push(("vup", vec3(self.vup)));
+        }
+        match self.lens {
+            Some(lens) => calls.push(("lens", format!("{:?}", lens))),
+            None => {
+                calls.push(("vfov", format!("{:?}", self.vfov)));
+                calls.push(("aperture", format!("{:?}", self.aperture)));
+            }
+        }
+        calls.push(("focus_dist", format!("{:?}", self.focus_dist)));
+        calls.push(("width", "WIDTH".to_string()));
+        calls.push(("height", "HEIGHT".to_string()));
+        if self.aspect != self.width as f32 / self.height as f32 {
+            calls.push(("aspect", format!("{:?}", self.aspect)));
+        }
+        if self.camera_type != CameraType::default() {
+            calls.push(("camera_type", format!("CameraType::{:?}", self.camera_type)));
+        }
+        if let Some(stereo) = self.stereo {
+            calls.push((
+                "stereo",
+                format!(
+                    "Stereo {{ layout: StereoLayout::{:?}, eye_separation: {:?} }}",
+                    stereo.layout, stereo.eye_separation
+                ),
+            ));
+        }
+        match &self.aperture_shape {
+            Aperture::Disk => {}
+            Aperture::Polygon { .. } => calls.push((
+                "aperture_shape",
+                format!("Aperture::{:?}", self.aperture_shape),
+            )),
+            Aperture::Mask(_) => calls.push((
+                "aperture_shape",
+                "Aperture::Mask(/* the mask of the scene */)".to_string(),
+            )),
+        }
+        if self.cat_eye != 0. {
+            calls.push(("cat_eye", format!("{:?}", self.cat_eye)));
+        }
+        if self.tilt != Vec2::ZERO {
+            calls.push(("tilt", vec2(self.tilt)));
+        }
+        if self.shift != Vec2::ZERO {
+            calls.push(("shift", vec2(self.shift)));
+        }
+
+        let mut code = "CameraConfigBuilder::default()\n".to_string();
+        for (name, value) in calls {
+            code += &format!("    .{}({})\n", name, value);
+        }
+        code + "    .build()\n    .unwrap()"
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Exposure(Range<f32>);
 
 impl Default for Exposure {
@@ -56,6 +366,21 @@ impl Default for Exposure {
 }
 
 impl CameraConfigBuilder {
+    /// See `CameraConfig::focus_on`, `lookfrom` and `lookat` must be set first
+    pub fn focus_on(&mut self, point: Vec3) -> &mut Self {
+        let lookfrom = self.lookfrom.expect("lookfrom must be set before focusing");
+        let lookat = self.lookat.expect("lookat must be set before focusing");
+        let forward = (lookat - lookfrom).normalize();
+        self.focus_dist((point - lookfrom).dot(forward).max(MIN_FOCUS_DIST))
+    }
+
+    fn default_vfov(&self) -> f32 {
+        self.lens
+            .flatten()
+            .expect("either vfov or lens must be set")
+            .vfov()
+    }
+
     fn default_aspect(&self) -> f32 {
         self.width.unwrap() as f32 / self.height.unwrap() as f32
     }
@@ -64,42 +389,382 @@ impl CameraConfigBuilder {
 impl Camera {
     #[allow(clippy::too_many_arguments)]
     pub fn new(config: CameraConfig) -> Self {
-        use std::f32::consts::PI;
-        let theta = config.vfov * PI / 180.;
-        let half_height = (theta / 2.).tan();
-        let half_width = config.aspect * half_height;
+        let original = config.clone();
+        let mut config = config;
+        if let Some(lens) = config.lens {
+            config.vfov = lens.vfov();
+            config.aperture = lens.aperture();
+            config.exposure = Exposure(0.0..lens.shutter);
+        }
+
+        let (aspect, eye_height) = match config.stereo.map(|stereo| stereo.layout) {
+            Some(StereoLayout::SideBySide) => (config.aspect / 2., config.height),
+            Some(StereoLayout::OverUnder) => (config.aspect * 2., config.height / 2),
+            None => (config.aspect, config.height),
+        };
+        let eye_height = eye_height.max(1) as f32;
 
         let w = (config.lookfrom - config.lookat).normalize();
         let u = config.vup.cross(w).normalize();
         let v = w.cross(u);
 
+        // The view window lies at a distance of 1, or on the camera plane for orthographic views
+        let (half_height, distance, pixel_spread) = match config.camera_type {
+            CameraType::Perspective => {
+                let half_height = (config.vfov.to_radians() / 2.).tan();
+                (half_height, 1., 2. * half_height / eye_height)
+            }
+            CameraType::Orthographic { height } => (height / 2., 0., 0.),
+            CameraType::Equirectangular => (1., 1., PI / eye_height),
+            CameraType::Fisheye { fov } => (1., 1., fov.to_radians() / eye_height),
+        };
+        let half_width = aspect * half_height;
+        let horizontal = 2. * half_width * u;
+        let vertical = 2. * half_height * v;
+
+        let (tilt, swing) = (config.tilt.x.to_radians(), config.tilt.y.to_radians());
+        let focus_normal = if tilt != 0. || swing != 0. {
+            Some((w + tilt.tan() * v + swing.tan() * u).normalize())
+        } else {
+            None
+        };
+
         Camera {
-            lower_left_corner: config.lookfrom
-                - half_width * config.focus_dist * u
-                - half_height * config.focus_dist * v
-                - config.focus_dist * w,
-            horizontal: 2. * half_width * config.focus_dist * u,
-            vertical: 2. * half_height * config.focus_dist * v,
+            lower_left_corner: config.lookfrom - half_width * u - half_height * v - distance * w
+                + config.shift.x * horizontal
+                + config.shift.y * vertical,
+            horizontal,
+            vertical,
             origin: config.lookfrom,
             u,
             v,
             w,
             lens_radius: config.aperture / 2.,
+            aperture_shape: config.aperture_shape,
+            cat_eye: config.cat_eye,
+            focus_dist: config.focus_dist.max(MIN_FOCUS_DIST),
+            focus_normal,
+            camera_type: config.camera_type,
+            stereo: config.stereo,
+            aspect,
+            pixel_spread,
             exposure: config.exposure.0,
             width: config.width,
             height: config.height,
+            config: original,
+        }
+    }
+
+    pub fn get_ray(&self, u: f32, v: f32, sampler: &mut impl Sampler) -> Ray {
+        let (u, v, eye) = match self.stereo {
+            Some(stereo) => stereo.eye(u, v),
+            None => (u, v, 0.),
+        };
+        let (origin, direction) = self.project(u, v, eye);
+
+        // Rays through any point of the lens meet the pinhole ray at the focus distance, or
+        // where it crosses the tilted focus plane
+        let mut focus = self.focus_dist * direction;
+        if let Some(normal) = self.focus_normal {
+            let center = self.origin - self.focus_dist * self.w;
+            let t = (center - origin).dot(normal) / focus.dot(normal);
+            if t > 0. {
+                focus *= t;
+            }
+        }
+
+        let rd = self.lens_radius * self.sample_lens(u, v, sampler);
+        let offset = self.u * rd.x + self.v * rd.y;
+        let origin = origin + offset;
+        let direction = focus - offset;
+        let time =
+            self.exposure.start + sampler.get_1d() * (self.exposure.end - self.exposure.start);
+
+        Ray {
+            spread: self.pixel_spread,
+            ..Ray::new(origin, direction, time)
+        }
+    }
+
+    /// Point of the aperture in the unit disk seen from `(u, v)`, the cat's eye keeps the part
+    /// of the aperture inside the lens barrel seen off center
+    fn sample_lens(&self, u: f32, v: f32, sampler: &mut impl Sampler) -> Vec2 {
+        if self.lens_radius <= 0. {
+            return Vec2::ZERO;
+        }
+        if self.cat_eye <= 0. {
+            return self.aperture_shape.sample(sampler);
+        }
+
+        let barrel = self.cat_eye * Vec2::new(2. * u - 1., 2. * v - 1.);
+        (0..CAT_EYE_TRIES)
+            .map(|_| self.aperture_shape.sample(sampler))
+            .find(|p| (*p - barrel).length_squared() <= 1.)
+            .unwrap_or(Vec2::ZERO)
+    }
+
+    /// Origin and direction of the pinhole ray through `(u, v)` for an eye moved by `eye` to the
+    /// right, the direction is a unit vector except for perspective views where it reaches the
+    /// plane at a distance of 1
+    fn project(&self, u: f32, v: f32, eye: f32) -> (Vec3, Vec3) {
+        match self.camera_type {
+            CameraType::Perspective => {
+                let target = self.lower_left_corner + u * self.horizontal + v * self.vertical;
+                // The eyes converge on the focus plane
+                let direction = target - self.origin - eye / self.focus_dist * self.u;
+                (self.origin + eye * self.u, direction)
+            }
+            CameraType::Orthographic { .. } => {
+                let origin =
+                    self.lower_left_corner + u * self.horizontal + v * self.vertical + eye * self.u;
+                (origin, -self.w)
+            }
+            CameraType::Equirectangular => {
+                let phi = (u - 0.5) * 2. * PI;
+                let theta = (v - 0.5) * PI;
+                let forward = phi.sin() * self.u - phi.cos() * self.w;
+                let right = phi.cos() * self.u + phi.sin() * self.w;
+                let direction = theta.cos() * forward + theta.sin() * self.v;
+                (self.origin + eye * right, direction)
+            }
+            CameraType::Fisheye { fov } => {
+                let x = (2. * u - 1.) * self.aspect;
+                let y = 2. * v - 1.;
+                let radius = (x * x + y * y).sqrt();
+                let theta = radius * fov.to_radians() / 2.;
+                let direction = if radius > 0. {
+                    theta.sin() / radius * (x * self.u + y * self.v) - theta.cos() * self.w
+                } else {
+                    -self.w
+                };
+                (self.origin + eye * self.u, direction)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    fn config() -> CameraConfig {
+        CameraConfigBuilder::default()
+            .lookfrom(Vec3::ZERO)
+            .lookat(Vec3::new(0., 0., -1.))
+            .vfov(60.)
+            .focus_dist(1.)
+            .aperture(0.5)
+            .width(20)
+            .height(10)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn lens_rays_meet_on_the_focus_plane() {
+        let mut config = config();
+        config.focus_on(Vec3::new(3., 1., -4.));
+        assert!((config.focus_dist - 4.).abs() < 1e-5);
+
+        let camera = Camera::new(config);
+        let mut sampler = IndependentSampler::new(1);
+        let focus = |r: Ray| r.point_at((-4. - r.origin.z) / r.direction.z);
+        let expected = focus(camera.get_ray(0.3, 0.7, &mut sampler));
+        for _ in 0..16 {
+            let r = camera.get_ray(0.3, 0.7, &mut sampler);
+            assert!((focus(r) - expected).length() < 1e-4);
+        }
+    }
+
+    fn camera(camera_type: CameraType, focus_dist: f32) -> Camera {
+        let mut config = config();
+        config.camera_type = camera_type;
+        config.focus_dist = focus_dist;
+        Camera::new(config)
+    }
+
+    /// Direction of the pinhole ray through `(u, v)`
+    fn direction(camera: &Camera, u: f32, v: f32) -> Vec3 {
+        camera.project(u, v, 0.).1.normalize()
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    #[test]
+    fn perspective_covers_the_field_of_view() {
+        let camera = camera(CameraType::Perspective, 3.);
+        assert!(close(direction(&camera, 0.5, 0.5), Vec3::new(0., 0., -1.)));
+        let top = direction(&camera, 0.5, 1.);
+        assert!((top.y.atan2(-top.z).to_degrees() - 30.).abs() < 1e-3);
+        let right = direction(&camera, 1., 0.5);
+        assert!((right.x / -right.z - 2. * 30f32.to_radians().tan()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = camera(CameraType::Orthographic { height: 4. }, 3.);
+        for &(u, v) in [(0., 0.), (0.5, 0.5), (1., 0.25)].iter() {
+            assert!(close(direction(&camera, u, v), Vec3::new(0., 0., -1.)));
+        }
+        let (bottom, _) = camera.project(0.5, 0., 0.);
+        let (top, _) = camera.project(0.5, 1., 0.);
+        assert!(close(top - bottom, Vec3::new(0., 4., 0.)));
+    }
+
+    #[test]
+    fn equirectangular_covers_the_sphere() {
+        let camera = camera(CameraType::Equirectangular, 3.);
+        assert!(close(direction(&camera, 0.5, 0.5), Vec3::new(0., 0., -1.)));
+        assert!(close(direction(&camera, 0.75, 0.5), Vec3::new(1., 0., 0.)));
+        assert!(close(direction(&camera, 0., 0.5), Vec3::new(0., 0., 1.)));
+        assert!(close(direction(&camera, 0.3, 1.), Vec3::new(0., 1., 0.)));
+    }
+
+    #[test]
+    fn fisheye_is_equidistant() {
+        let camera = camera(CameraType::Fisheye { fov: 180. }, 3.);
+        assert!(close(direction(&camera, 0.5, 0.5), Vec3::new(0., 0., -1.)));
+        assert!(close(direction(&camera, 0.5, 1.), Vec3::new(0., 1., 0.)));
+        let d = direction(&camera, 0.5, 0.75);
+        assert!((d.y.atan2(-d.z).to_degrees() - 45.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn stereo_eyes_converge_on_the_focus_plane() {
+        let mut config = config();
+        config.focus_dist = 5.;
+        config.stereo = Some(Stereo {
+            layout: StereoLayout::SideBySide,
+            eye_separation: 0.2,
+        });
+        config.aperture = 0.;
+        let camera = Camera::new(config);
+        let mut sampler = IndependentSampler::new(1);
+        let left = camera.get_ray(0.2, 0.6, &mut sampler);
+        let right = camera.get_ray(0.7, 0.6, &mut sampler);
+        assert!(close(left.origin, Vec3::new(-0.1, 0., 0.)));
+        assert!(close(right.origin, Vec3::new(0.1, 0., 0.)));
+        let focus = |r: Ray| r.point_at((-5. - r.origin.z) / r.direction.z);
+        assert!(close(focus(left), focus(right)));
+    }
+
+    #[test]
+    fn non_positive_focus_distances_are_clamped() {
+        let types = [
+            CameraType::Perspective,
+            CameraType::Orthographic { height: 2. },
+            CameraType::Equirectangular,
+            CameraType::Fisheye { fov: 120. },
+        ];
+        let mut sampler = IndependentSampler::new(1);
+        for &camera_type in types.iter() {
+            for &focus_dist in [0., -2.].iter() {
+                let camera = camera(camera_type, focus_dist);
+                let r = camera.get_ray(0.3, 0.6, &mut sampler);
+                assert!(r.origin.is_finite() && r.direction.is_finite());
+                assert!(r.direction.length() > 0.);
+            }
         }
+
+        let mut config = config();
+        config.focus_on(Vec3::new(0., 0., 2.));
+        assert_eq!(config.focus_dist, MIN_FOCUS_DIST);
+    }
+
+    /// Fraction of `points` in each of `bins`
+    fn histogram(points: &[Vec2], bins: usize, bin: impl Fn(Vec2) -> usize) -> Vec<f32> {
+        let mut counts = vec![0.; bins];
+        for p in points {
+            counts[bin(*p)] += 1. / points.len() as f32;
+        }
+        counts
+    }
+
+    fn sectors(p: Vec2) -> usize {
+        ((p.y.atan2(p.x) + PI) / (2. * PI) * 8.).min(7.) as usize
+    }
+
+    #[test]
+    fn disk_aperture_is_uniform() {
+        let mut sampler = IndependentSampler::new(1);
+        let points: Vec<Vec2> = (0..8000)
+            .map(|_| Aperture::Disk.sample(&mut sampler))
+            .collect();
+        assert!(points.iter().all(|p| p.length() < 1.));
+
+        // Eight sectors split in two rings of the same area
+        let bins = histogram(&points, 16, |p| {
+            sectors(p) * 2 + (p.length() > std::f32::consts::FRAC_1_SQRT_2) as usize
+        });
+        assert!(
+            bins.iter().all(|f| (f - 1. / 16.).abs() < 0.015),
+            "{:?}",
+            bins
+        );
+    }
+
+    #[test]
+    fn polygon_aperture_is_uniform() {
+        let aperture = Aperture::Polygon {
+            blades: 6,
+            rotation: 30.,
+        };
+        let mut sampler = IndependentSampler::new(1);
+        let points: Vec<Vec2> = (0..8000).map(|_| aperture.sample(&mut sampler)).collect();
+
+        // Inside every side of the hexagon, whose apothem is cos(30°)
+        let apothem = 30f32.to_radians().cos();
+        for p in points.iter() {
+            for side in 0..6 {
+                let a = (side as f32 * 60.).to_radians();
+                assert!(p.dot(Vec2::new(a.cos(), a.sin())) <= apothem + 1e-5);
+            }
+        }
+        let bins = histogram(&points, 8, sectors);
+        assert!(
+            bins.iter().all(|f| (f - 1. / 8.).abs() < 0.02),
+            "{:?}",
+            bins
+        );
+        assert!(points.iter().any(|p| p.length() > 0.95));
+    }
+
+    #[test]
+    fn mask_aperture_follows_the_image() {
+        let mut image = image::GrayImage::new(2, 2);
+        image.put_pixel(0, 0, image::Luma([255]));
+        image.put_pixel(1, 1, image::Luma([255]));
+        let mask = ApertureMask::from_image(&DynamicImage::ImageLuma8(image));
+
+        let mut sampler = IndependentSampler::new(1);
+        let points: Vec<Vec2> = (0..4000).map(|_| mask.sample(&mut sampler)).collect();
+        assert!(points.iter().all(|p| p.length() < 1.));
+        // Only the top left and bottom right quarters are open
+        assert!(points.iter().all(|p| p.x * p.y <= 0.));
+        let left = points.iter().filter(|p| p.x < 0.).count() as f32 / 4000.;
+        assert!((left - 0.5).abs() < 0.03);
     }
 
-    pub fn get_ray(&self, u: f32, v: f32, rng: &mut impl Rng) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disk(rng);
-        let offset = u * rd.x + v * rd.y;
-        let offset = Vec3::splat(offset);
-        let origin = self.origin + offset;
-        let direction =
-            self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin - offset;
-        let time = rng.gen_range(self.exposure.start..self.exposure.end);
+    #[test]
+    fn physical_lens() {
+        let lens = PhysicalLens::default();
+        assert!((lens.vfov() - 26.99).abs() < 0.01);
+        assert!((lens.aperture() - 0.00625).abs() < 1e-6);
 
-        Ray::new(origin, direction, time)
+        let config = CameraConfigBuilder::default()
+            .lookfrom(Vec3::ZERO)
+            .lookat(Vec3::new(0., 0., -1.))
+            .focus_dist(1.)
+            .lens(lens)
+            .width(20)
+            .height(10)
+            .build()
+            .unwrap();
+        assert!((config.vfov - lens.vfov()).abs() < 1e-5);
+        let camera = Camera::new(config);
+        assert!((camera.lens_radius - 0.003125).abs() < 1e-6);
     }
 }
@@ -0,0 +1,514 @@
+//! Snapshots of a render in progress, to continue it after the process stopped or to add samples
+//! to a finished image
+//!
+//! The samplers draw the same numbers for a given pixel, sample index and seed whichever thread
+//! takes them, so the state of the random numbers is only the seed, the strata and the index of
+//! the next sample. The file holds them with the accumulated samples, in little endian.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    aov::Aov,
+    camera::CameraConfig,
+    renderer::{Accumulation, AovLayer, Region, RenderSettings, SEED},
+    stats::{Counters, RenderStats, ThreadStats},
+    vec3::Vec3,
+};
+
+const MAGIC: &[u8; 4] = b"RWCP";
+const VERSION: u32 = 2;
+
+/// What a render is an image of, samples rendered apart only add up when their setups match
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderSetup {
+    /// Name of the scene or path of its file
+    pub scene: String,
+    /// Seed of the random numbers used to generate the scene
+    pub scene_seed: u64,
+    /// Hash of the files the scene was read from, see `files_hash`
+    pub files_hash: u64,
+    /// Hash of the camera and the settings, see `setup_hash`
+    pub setup_hash: u64,
+    pub width: u32,
+    pub height: u32,
+    /// Samples per pixel the sampler spreads its points over, kept when samples are added
+    pub strata: u32,
+}
+
+impl RenderSetup {
+    /// Checks that `local`, the setup of this process, renders the same image as this one,
+    /// the seed and the strata are taken from this setup
+    pub fn validate(&self, local: &RenderSetup) -> Result<(), String> {
+        if local.scene != self.scene {
+            return Err(format!(
+                "the scene is {} instead of {}",
+                local.scene, self.scene
+            ));
+        }
+        if (local.width, local.height) != (self.width, self.height) {
+            return Err(format!(
+                "the image is {}x{} instead of {}x{}",
+                local.width, local.height, self.width, self.height
+            ));
+        }
+        if local.files_hash != self.files_hash {
+            return Err(format!("the files of {} differ", self.scene));
+        }
+        if local.setup_hash != self.setup_hash {
+            return Err("the camera, depth, filter, sampler or passes differ".to_string());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        write_string(w, &self.scene)?;
+        write_u64(w, self.scene_seed)?;
+        write_u64(w, self.files_hash)?;
+        write_u64(w, self.setup_hash)?;
+        write_u32(w, self.width)?;
+        write_u32(w, self.height)?;
+        write_u32(w, self.strata)
+    }
+
+    pub(crate) fn read(r: &mut impl Read) -> io::Result<Self> {
+        Ok(RenderSetup {
+            scene: read_string(r)?,
+            scene_seed: read_u64(r)?,
+            files_hash: read_u64(r)?,
+            setup_hash: read_u64(r)?,
+            width: read_u32(r)?,
+            height: read_u32(r)?,
+            strata: read_u32(r)?,
+        })
+    }
+}
+
+pub struct Checkpoint {
+    pub setup: RenderSetup,
+    pub sampler_seed: u64,
+    pub image: Accumulation,
+}
+
+impl Checkpoint {
+    /// Samples taken in every pixel so far, the index of the next sample
+    pub fn samples(&self) -> Result<u32, String> {
+        let first = self.image.samples.first().copied().unwrap_or(0);
+        if self.image.samples.iter().all(|s| *s == first) {
+            Ok(first)
+        } else {
+            Err("the checkpoint has pixels with different numbers of samples".to_string())
+        }
+    }
+
+    /// Checks that the render set up as `local` can go on from this checkpoint
+    pub fn validate(&self, local: &RenderSetup) -> Result<(), String> {
+        self.setup.validate(local)?;
+        let image = &self.image;
+        if (image.x, image.y, image.width, image.height)
+            != (0, 0, self.setup.width, self.setup.height)
+        {
+            return Err(format!(
+                "the checkpoint holds a {}x{} image instead of {}x{}",
+                image.width, image.height, self.setup.width, self.setup.height
+            ));
+        }
+        if self.sampler_seed != SEED {
+            return Err("the checkpoint was rendered with another sampler seed".to_string());
+        }
+        self.samples().map(|_| ())
+    }
+
+    /// Writes next to `path` first so a crash while saving keeps the previous checkpoint
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+
+        let write = || -> io::Result<()> {
+            let mut w = BufWriter::new(File::create(&temporary)?);
+            self.write(&mut w)?;
+            w.into_inner()?.sync_all()
+        };
+        write()
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let mut r = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+        Checkpoint::read(&mut r).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => "the checkpoint is truncated".to_string(),
+            _ => e.to_string(),
+        })
+    }
+
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        write_u32(w, VERSION)?;
+        self.setup.write(w)?;
+        write_u64(w, self.sampler_seed)?;
+        write_accumulation(w, &self.image)
+    }
+
+    fn read(r: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a checkpoint"));
+        }
+        if read_u32(r)? != VERSION {
+            return Err(invalid_data(
+                "the checkpoint was written by another version",
+            ));
+        }
+        Ok(Checkpoint {
+            setup: RenderSetup::read(r)?,
+            sampler_seed: read_u64(r)?,
+            image: read_accumulation(r, |_| Ok(()))?,
+        })
+    }
+}
+
+/// Hash of the contents of `files`, missing files hash like empty ones
+pub fn files_hash(files: &[PathBuf]) -> u64 {
+    files.iter().fold(FNV_OFFSET, |hash, file| {
+        let contents = fs::read(file).unwrap_or_default();
+        fnv1a(fnv1a(hash, file.to_string_lossy().as_bytes()), &contents)
+    })
+}
+
+/// Hash of everything but the number of samples that changes what the samples of a render are
+pub fn setup_hash(camera: &CameraConfig, settings: &RenderSettings) -> u64 {
+    let setup = format!(
+        "{:?} {} {:?} {:?} {:?}",
+        camera, settings.max_depth, settings.filter, settings.sampler, settings.aovs
+    );
+    fnv1a(FNV_OFFSET, setup.as_bytes())
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a, unlike the hasher of the standard library it is the same on every version of Rust
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub(crate) fn write_accumulation(w: &mut impl Write, image: &Accumulation) -> io::Result<()> {
+    for v in [image.x, image.y, image.width, image.height].iter() {
+        write_u32(w, *v)?;
+    }
+    for (color, weight) in &image.pixels {
+        write_vec3(w, *color)?;
+        write_f32(w, *weight)?;
+    }
+    for magnitude in &image.weight_magnitudes {
+        write_f32(w, *magnitude)?;
+    }
+    for samples in &image.samples {
+        write_u32(w, *samples)?;
+    }
+    for cost in &image.cost {
+        write_f32(w, *cost)?;
+    }
+
+    write_u32(w, image.aovs.len() as u32)?;
+    for (aov, layer) in &image.aovs {
+        write_string(w, aov.name())?;
+        match layer {
+            AovLayer::Color(values) => {
+                for value in values {
+                    write_vec3(w, *value)?;
+                }
+            }
+            AovLayer::Id(ids) => {
+                for (id, weight) in ids {
+                    write_u32(w, *id)?;
+                    write_f32(w, *weight)?;
+                }
+            }
+        }
+    }
+
+    let counters = &image.stats.counters;
+    for v in [
+        counters.camera_rays,
+        counters.bounce_rays,
+        counters.bvh_nodes,
+        counters.primitive_tests,
+    ]
+    .iter()
+    {
+        write_u64(w, *v)?;
+    }
+    write_u32(w, image.stats.threads.len() as u32)?;
+    for thread in &image.stats.threads {
+        write_u64(w, thread.rays)?;
+        write_u64(w, thread.time.as_nanos() as u64)?;
+    }
+    Ok(())
+}
+
+/// Reads an accumulation written by `write_accumulation`, `check` sees its region before
+/// anything is read for its pixels
+pub(crate) fn read_accumulation(
+    r: &mut impl Read,
+    check: impl FnOnce(Region) -> io::Result<()>,
+) -> io::Result<Accumulation> {
+    let region = Region {
+        x: read_u32(r)?,
+        y: read_u32(r)?,
+        width: read_u32(r)?,
+        height: read_u32(r)?,
+    };
+    check(region)?;
+    let size = (region.width as usize)
+        .checked_mul(region.height as usize)
+        .ok_or_else(|| invalid_data("the image is too large"))?;
+
+    let pixels = read_values(r, size, |r| Ok((read_vec3(r)?, read_f32(r)?)))?;
+    let weight_magnitudes = read_values(r, size, read_f32)?;
+    let samples = read_values(r, size, read_u32)?;
+    let cost = read_values(r, size, read_f32)?;
+
+    let layers = read_u32(r)?;
+    let mut aovs = Vec::new();
+    for _ in 0..layers {
+        let aov: Aov = read_string(r)?
+            .parse()
+            .map_err(|e: String| invalid_data(&e))?;
+        let layer = if aov.is_id() {
+            AovLayer::Id(read_values(r, size, |r| Ok((read_u32(r)?, read_f32(r)?)))?)
+        } else {
+            AovLayer::Color(read_values(r, size, read_vec3)?)
+        };
+        aovs.push((aov, layer));
+    }
+
+    let counters = Counters {
+        camera_rays: read_u64(r)?,
+        bounce_rays: read_u64(r)?,
+        bvh_nodes: read_u64(r)?,
+        primitive_tests: read_u64(r)?,
+    };
+    let threads = read_u32(r)? as usize;
+    let threads = read_values(r, threads, |r| {
+        Ok(ThreadStats {
+            rays: read_u64(r)?,
+            time: Duration::from_nanos(read_u64(r)?),
+        })
+    })?;
+
+    Ok(Accumulation {
+        x: region.x,
+        y: region.y,
+        width: region.width,
+        height: region.height,
+        pixels,
+        weight_magnitudes,
+        aovs,
+        samples,
+        cost,
+        stats: RenderStats { counters, threads },
+    })
+}
+
+/// `count` values read one after the other, the count comes from the file so the buffer only
+/// grows as the values are read and a corrupt file runs out of data before taking much memory
+fn read_values<R: Read, T>(
+    r: &mut R,
+    count: usize,
+    read: impl Fn(&mut R) -> io::Result<T>,
+) -> io::Result<Vec<T>> {
+    let mut values = Vec::new();
+    for _ in 0..count {
+        values.push(read(r)?);
+    }
+    Ok(values)
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn write_u32(w: &mut impl Write, v: u32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+pub(crate) fn write_u64(w: &mut impl Write, v: u64) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_f32(w: &mut impl Write, v: f32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_vec3(w: &mut impl Write, v: Vec3) -> io::Result<()> {
+    write_f32(w, v.x)?;
+    write_f32(w, v.y)?;
+    write_f32(w, v.z)
+}
+
+pub(crate) fn write_string(w: &mut impl Write, s: &str) -> io::Result<()> {
+    write_u32(w, s.len() as u32)?;
+    w.write_all(s.as_bytes())
+}
+
+pub(crate) fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_vec3(r: &mut impl Read) -> io::Result<Vec3> {
+    Ok(Vec3::new(read_f32(r)?, read_f32(r)?, read_f32(r)?))
+}
+
+pub(crate) fn read_string(r: &mut impl Read) -> io::Result<String> {
+    // Like the buffers, only as long as the data that is really there
+    let length = read_u32(r)? as usize;
+    let mut bytes = Vec::new();
+    r.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint() -> Checkpoint {
+        let mut image = Accumulation::new(0, 0, 3, 2, &[Aov::Albedo, Aov::ObjectId]);
+        for (i, pixel) in image.pixels.iter_mut().enumerate() {
+            *pixel = (Vec3::new(i as f32, 0.5, -1.), 2. + i as f32);
+        }
+        for (i, magnitude) in image.weight_magnitudes.iter_mut().enumerate() {
+            *magnitude = 3. + i as f32;
+        }
+        image.samples = vec![4; 6];
+        image.cost = vec![0.25; 6];
+        if let (_, AovLayer::Id(ids)) = &mut image.aovs[1] {
+            ids[2] = (7, 0.5);
+        }
+        image.stats.counters.camera_rays = 24;
+        image.stats.threads.push(ThreadStats {
+            rays: 24,
+            time: Duration::from_millis(3),
+        });
+
+        Checkpoint {
+            setup: setup(),
+            sampler_seed: SEED,
+            image,
+        }
+    }
+
+    fn setup() -> RenderSetup {
+        RenderSetup {
+            scene: "cornell_box".to_string(),
+            scene_seed: 1,
+            files_hash: 2,
+            setup_hash: 3,
+            width: 3,
+            height: 2,
+            strata: 16,
+        }
+    }
+
+    fn bytes(checkpoint: &Checkpoint) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let original = bytes(&checkpoint());
+        let read = Checkpoint::read(&mut &original[..]).unwrap();
+        assert_eq!(read.setup, setup());
+        assert_eq!(read.samples(), Ok(4));
+        assert_eq!(read.image.weight_magnitudes[5], 8.);
+        assert_eq!(bytes(&read), original);
+        assert!(read.validate(&setup()).is_ok());
+    }
+
+    #[test]
+    fn truncated_checkpoints_are_errors() {
+        let bytes = bytes(&checkpoint());
+        for length in 0..bytes.len() {
+            let error = Checkpoint::read(&mut &bytes[..length]).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "{}", length);
+        }
+    }
+
+    #[test]
+    fn corrupt_checkpoints_are_errors() {
+        let original = bytes(&checkpoint());
+        let read = |change: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = original.clone();
+            change(&mut bytes);
+            Checkpoint::read(&mut &bytes[..]).err().unwrap().kind()
+        };
+
+        assert_eq!(read(&|b| b[0] = b'X'), io::ErrorKind::InvalidData);
+        assert_eq!(read(&|b| b[4] = 9), io::ErrorKind::InvalidData);
+        // A scene name far longer than the file
+        assert_eq!(
+            read(&|b| b[8..12].copy_from_slice(&u32::MAX.to_le_bytes())),
+            io::ErrorKind::UnexpectedEof
+        );
+        // An image far larger than the file, its width follows the name, three hashes, the
+        // size, the strata, the sampler seed, x and y
+        let width = 12 + "cornell_box".len() + 3 * 8 + 3 * 4 + 8 + 2 * 4;
+        assert_eq!(
+            read(&|b| b[width..width + 8].copy_from_slice(&[0xff; 8])),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn other_renders_do_not_validate() {
+        let checkpoint = checkpoint();
+        let changes: [&dyn Fn(&mut RenderSetup); 4] = [
+            &|s| s.scene = "random".to_string(),
+            &|s| s.files_hash = 5,
+            &|s| s.setup_hash = 5,
+            &|s| s.width = 4,
+        ];
+        for change in changes.iter() {
+            let mut local = setup();
+            change(&mut local);
+            assert!(checkpoint.validate(&local).is_err());
+        }
+
+        // The image must cover the whole render
+        let mut cropped = self::checkpoint();
+        cropped.image = Accumulation::new(1, 0, 2, 2, &[]);
+        assert!(cropped.validate(&setup()).is_err());
+
+        let mut uneven = checkpoint;
+        uneven.image.samples[1] = 5;
+        assert!(uneven.samples().is_err());
+    }
+}
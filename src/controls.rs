@@ -0,0 +1,234 @@
+//! Mouse and keyboard controls of the preview window
+//!
+//! - Left drag orbits around `lookat`, right or middle drag pans and the wheel zooms
+//! - W, A, S, D fly forward, left, back and right, Q and E go down and up
+//! - `+` and `-` double or halve the samples per pixel
+//! - Page up and page down change the maximum depth
+//! - `[` and `]` close or open the aperture, by a stop for a physical lens
+//! - `,` and `.` bring the focus closer or push it away, F focuses on `lookat`
+//! - C prints the camera configuration to paste in a scene
+
+use glam::Quat;
+use winit::{
+    dpi::PhysicalPosition,
+    event::{
+        ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+    },
+};
+
+use raytracing_weekend_rs::{
+    camera::{CameraConfig, CameraType, MIN_FOCUS_DIST},
+    renderer::RenderSettings,
+    vec3::Vec3,
+};
+
+/// Radians turned per pixel dragged
+const ORBIT_SPEED: f32 = 0.005;
+
+/// Fraction of the distance to `lookat` moved per key press
+const FLY_SPEED: f32 = 0.05;
+
+/// Factor applied to the distance to `lookat` per line scrolled
+const ZOOM_FACTOR: f32 = 0.9;
+
+/// Factor applied to the focus distance per key press
+const FOCUS_FACTOR: f32 = 1.1;
+
+pub struct Controls {
+    pub camera: CameraConfig,
+    pub settings: RenderSettings,
+    cursor: Option<PhysicalPosition<f64>>,
+    orbiting: bool,
+    panning: bool,
+}
+
+impl Controls {
+    pub fn new(camera: CameraConfig, settings: RenderSettings) -> Self {
+        Controls {
+            camera,
+            settings,
+            cursor: None,
+            orbiting: false,
+            panning: false,
+        }
+    }
+
+    /// Updates the camera and the settings from `event`, returns whether the render must
+    /// restart
+    pub fn handle(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state, button, .. } => {
+                let pressed = *state == ElementState::Pressed;
+                match button {
+                    MouseButton::Left => self.orbiting = pressed,
+                    MouseButton::Right | MouseButton::Middle => self.panning = pressed,
+                    _ => (),
+                }
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let previous = self.cursor.replace(*position);
+                let (dx, dy) = match previous {
+                    Some(previous) => (
+                        (position.x - previous.x) as f32,
+                        (position.y - previous.y) as f32,
+                    ),
+                    None => return false,
+                };
+                if self.orbiting {
+                    self.orbit(dx, dy);
+                    true
+                } else if self.panning {
+                    self.pan(dx, dy);
+                    true
+                } else {
+                    false
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.,
+                };
+                self.zoom(lines);
+                lines != 0.
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => self.key(*key),
+            _ => false,
+        }
+    }
+
+    fn key(&mut self, key: VirtualKeyCode) -> bool {
+        use VirtualKeyCode::*;
+
+        let forward = (self.camera.lookat - self.camera.lookfrom).normalize();
+        let right = forward.cross(self.camera.vup).normalize();
+        let up = self.camera.vup.normalize();
+        match key {
+            W => self.fly(forward),
+            S => self.fly(-forward),
+            A => self.fly(-right),
+            D => self.fly(right),
+            Q => self.fly(-up),
+            E => self.fly(up),
+            Equals | NumpadAdd => self.settings.num_samples *= 2,
+            Minus | NumpadSubtract => {
+                self.settings.num_samples = (self.settings.num_samples / 2).max(1)
+            }
+            PageUp => self.settings.max_depth += 1,
+            PageDown => self.settings.max_depth = (self.settings.max_depth - 1).max(1),
+            LBracket => self.scale_aperture(0.5),
+            RBracket => self.scale_aperture(2.),
+            Comma => {
+                self.camera.focus_dist = (self.camera.focus_dist / FOCUS_FACTOR).max(MIN_FOCUS_DIST)
+            }
+            Period => {
+                self.camera.focus_dist = (self.camera.focus_dist * FOCUS_FACTOR).max(MIN_FOCUS_DIST)
+            }
+            F => {
+                let lookat = self.camera.lookat;
+                self.camera.focus_on(lookat);
+            }
+            C => {
+                println!("{}", self.camera.builder_code());
+                return false;
+            }
+            _ => return false,
+        }
+        println!(
+            "{} samples, depth {}, aperture {}, focus distance {}",
+            self.settings.num_samples,
+            self.settings.max_depth,
+            self.aperture(),
+            self.camera.focus_dist
+        );
+        true
+    }
+
+    fn distance(&self) -> f32 {
+        (self.camera.lookfrom - self.camera.lookat).length()
+    }
+
+    /// Turns the camera around `lookat`, keeping it from going over the poles
+    fn orbit(&mut self, dx: f32, dy: f32) {
+        let up = self.camera.vup.normalize();
+        let offset = self.camera.lookfrom - self.camera.lookat;
+        let right = up.cross(offset).normalize();
+
+        let angle_to_up = offset.normalize().dot(up).acos();
+        let pitch = (-dy * ORBIT_SPEED).clamp(0.01 - angle_to_up, 3.13 - angle_to_up);
+        let rotation =
+            Quat::from_axis_angle(up, -dx * ORBIT_SPEED) * Quat::from_axis_angle(right, pitch);
+        self.camera.lookfrom = self.camera.lookat + rotation * offset;
+    }
+
+    /// Moves the camera along the view plane so the point under the cursor follows it
+    fn pan(&mut self, dx: f32, dy: f32) {
+        let pixel = match self.camera.camera_type {
+            CameraType::Orthographic { height } => height / self.camera.height as f32,
+            _ => {
+                let vfov = match self.camera.lens {
+                    Some(lens) => lens.vfov(),
+                    None => self.camera.vfov,
+                };
+                2. * (vfov.to_radians() / 2.).tan() * self.distance() / self.camera.height as f32
+            }
+        };
+        let forward = (self.camera.lookat - self.camera.lookfrom).normalize();
+        let right = forward.cross(self.camera.vup).normalize();
+        let up = right.cross(forward);
+        self.translate(pixel * (-dx * right + dy * up));
+    }
+
+    /// Moves the camera toward `lookat`, or shrinks the view of an orthographic camera
+    fn zoom(&mut self, lines: f32) {
+        let factor = ZOOM_FACTOR.powf(lines);
+        match &mut self.camera.camera_type {
+            CameraType::Orthographic { height } => *height *= factor,
+            _ => {
+                let offset = self.camera.lookfrom - self.camera.lookat;
+                self.camera.lookfrom = self.camera.lookat + factor * offset;
+            }
+        }
+    }
+
+    fn fly(&mut self, direction: Vec3) {
+        self.translate(FLY_SPEED * self.distance() * direction);
+    }
+
+    fn translate(&mut self, offset: Vec3) {
+        self.camera.lookfrom += offset;
+        self.camera.lookat += offset;
+    }
+
+    fn aperture(&self) -> f32 {
+        match self.camera.lens {
+            Some(lens) => lens.aperture(),
+            None => self.camera.aperture,
+        }
+    }
+
+    /// Multiplies the area of the aperture by `factor`, an aperture closed to a pinhole opens
+    /// to a hundredth of the distance to `lookat`
+    fn scale_aperture(&mut self, factor: f32) {
+        if let Some(lens) = &mut self.camera.lens {
+            lens.f_stop /= factor.sqrt();
+        } else if self.camera.aperture > 0. {
+            self.camera.aperture *= factor.sqrt();
+        } else if factor > 1. {
+            self.camera.aperture = self.distance() / 100.;
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Edge-avoiding à-trous wavelet filter from Dammertz et al., guided by the albedo and normal
+//! passes, with the per pixel variance estimate of SVGF steering how much each pixel is blurred
+
+use rayon::prelude::*;
+
+use crate::{
+    aov::{Aov, AovBuffer, RenderOutput},
+    vec3::{Vec3, Vec3Wrapper},
+};
+
+/// Weights of the B3 spline the filter is built from
+const KERNEL: [f32; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+
+/// Albedos below this are not divided out of the image
+const MIN_ALBEDO: f32 = 0.001;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Denoiser {
+    /// Number of passes, the kernel spans `4 * 2^iterations` pixels
+    pub iterations: u32,
+    /// Tolerance to luminance differences in standard deviations of the noise
+    pub sigma_luminance: f32,
+    /// Exponent of the cosine between the normals, higher keeps sharper edges
+    pub sigma_normal: f32,
+    /// Tolerance to albedo differences
+    pub sigma_albedo: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_luminance: 4.,
+            sigma_normal: 128.,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+/// Pixel being filtered with its guides
+#[derive(Clone, Copy)]
+struct Pixel {
+    color: Vec3,
+    variance: f32,
+    albedo: Option<Vec3>,
+    normal: Option<Vec3>,
+}
+
+impl Denoiser {
+    /// Denoised beauty image of `output`, its albedo and normal passes are used when present
+    pub fn denoise(&self, output: &RenderOutput) -> Vec<Vec3> {
+        let (width, height) = (output.width as usize, output.height as usize);
+        if width == 0 || height == 0 {
+            return Vec::new();
+        }
+        let guide = |aov| match output.aov(aov) {
+            Some(AovBuffer::Color(values)) => Some(values),
+            _ => None,
+        };
+        let albedo = guide(Aov::Albedo);
+        let normal = guide(Aov::Normal);
+
+        // Filtering the lighting alone keeps the texture details
+        let demodulate = |i: usize| match albedo {
+            Some(albedo) => albedo[i].map(|a| if a < MIN_ALBEDO { 1. } else { a }),
+            None => Vec3::ONE,
+        };
+
+        let colors: Vec<Vec3> = (0..width * height)
+            .map(|i| output.beauty[i] / demodulate(i))
+            .collect();
+        let variances = spatial_variance(&colors, width, height);
+        let mut pixels: Vec<Pixel> = (0..width * height)
+            .map(|i| Pixel {
+                color: colors[i],
+                variance: variances[i],
+                albedo: albedo.map(|a| a[i]),
+                normal: normal.map(|n| n[i]),
+            })
+            .collect();
+
+        for iteration in 0..self.iterations {
+            pixels = self.filter(&pixels, width, height, 1 << iteration);
+        }
+
+        pixels
+            .iter()
+            .enumerate()
+            .map(|(i, p)| p.color * demodulate(i))
+            .collect()
+    }
+
+    /// Single pass of the filter with the kernel taps `step` pixels apart
+    fn filter(&self, pixels: &[Pixel], width: usize, height: usize, step: usize) -> Vec<Pixel> {
+        let mut filtered = pixels.to_vec();
+        filtered
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, out) in row.iter_mut().enumerate() {
+                    let p = pixels[y * width + x];
+                    // Noise left around the pixel, blurred so a lone outlier does not stop the filter
+                    let deviation = self.sigma_luminance
+                        * blurred_variance(pixels, width, height, x, y).sqrt()
+                        + 1e-6;
+
+                    let mut color = Vec3::ZERO;
+                    let mut variance = 0.;
+                    let mut weights = 0.;
+                    for (j, kj) in KERNEL.iter().enumerate() {
+                        for (i, ki) in KERNEL.iter().enumerate() {
+                            let qx = x as i64 + (i as i64 - 2) * step as i64;
+                            let qy = y as i64 + (j as i64 - 2) * step as i64;
+                            if qx < 0 || qy < 0 || qx >= width as i64 || qy >= height as i64 {
+                                continue;
+                            }
+                            let q = pixels[qy as usize * width + qx as usize];
+
+                            let mut weight = (-(luminance(p.color) - luminance(q.color)).abs()
+                                / deviation)
+                                .exp();
+                            if let (Some(np), Some(nq)) = (p.normal, q.normal) {
+                                weight *= np.dot(nq).max(0.).powf(self.sigma_normal);
+                            }
+                            if let (Some(ap), Some(aq)) = (p.albedo, q.albedo) {
+                                weight *= (-(ap - aq).length_squared()
+                                    / (self.sigma_albedo * self.sigma_albedo))
+                                    .exp();
+                            }
+                            let weight = kj * ki * weight;
+
+                            color += weight * q.color;
+                            variance += weight * weight * q.variance;
+                            weights += weight;
+                        }
+                    }
+
+                    // The center pixel always has a weight, unless the kernel underflows
+                    if weights > 0. {
+                        out.color = color / weights;
+                        out.variance = variance / (weights * weights);
+                    }
+                }
+            });
+        filtered
+    }
+}
+
+fn luminance(color: Vec3) -> f32 {
+    color.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+/// Variance of the luminance over the 5x5 pixels around each pixel, an estimate of the noise
+/// when the variance of the samples was not kept
+fn spatial_variance(colors: &[Vec3], width: usize, height: usize) -> Vec<f32> {
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            let mut sum = 0.;
+            let mut sum_squared = 0.;
+            let mut count = 0.;
+            for qy in y.saturating_sub(2)..(y + 3).min(height) {
+                for qx in x.saturating_sub(2)..(x + 3).min(width) {
+                    let l = luminance(colors[qy * width + qx]);
+                    sum += l;
+                    sum_squared += l * l;
+                    count += 1.;
+                }
+            }
+            let mean = sum / count;
+            (sum_squared / count - mean * mean).max(0.)
+        })
+        .collect()
+}
+
+/// Variance of the pixel blurred by a 3x3 gaussian
+fn blurred_variance(pixels: &[Pixel], width: usize, height: usize, x: usize, y: usize) -> f32 {
+    const GAUSSIAN: [f32; 3] = [0.25, 0.5, 0.25];
+
+    let mut variance = 0.;
+    let mut weights = 0.;
+    for (j, kj) in GAUSSIAN.iter().enumerate() {
+        for (i, ki) in GAUSSIAN.iter().enumerate() {
+            let (qx, qy) = ((x + i).wrapping_sub(1), (y + j).wrapping_sub(1));
+            if qx < width && qy < height {
+                variance += kj * ki * pixels[qy * width + qx].variance;
+                weights += kj * ki;
+            }
+        }
+    }
+    variance / weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(
+        width: u32,
+        height: u32,
+        beauty: Vec<Vec3>,
+        aovs: Vec<(Aov, AovBuffer)>,
+    ) -> RenderOutput {
+        RenderOutput {
+            width,
+            height,
+            beauty,
+            aovs,
+        }
+    }
+
+    /// Pseudo random noise in `[-0.5, 0.5)`
+    fn noise(i: usize) -> f32 {
+        crate::sampler::to_unit(crate::sampler::hash(&[i as u32], 3) as u32) - 0.5
+    }
+
+    #[test]
+    fn empty_images_stay_empty() {
+        let denoiser = Denoiser::default();
+        assert!(denoiser
+            .denoise(&output(0, 4, Vec::new(), Vec::new()))
+            .is_empty());
+        assert!(denoiser
+            .denoise(&output(4, 0, Vec::new(), Vec::new()))
+            .is_empty());
+    }
+
+    #[test]
+    fn constant_images_are_unchanged() {
+        let color = Vec3::new(0.2, 0.5, 0.7);
+        let denoised = Denoiser::default().denoise(&output(9, 7, vec![color; 63], Vec::new()));
+        assert_eq!(denoised.len(), 63);
+        assert!(denoised.iter().all(|c| (*c - color).length() < 1e-5));
+    }
+
+    #[test]
+    fn noise_is_reduced_and_normal_edges_kept() {
+        let (width, height) = (32, 32);
+        // Left half faces the camera and is darker than the right half which faces up
+        let left = |i: usize| i % width < width / 2;
+        let beauty: Vec<Vec3> = (0..width * height)
+            .map(|i| Vec3::splat(if left(i) { 0.2 } else { 0.8 } + 0.2 * noise(i)))
+            .collect();
+        let normals = (0..width * height)
+            .map(|i| if left(i) { Vec3::Z } else { Vec3::Y })
+            .collect();
+        let output = output(
+            width as u32,
+            height as u32,
+            beauty.clone(),
+            vec![(Aov::Normal, AovBuffer::Color(normals))],
+        );
+        let denoised = Denoiser::default().denoise(&output);
+
+        let error = |pixels: &[Vec3]| -> f32 {
+            pixels
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (c.x - if left(i) { 0.2 } else { 0.8 }).powi(2))
+                .sum()
+        };
+        assert!(error(&denoised) < 0.1 * error(&beauty));
+        // Pixels on each side of the edge are not blurred into each other
+        for y in 0..height {
+            let row = y * width + width / 2;
+            assert!((denoised[row - 1].x - 0.2).abs() < 0.1);
+            assert!((denoised[row].x - 0.8).abs() < 0.1);
+        }
+    }
+}
@@ -0,0 +1,694 @@
+//! Rendering an image across worker processes, on this machine or others
+//!
+//! The coordinator listens for workers, sends each the setup of the render, then hands out work
+//! units one at a time: regions of the image with a range of samples. Workers load the scene
+//! themselves and send back the accumulated samples, which add up on the coordinator like the
+//! tiles of a local render. Workers send a heartbeat while they load the scene or render, so a
+//! unit can take hours, and the unit of a worker that disconnects or goes silent goes back to
+//! the others.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    ops::Range,
+    str::FromStr,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Condvar, Mutex, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    camera::Camera,
+    checkpoint::{
+        invalid_data, read_accumulation, read_string, read_u32, write_accumulation, write_string,
+        write_u32, RenderSetup,
+    },
+    hittable::Hittables,
+    renderer::{render_region, Accumulation, Region, RenderSettings},
+};
+
+/// Side of the square regions handed out when the image is split in tiles
+const TILE_SIZE: u32 = 64;
+
+/// Number of units the samples are split in when each unit covers the whole image
+const SAMPLE_BATCHES: u32 = 16;
+
+/// Times a unit is handed out before giving up, in case the unit itself kills the workers
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Time between two heartbeats of a worker loading the scene or rendering a unit
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time a worker can stay silent before it is considered gone
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Time between two checks for new workers
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Split {
+    /// Regions of the image with all their samples
+    Tiles,
+    /// The whole image with a range of its samples
+    Samples,
+}
+
+impl FromStr for Split {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "tiles" => Ok(Split::Tiles),
+            "samples" => Ok(Split::Samples),
+            _ => Err(format!("unknown split {}, expected tiles or samples", name)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkUnit {
+    pub region: Region,
+    pub samples: Range<u32>,
+}
+
+/// Units covering the samples in `samples` of every pixel of a `width` by `height` image
+pub fn split(split: Split, width: u32, height: u32, samples: Range<u32>) -> Vec<WorkUnit> {
+    match split {
+        Split::Tiles => (0..height)
+            .step_by(TILE_SIZE as usize)
+            .flat_map(|y| {
+                let samples = samples.clone();
+                (0..width)
+                    .step_by(TILE_SIZE as usize)
+                    .map(move |x| WorkUnit {
+                        region: Region {
+                            x,
+                            y,
+                            width: TILE_SIZE.min(width - x),
+                            height: TILE_SIZE.min(height - y),
+                        },
+                        samples: samples.clone(),
+                    })
+            })
+            .collect(),
+        Split::Samples => {
+            let (count, end) = (samples.end.saturating_sub(samples.start), samples.end);
+            let batch = count.div_ceil(SAMPLE_BATCHES).max(1);
+            samples
+                .step_by(batch as usize)
+                .map(|start| WorkUnit {
+                    region: Region {
+                        x: 0,
+                        y: 0,
+                        width,
+                        height,
+                    },
+                    samples: start..(start + batch).min(end),
+                })
+                .collect()
+        }
+    }
+}
+
+/// Messages from the coordinator
+enum Request {
+    Setup(RenderSetup),
+    Work(WorkUnit),
+}
+
+/// Messages from a worker
+enum Response {
+    Ready,
+    /// The worker cannot render the setup it was sent
+    Refused(String),
+    Done(Accumulation),
+    /// Heartbeat of a worker still loading the scene or rendering
+    Working,
+}
+
+impl Request {
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        match self {
+            Request::Setup(setup) => {
+                w.write_all(&[0])?;
+                setup.write(w)?;
+            }
+            Request::Work(unit) => {
+                w.write_all(&[1])?;
+                let region = unit.region;
+                for v in [region.x, region.y, region.width, region.height].iter() {
+                    write_u32(w, *v)?;
+                }
+                write_u32(w, unit.samples.start)?;
+                write_u32(w, unit.samples.end)?;
+            }
+        }
+        w.flush()
+    }
+
+    /// Next request, `None` once the coordinator hung up
+    fn read(r: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut tag = [0];
+        match r.read_exact(&mut tag) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let request = match tag[0] {
+            0 => Request::Setup(RenderSetup::read(r)?),
+            1 => Request::Work(WorkUnit {
+                region: Region {
+                    x: read_u32(r)?,
+                    y: read_u32(r)?,
+                    width: read_u32(r)?,
+                    height: read_u32(r)?,
+                },
+                samples: read_u32(r)?..read_u32(r)?,
+            }),
+            _ => return Err(invalid_data("unknown request")),
+        };
+        Ok(Some(request))
+    }
+}
+
+impl Response {
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        match self {
+            Response::Ready => w.write_all(&[0])?,
+            Response::Refused(reason) => {
+                w.write_all(&[1])?;
+                write_string(w, reason)?;
+            }
+            Response::Done(accumulation) => {
+                w.write_all(&[2])?;
+                write_accumulation(w, accumulation)?;
+            }
+            Response::Working => w.write_all(&[3])?,
+        }
+        w.flush()
+    }
+
+    /// Next response other than a heartbeat, the samples are only read if they are of
+    /// `expected`
+    fn read(r: &mut impl Read, expected: Option<Region>) -> io::Result<Self> {
+        let mut tag = [0];
+        // Heartbeats only keep the connection alive
+        loop {
+            r.read_exact(&mut tag)?;
+            if tag[0] != 3 {
+                break;
+            }
+        }
+        match tag[0] {
+            0 => Ok(Response::Ready),
+            1 => Ok(Response::Refused(read_string(r)?)),
+            2 => Ok(Response::Done(read_accumulation(
+                r,
+                |region| match expected {
+                    Some(expected) if expected == region => Ok(()),
+                    Some(expected) => Err(invalid_data(&format!(
+                        "samples of {:?} instead of {:?}",
+                        region, expected
+                    ))),
+                    None => Err(invalid_data("unexpected samples")),
+                },
+            )?)),
+            _ => Err(invalid_data("unknown response")),
+        }
+    }
+}
+
+/// Connects to the coordinator at `address` and renders the units it sends until it hangs up,
+/// `prepare` loads the scene of the setup it sends first or tells why it cannot
+pub fn work(
+    address: impl ToSocketAddrs,
+    prepare: impl FnOnce(&RenderSetup) -> Result<(Hittables, Camera, RenderSettings), String>,
+) -> Result<(), String> {
+    let stream = TcpStream::connect(address).map_err(|e| e.to_string())?;
+    stream.set_nodelay(true).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let mut writer = BufWriter::new(stream);
+
+    let setup = match Request::read(&mut reader) {
+        Ok(Some(Request::Setup(setup))) => setup,
+        Ok(_) => return Err("the coordinator did not send the setup first".to_string()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let (world, camera, settings) = match heartbeat(&mut writer, || prepare(&setup)) {
+        Ok(prepared) => prepared,
+        Err(reason) => {
+            // The coordinator only needs to know, it may have other workers
+            let _ = Response::Refused(reason.clone()).write(&mut writer);
+            return Err(reason);
+        }
+    };
+    Response::Ready
+        .write(&mut writer)
+        .map_err(|e| e.to_string())?;
+
+    loop {
+        match Request::read(&mut reader).map_err(|e| e.to_string())? {
+            Some(Request::Work(unit)) => {
+                let accumulation = heartbeat(&mut writer, || {
+                    render_region(&camera, &world, &settings, unit.region, unit.samples)
+                });
+                Response::Done(accumulation)
+                    .write(&mut writer)
+                    .map_err(|e| e.to_string())?;
+            }
+            Some(Request::Setup(_)) => return Err("the setup was sent twice".to_string()),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Runs `f` while sending a heartbeat to the coordinator every `HEARTBEAT_INTERVAL`
+fn heartbeat<T>(writer: &mut (impl Write + Send), f: impl FnOnce() -> T) -> T {
+    let (stop, stopped) = mpsc::channel::<()>();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(HEARTBEAT_INTERVAL) {
+                // A broken connection shows when the result is sent
+                if Response::Working.write(writer).is_err() {
+                    break;
+                }
+            }
+        });
+        let result = f();
+        drop(stop);
+        result
+    })
+}
+
+/// Units left to render, shared by the threads talking to the workers
+#[derive(Default)]
+struct Queue {
+    /// Units with the number of times they were handed out
+    units: VecDeque<(WorkUnit, u32)>,
+    /// Workers that accepted the setup and are still connected
+    workers: usize,
+    finished: bool,
+}
+
+enum Event {
+    Done(Accumulation),
+    Failed(String),
+    Report(Report),
+}
+
+/// What happens during a distributed render, for the caller of `coordinate` to show
+#[derive(Clone, Debug, PartialEq)]
+pub enum Report {
+    /// The worker at this address accepted the setup
+    Joined(String),
+    /// The worker at this address cannot render the setup, with the reason
+    Refused(String, String),
+    /// The worker at this address failed or went silent, its unit goes back to the others
+    Lost(String, String),
+    /// Units rendered so far out of all of them
+    Progress(usize, usize),
+}
+
+impl Report {
+    /// Whether this is about something going wrong
+    pub fn is_error(&self) -> bool {
+        matches!(self, Report::Refused(..) | Report::Lost(..))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Report::Joined(address) => write!(f, "Worker {} joined", address),
+            Report::Refused(address, reason) => {
+                write!(f, "Worker {} refused the render: {}", address, reason)
+            }
+            Report::Lost(address, reason) => write!(f, "Lost worker {}: {}", address, reason),
+            Report::Progress(done, total) => write!(f, "{}/{} units rendered", done, total),
+        }
+    }
+}
+
+/// Renders `units` on the workers connecting to `listener` and adds their samples to `image`,
+/// `workers_may_come` tells whether to keep waiting while no worker is connected and `report`
+/// hears of the workers and the progress
+pub fn coordinate(
+    listener: TcpListener,
+    setup: &RenderSetup,
+    settings: &RenderSettings,
+    units: Vec<WorkUnit>,
+    image: &mut Accumulation,
+    mut workers_may_come: impl FnMut() -> bool,
+    mut report: impl FnMut(Report),
+) -> Result<(), String> {
+    let total = units.len();
+    let queue = Arc::new((
+        Mutex::new(Queue {
+            units: units.into_iter().map(|unit| (unit, 0)).collect(),
+            ..Queue::default()
+        }),
+        Condvar::new(),
+    ));
+    let (events, received) = mpsc::channel();
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+    let result = (|| {
+        let mut done = 0;
+        while done < total {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let setup = setup.clone();
+                    let settings = settings.clone();
+                    let queue = queue.clone();
+                    let events = events.clone();
+                    thread::spawn(move || serve(stream, &setup, &settings, &queue, &events));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.to_string()),
+            }
+
+            match received.recv_timeout(POLL_INTERVAL) {
+                Ok(Event::Done(accumulation)) => {
+                    image.add(&accumulation);
+                    done += 1;
+                    report(Report::Progress(done, total));
+                }
+                Ok(Event::Failed(reason)) => return Err(reason),
+                Ok(Event::Report(message)) => report(message),
+                Err(RecvTimeoutError::Timeout) => {
+                    if queue.0.lock().unwrap().workers == 0 && !workers_may_come() {
+                        return Err(format!(
+                            "no worker left with {} units to render",
+                            total - done
+                        ));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => unreachable!(),
+            }
+        }
+        Ok(())
+    })();
+
+    // Workers hang up on their own once their connection closes
+    let (lock, available) = &*queue;
+    lock.lock().unwrap().finished = true;
+    available.notify_all();
+    result
+}
+
+/// A worker that accepted the setup, counted in the queue until it is dropped
+///
+/// Dropping it puts the unit it was rendering back in the queue on every way out of `serve`, so
+/// a worker lost at any point never leaves the coordinator waiting for it.
+struct Joined<'a> {
+    queue: &'a (Mutex<Queue>, Condvar),
+    events: &'a Sender<Event>,
+    /// Unit handed to the worker and not rendered yet, with the times it was handed out before
+    unit: Option<(WorkUnit, u32)>,
+}
+
+impl<'a> Joined<'a> {
+    fn new(queue: &'a (Mutex<Queue>, Condvar), events: &'a Sender<Event>) -> Self {
+        queue.0.lock().unwrap().workers += 1;
+        Joined {
+            queue,
+            events,
+            unit: None,
+        }
+    }
+
+    /// Next unit for the worker, `None` once the image is finished
+    fn next_unit(&mut self) -> Option<WorkUnit> {
+        let (lock, available) = self.queue;
+        let mut queue = lock.lock().unwrap();
+        while queue.units.is_empty() && !queue.finished {
+            queue = available.wait(queue).unwrap();
+        }
+        if queue.finished {
+            return None;
+        }
+        self.unit = queue.units.pop_front();
+        self.unit.as_ref().map(|(unit, _)| unit.clone())
+    }
+
+    /// The unit was rendered, it no longer goes back to the queue
+    fn unit_done(&mut self) {
+        self.unit = None;
+    }
+}
+
+impl Drop for Joined<'_> {
+    fn drop(&mut self) {
+        let (lock, available) = self.queue;
+        let mut queue = lock.lock().unwrap_or_else(PoisonError::into_inner);
+        queue.workers -= 1;
+        if let Some((unit, attempts)) = self.unit.take() {
+            if attempts + 1 >= MAX_ATTEMPTS {
+                let _ = self.events.send(Event::Failed(format!(
+                    "{:?} failed on {} workers",
+                    unit, MAX_ATTEMPTS
+                )));
+            } else {
+                queue.units.push_back((unit, attempts + 1));
+                available.notify_one();
+            }
+        }
+    }
+}
+
+/// Sends units to the worker connected to `stream` until there are none left or it fails
+fn serve(
+    stream: TcpStream,
+    setup: &RenderSetup,
+    settings: &RenderSettings,
+    queue: &(Mutex<Queue>, Condvar),
+    events: &Sender<Event>,
+) {
+    let address = stream
+        .peer_addr()
+        .map_or_else(|_| "?".to_string(), |a| a.to_string());
+
+    let connect = || -> io::Result<_> {
+        // Accepted sockets may inherit the mode of the listener
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream.try_clone()?);
+        Request::Setup(setup.clone()).write(&mut writer)?;
+        Ok((Response::read(&mut reader, None)?, reader, writer))
+    };
+    // The coordinator only stops listening once the image is finished
+    let report = |report: Report| {
+        let _ = events.send(Event::Report(report));
+    };
+    let (mut reader, mut writer) = match connect() {
+        Ok((Response::Ready, reader, writer)) => (reader, writer),
+        Ok((Response::Refused(reason), _, _)) => return report(Report::Refused(address, reason)),
+        Ok(_) => return report(Report::Lost(address, "no answer to the setup".to_string())),
+        Err(e) => return report(Report::Lost(address, e.to_string())),
+    };
+    report(Report::Joined(address.clone()));
+    let mut worker = Joined::new(queue, events);
+
+    while let Some(unit) = worker.next_unit() {
+        let expected = unit
+            .region
+            .with_margin(&settings.filter, setup.width, setup.height);
+        let result = Request::Work(unit)
+            .write(&mut writer)
+            .and_then(|_| Response::read(&mut reader, Some(expected)))
+            .and_then(|response| match response {
+                Response::Done(accumulation) if has_aovs(&accumulation, settings) => {
+                    Ok(accumulation)
+                }
+                _ => Err(invalid_data("expected the samples of the unit")),
+            });
+        match result {
+            Ok(accumulation) => {
+                worker.unit_done();
+                if events.send(Event::Done(accumulation)).is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                let reason = match e.kind() {
+                    io::ErrorKind::UnexpectedEof => "the connection closed".to_string(),
+                    _ => e.to_string(),
+                };
+                report(Report::Lost(address, reason));
+                break;
+            }
+        }
+    }
+}
+
+/// Whether `accumulation` holds the passes of `settings`, so adding it cannot panic
+fn has_aovs(accumulation: &Accumulation, settings: &RenderSettings) -> bool {
+    accumulation
+        .aovs
+        .iter()
+        .map(|(aov, _)| *aov)
+        .eq(settings.aovs.iter().copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> RenderSetup {
+        RenderSetup {
+            scene: "cornell_box".to_string(),
+            scene_seed: 1,
+            files_hash: 2,
+            setup_hash: 3,
+            width: 100,
+            height: 70,
+            strata: 16,
+        }
+    }
+
+    fn region() -> Region {
+        Region {
+            x: 64,
+            y: 64,
+            width: 36,
+            height: 6,
+        }
+    }
+
+    fn bytes(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn done() -> Vec<u8> {
+        let mut accumulation = Accumulation::new(64, 64, 36, 6, &[]);
+        accumulation.samples = vec![3; 36 * 6];
+        bytes(|w| Response::Done(accumulation).write(w))
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let unit = WorkUnit {
+            region: region(),
+            samples: 4..9,
+        };
+        let mut stream = bytes(|w| Request::Setup(setup()).write(w));
+        stream.extend(bytes(|w| Request::Work(unit.clone()).write(w)));
+
+        let r = &mut &stream[..];
+        assert!(matches!(Request::read(r), Ok(Some(Request::Setup(s))) if s == setup()));
+        assert!(matches!(Request::read(r), Ok(Some(Request::Work(u))) if u == unit));
+        // The coordinator hung up
+        assert!(matches!(Request::read(r), Ok(None)));
+    }
+
+    #[test]
+    fn responses_round_trip_past_heartbeats() {
+        let mut stream = bytes(|w| Response::Working.write(w));
+        stream.extend(bytes(|w| Response::Ready.write(w)));
+        stream.extend(bytes(|w| Response::Working.write(w)));
+        stream.extend(bytes(|w| {
+            Response::Refused("no scene".to_string()).write(w)
+        }));
+        stream.extend(done());
+
+        let r = &mut &stream[..];
+        assert!(matches!(Response::read(r, None), Ok(Response::Ready)));
+        assert!(matches!(Response::read(r, None), Ok(Response::Refused(s)) if s == "no scene"));
+        match Response::read(r, Some(region())) {
+            Ok(Response::Done(accumulation)) => {
+                assert_eq!((accumulation.x, accumulation.width), (64, 36));
+                assert!(accumulation.samples.iter().all(|s| *s == 3));
+            }
+            _ => panic!("expected the samples"),
+        }
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn truncated_messages_are_errors() {
+        let unit = WorkUnit {
+            region: region(),
+            samples: 0..1,
+        };
+        for request in [Request::Setup(setup()), Request::Work(unit)].iter() {
+            let stream = bytes(|w| request.write(w));
+            // An empty stream is a hang up, anything else cut short is an error
+            for length in 1..stream.len() {
+                let error = Request::read(&mut &stream[..length]).err().unwrap();
+                assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "{}", length);
+            }
+        }
+
+        let stream = done();
+        for length in 0..stream.len() {
+            let error = Response::read(&mut &stream[..length], Some(region()))
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "{}", length);
+        }
+    }
+
+    #[test]
+    fn corrupt_messages_are_errors() {
+        let kind = |result: io::Result<Response>| result.err().unwrap().kind();
+        assert!(Request::read(&mut &[7u8][..]).is_err());
+        assert_eq!(
+            kind(Response::read(&mut &[7u8][..], None)),
+            io::ErrorKind::InvalidData
+        );
+
+        // Samples of another unit, or when none were asked for
+        let stream = done();
+        let other = Region { x: 0, ..region() };
+        assert_eq!(
+            kind(Response::read(&mut &stream[..], Some(other))),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(Response::read(&mut &stream[..], None)),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn units_cover_every_sample_once() {
+        for (width, height) in [(100, 70), (64, 128), (1, 1), (130, 3)].iter().copied() {
+            for samples in [0..16, 5..8, 3..100, 0..1].iter() {
+                for split_by in [Split::Tiles, Split::Samples].iter().copied() {
+                    let mut count = vec![0; (width * height * samples.end) as usize];
+                    for unit in split(split_by, width, height, samples.clone()) {
+                        let r = unit.region;
+                        assert!(r.x + r.width <= width && r.y + r.height <= height);
+                        for y in r.y..r.y + r.height {
+                            for x in r.x..r.x + r.width {
+                                for s in unit.samples.clone() {
+                                    count[((y * width + x) * samples.end + s) as usize] += 1;
+                                }
+                            }
+                        }
+                    }
+                    for (i, c) in count.iter().enumerate() {
+                        let s = i as u32 % samples.end;
+                        let expected = if samples.contains(&s) { 1 } else { 0 };
+                        assert_eq!(*c, expected, "{:?} {}x{}", split_by, width, height);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn splits_are_parsed() {
+        assert_eq!("tiles".parse(), Ok(Split::Tiles));
+        assert_eq!("Samples".parse(), Ok(Split::Samples));
+        assert!("pixels".parse::<Split>().is_err());
+    }
+}
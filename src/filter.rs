@@ -0,0 +1,207 @@
+use std::{f32::consts::PI, str::FromStr};
+
+/// Reconstruction filter weighting the samples around each pixel, `radius` is in pixels
+///
+/// The filters are separable, the weight of a sample is the product of the 1D filter along
+/// both axes. Mitchell and Lanczos have negative lobes which sharpen the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Box {
+        radius: f32,
+    },
+    Tent {
+        radius: f32,
+    },
+    /// `alpha` controls the falloff, the curve is shifted down to reach 0 at `radius`
+    Gaussian {
+        radius: f32,
+        alpha: f32,
+    },
+    /// `b` and `c` of the Mitchell-Netravali family, 1/3 each is the usual compromise between
+    /// blurring and ringing
+    Mitchell {
+        radius: f32,
+        b: f32,
+        c: f32,
+    },
+    /// Sinc windowed by a sinc stretched to `radius`
+    Lanczos {
+        radius: f32,
+    },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => radius,
+        }
+    }
+
+    pub fn with_radius(self, radius: f32) -> Self {
+        match self {
+            Filter::Box { .. } => Filter::Box { radius },
+            Filter::Tent { .. } => Filter::Tent { radius },
+            Filter::Gaussian { alpha, .. } => Filter::Gaussian { radius, alpha },
+            Filter::Mitchell { b, c, .. } => Filter::Mitchell { radius, b, c },
+            Filter::Lanczos { .. } => Filter::Lanczos { radius },
+        }
+    }
+
+    /// Weight of a sample at `(x, y)` pixels from the center of a pixel
+    pub fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match *self {
+            Filter::Box { radius } => {
+                if x < radius {
+                    1.
+                } else {
+                    0.
+                }
+            }
+            Filter::Tent { radius } => (radius - x).max(0.),
+            Filter::Gaussian { radius, alpha } => {
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                if x >= radius {
+                    return 0.;
+                }
+                // The cubic spans [-2, 2]
+                let x = 2. * x / radius;
+                if x > 1. {
+                    ((-b - 6. * c) * x * x * x
+                        + (6. * b + 30. * c) * x * x
+                        + (-12. * b - 48. * c) * x
+                        + (8. * b + 24. * c))
+                        / 6.
+                } else {
+                    ((12. - 9. * b - 6. * c) * x * x * x
+                        + (-18. + 12. * b + 6. * c) * x * x
+                        + (6. - 2. * b))
+                        / 6.
+                }
+            }
+            Filter::Lanczos { radius } => {
+                if x >= radius {
+                    0.
+                } else {
+                    sinc(x) * sinc(x / radius)
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x < 1e-5 {
+        1.
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Parses the name of a filter with its usual radius, like `mitchell`
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(match name.to_lowercase().as_str() {
+            "box" => Filter::default(),
+            "tent" | "triangle" => Filter::Tent { radius: 1. },
+            "gaussian" => Filter::Gaussian {
+                radius: 1.5,
+                alpha: 2.,
+            },
+            "mitchell" => Filter::Mitchell {
+                radius: 2.,
+                b: 1. / 3.,
+                c: 1. / 3.,
+            },
+            "lanczos" => Filter::Lanczos { radius: 3. },
+            _ => {
+                return Err(format!(
+                    "unknown filter {}, expected box, tent, gaussian, mitchell or lanczos",
+                    name
+                ))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> Vec<Filter> {
+        ["box", "tent", "gaussian", "mitchell", "lanczos"]
+            .iter()
+            .map(|name| name.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn parses_names() {
+        assert_eq!("Mitchell".parse::<Filter>().unwrap().radius(), 2.);
+        assert_eq!("triangle".parse(), Ok(Filter::Tent { radius: 1. }));
+        assert!("sinc".parse::<Filter>().is_err());
+        assert_eq!(
+            Filter::default().with_radius(1.),
+            Filter::Box { radius: 1. }
+        );
+    }
+
+    #[test]
+    fn weights_are_symmetric_and_vanish_at_the_radius() {
+        for filter in filters() {
+            let radius = filter.radius();
+            assert!(filter.evaluate(0., 0.) > 0., "{:?}", filter);
+            assert_eq!(filter.evaluate(radius, 0.), 0., "{:?}", filter);
+            assert_eq!(filter.evaluate(0.1, radius + 0.1), 0., "{:?}", filter);
+            assert_eq!(filter.evaluate(0.3, -0.7), filter.evaluate(-0.3, 0.7));
+        }
+    }
+
+    #[test]
+    fn pixel_weights_sum_to_a_constant() {
+        // Box, tent and Mitchell with b + 2c = 1 weight pixel centres as a partition of unity,
+        // the others stay close to it
+        for filter in filters() {
+            let sums: Vec<f32> = (0..32)
+                .map(|s| {
+                    let x = (s as f32 + 0.5) / 32.;
+                    (-4..=4)
+                        .map(|i| filter.evaluate_1d(x - (i as f32 + 0.5)))
+                        .sum()
+                })
+                .collect();
+            let min = sums.iter().copied().fold(f32::MAX, f32::min);
+            let max = sums.iter().copied().fold(f32::MIN, f32::max);
+            assert!(min > 0., "{:?}", filter);
+            let tolerance = match filter {
+                Filter::Gaussian { .. } | Filter::Lanczos { .. } => 0.1,
+                _ => 1e-4,
+            };
+            assert!(
+                (max - min) / max < tolerance,
+                "{:?} {} {}",
+                filter,
+                min,
+                max
+            );
+        }
+    }
+}
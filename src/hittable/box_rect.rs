@@ -1,4 +1,7 @@
-use super::{rect::StaticAxis, FlipNormals, Hittable, HittableList, Hittables, Rect, AABB};
+use super::{
+    rect::{Facing, StaticAxis},
+    Hittable, HittableList, Hittables, Rect, AABB,
+};
 use crate::{material::MaterialType, vec3::Vec3};
 
 #[derive(Clone)]
@@ -18,13 +21,14 @@ impl BoxRect {
                 StaticAxis::Z,
                 material.clone(),
             ),
-            FlipNormals::new(Rect::new(
+            Rect::new_facing(
                 p0.x..p1.x,
                 p0.y..p1.y,
                 p0.z,
                 StaticAxis::Z,
+                Facing::Negative,
                 material.clone(),
-            )),
+            ),
             Rect::new(
                 p0.x..p1.x,
                 p0.z..p1.z,
@@ -32,13 +36,14 @@ impl BoxRect {
                 StaticAxis::Y,
                 material.clone(),
             ),
-            FlipNormals::new(Rect::new(
+            Rect::new_facing(
                 p0.x..p1.x,
                 p0.z..p1.z,
                 p0.y,
                 StaticAxis::Y,
+                Facing::Negative,
                 material.clone(),
-            )),
+            ),
             Rect::new(
                 p0.y..p1.y,
                 p0.z..p1.z,
@@ -46,13 +51,14 @@ impl BoxRect {
                 StaticAxis::X,
                 material.clone(),
             ),
-            FlipNormals::new(Rect::new(
+            Rect::new_facing(
                 p0.y..p1.y,
                 p0.z..p1.z,
                 p0.x,
                 StaticAxis::X,
+                Facing::Negative,
                 material,
-            )),
+            ),
         ]);
 
         Hittables::from(BoxRect {
@@ -4,6 +4,7 @@ use crate::{
         HitRecord, Hittable, Hittables,
     },
     ray::Ray,
+    stats,
 };
 use std::cmp::Ordering;
 
@@ -16,8 +17,11 @@ pub struct BvhNode {
 
 impl Hittable for BvhNode {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        stats::count(|c| c.bvh_nodes += 1);
         match self.bounding_box.hit(ray, t_min, t_max) {
             Some((t_min, t_max)) => {
+                let primitives = self.left.is_primitive() as u64 + self.right.is_primitive() as u64;
+                stats::count(|c| c.primitive_tests += primitives);
                 let left_rect = self.left.hit(ray, t_min, t_max);
                 let right_rec = self.right.hit(ray, t_min, t_max);
 
@@ -58,6 +62,10 @@ fn cmp_sort(a: &Hittables, b: &Hittables, index: usize) -> Ordering {
 
 impl BvhNode {
     pub fn new(list: Vec<Hittables>, time0: f32, time1: f32, depth: i32) -> Hittables {
+        stats::time_bvh_build(|| BvhNode::build(list, time0, time1, depth))
+    }
+
+    fn build(list: Vec<Hittables>, time0: f32, time1: f32, depth: i32) -> Hittables {
         let axis = depth % 3;
         let mut list = list;
         let n = list.len();
@@ -76,8 +84,8 @@ impl BvhNode {
                 let left_list = list[..(half as usize)].to_vec();
                 let right_list = list[(half as usize)..].to_vec();
                 (
-                    BvhNode::new(left_list, time0, time1, depth + 1),
-                    BvhNode::new(right_list, time0, time1, depth + 1),
+                    BvhNode::build(left_list, time0, time1, depth + 1),
+                    BvhNode::build(right_list, time0, time1, depth + 1),
                 )
             }
         };
@@ -0,0 +1,145 @@
+use super::{aabb::AABB, get_azimuth, HitRecord, Hittable, Hittables};
+use crate::{material::MaterialType, ray::Ray, vec3::Vec3};
+
+/// Cone along the y axis closed by its base, `center` is the middle of the base and the apex is
+/// `height` above it
+///
+/// On the side `u` goes around the axis and `v` from the base to the apex, on the base `v` goes
+/// from the axis to the rim.
+#[derive(Clone)]
+pub struct Cone {
+    center: Vec3,
+    radius: f32,
+    height: f32,
+    material: MaterialType,
+}
+
+impl Cone {
+    pub fn new(center: Vec3, radius: f32, height: f32, material: MaterialType) -> Hittables {
+        Hittables::from(Cone {
+            center,
+            radius,
+            height,
+            material,
+        })
+    }
+
+    fn side_hit(&self, r: &Ray, t: f32) -> HitRecord {
+        let local = r.point_at(t) - self.center;
+        let (u, dpdu) = get_azimuth(local);
+        let slope = self.radius / self.height;
+
+        // Gradient of x^2 + z^2 - slope^2 (height - y)^2
+        let normal = Vec3::new(local.x, slope * slope * (self.height - local.y), local.z);
+        let normal = if normal.length_squared() > 0. {
+            normal.normalize()
+        } else {
+            Vec3::Y
+        };
+
+        let distance = (local.x * local.x + local.z * local.z).sqrt();
+        let dpdv = if distance > 0. {
+            Vec3::new(
+                -self.radius * local.x / distance,
+                self.height,
+                -self.radius * local.z / distance,
+            )
+        } else {
+            Vec3::new(-self.radius, self.height, 0.)
+        };
+
+        HitRecord::new(
+            t,
+            u,
+            local.y / self.height,
+            r.point_at(t),
+            normal,
+            dpdu,
+            dpdv,
+            &self.material,
+        )
+        .with_face_normal(r)
+    }
+
+    fn base_hit(&self, r: &Ray, t: f32) -> HitRecord {
+        let local = r.point_at(t) - self.center;
+        let (u, dpdu) = get_azimuth(local);
+        let radial = Vec3::new(local.x, 0., local.z);
+        let distance = radial.length();
+        let dpdv = if distance > 0. {
+            self.radius / distance * radial
+        } else {
+            Vec3::new(self.radius, 0., 0.)
+        };
+
+        HitRecord::new(
+            t,
+            u,
+            distance / self.radius,
+            r.point_at(t),
+            -Vec3::Y,
+            dpdu,
+            dpdv,
+            &self.material,
+        )
+        .with_face_normal(r)
+    }
+}
+
+impl Hittable for Cone {
+    #[allow(clippy::many_single_char_names)]
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let o = r.origin - self.center;
+        let d = r.direction;
+        let k2 = (self.radius / self.height).powi(2);
+        let w = self.height - o.y;
+        let mut closest = t_max;
+        let mut result = None;
+
+        // (o + t d) on x^2 + z^2 = k^2 (height - y)^2, as a t^2 + 2 b t + c = 0
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let b = o.x * d.x + o.z * d.z + k2 * w * d.y;
+        let c = o.x * o.x + o.z * o.z - k2 * w * w;
+        let roots = if a.abs() < 1e-8 {
+            // Ray parallel to the slope, only one intersection
+            [-c / (2. * b), f32::INFINITY]
+        } else {
+            let discriminant = b * b - a * c;
+            if discriminant < 0. {
+                [f32::INFINITY; 2]
+            } else {
+                let root = discriminant.sqrt();
+                let (t0, t1) = ((-b - root) / a, (-b + root) / a);
+                [t0.min(t1), t0.max(t1)]
+            }
+        };
+
+        for t in roots.iter().copied() {
+            // The equation also describes the mirrored cone above the apex
+            let y = o.y + t * d.y;
+            if t > t_min && t < closest && y >= 0. && y <= self.height {
+                closest = t;
+                result = Some(self.side_hit(r, t));
+                break;
+            }
+        }
+
+        if d.y != 0. {
+            let t = -o.y / d.y;
+            let x = o.x + t * d.x;
+            let z = o.z + t * d.z;
+            if t > t_min && t < closest && x * x + z * z <= self.radius * self.radius {
+                result = Some(self.base_hit(r, t));
+            }
+        }
+
+        result
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(AABB {
+            min: self.center - Vec3::new(self.radius, 0., self.radius),
+            max: self.center + Vec3::new(self.radius, self.height, self.radius),
+        })
+    }
+}
@@ -1,12 +1,10 @@
 use super::{HitRecord, Hittable, Hittables};
 use crate::{
     material::{Isotropic, MaterialType},
-    random::random_double,
     ray::Ray,
     texture::TextureType,
     vec3::{Vec3, Vec3Wrapper},
 };
-use rand::{rngs::SmallRng, SeedableRng};
 
 #[derive(Clone)]
 pub struct ConstantMedium {
@@ -28,8 +26,6 @@ impl ConstantMedium {
 
 impl Hittable for ConstantMedium {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<super::HitRecord> {
-        let rng = &mut SmallRng::from_entropy();
-
         if let Some(rec1) = self.boundary.hit(r, std::f32::MIN, std::f32::MAX) {
             if let Some(rec2) = self.boundary.hit(r, rec1.t + 0.0001, std::f32::MAX) {
                 let mut rec1 = rec1;
@@ -52,7 +48,7 @@ impl Hittable for ConstantMedium {
                 }
 
                 let distance_inside_boundary = (rec2.t - rec1.t) * r.direction.length();
-                let hit_distance = -(1. / self.density) * random_double(rng).ln();
+                let hit_distance = -(1. / self.density) * (1. - r.medium_sample).ln();
 
                 if hit_distance < distance_inside_boundary {
                     let t = rec1.t + hit_distance / r.direction.length();
@@ -62,9 +58,15 @@ impl Hittable for ConstantMedium {
                         t,
                         point,
                         normal: Vec3::newi(1, 0, 0),
+                        shading_normal: Vec3::newi(1, 0, 0),
+                        dpdu: Vec3::newi(0, 1, 0),
+                        dpdv: Vec3::newi(0, 0, 1),
                         mat: &self.phase_function,
                         u: 0.0,
                         v: 0.0,
+                        front_face: true,
+                        color: None,
+                        object_id: rec1.object_id,
                     });
                 }
             }
@@ -0,0 +1,226 @@
+use super::{
+    aabb::{surrounding_box, AABB},
+    HitRecord, Hittable, Hittables,
+};
+use crate::ray::Ray;
+
+/// Maximum number of surfaces crossed along a ray inside one operand
+const MAX_CROSSINGS: usize = 64;
+
+#[derive(Clone, Copy, Debug)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    /// Parts of `a` that are not inside `b`
+    Difference,
+}
+
+impl CsgOp {
+    fn is_inside(self, inside_a: bool, inside_b: bool) -> bool {
+        match self {
+            CsgOp::Union => inside_a || inside_b,
+            CsgOp::Intersection => inside_a && inside_b,
+            CsgOp::Difference => inside_a && !inside_b,
+        }
+    }
+}
+
+/// Boolean combination of two closed hittables
+///
+/// Each operand is traced along the ray to find where it is entered and exited, and the first
+/// crossing that changes the inside state of the combined shape is reported.
+#[derive(Clone)]
+pub struct Csg {
+    op: CsgOp,
+    a: Box<Hittables>,
+    b: Box<Hittables>,
+    bbox: AABB,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: Hittables, b: Hittables) -> Hittables {
+        let box_a = a
+            .bounding_box(0., 1.)
+            .expect("no bounding box in Csg constructor");
+        let box_b = b
+            .bounding_box(0., 1.)
+            .expect("no bounding box in Csg constructor");
+
+        let bbox = match op {
+            CsgOp::Union => surrounding_box(box_a, box_b),
+            CsgOp::Intersection => {
+                let min = box_a.min.max(box_b.min);
+                // An empty overlap collapses to a point so the box never gets inverted
+                AABB {
+                    min,
+                    max: box_a.max.min(box_b.max).max(min),
+                }
+            }
+            CsgOp::Difference => box_a,
+        };
+
+        Hittables::from(Csg {
+            op,
+            a: Box::new(a),
+            b: Box::new(b),
+            bbox,
+        })
+    }
+
+    pub fn union(a: Hittables, b: Hittables) -> Hittables {
+        Csg::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection(a: Hittables, b: Hittables) -> Hittables {
+        Csg::new(CsgOp::Intersection, a, b)
+    }
+
+    pub fn difference(a: Hittables, b: Hittables) -> Hittables {
+        Csg::new(CsgOp::Difference, a, b)
+    }
+}
+
+/// Distance past a crossing the next one is looked for from, surfaces like `Rect` accept a hit
+/// exactly at `t_min` and would otherwise be found again
+const CROSSING_EPSILON: f32 = 1e-4;
+
+/// Walks the surfaces of one operand crossed along a ray, in order and one at a time
+struct Crossings<'a> {
+    hittable: &'a Hittables,
+    /// Whether the ray is inside the operand before the `next` crossing
+    inside: bool,
+    next: Option<HitRecord<'a>>,
+    found: usize,
+}
+
+impl<'a> Crossings<'a> {
+    fn new(hittable: &'a Hittables, r: &Ray, t_min: f32) -> Self {
+        let next = hittable.hit(r, t_min, f32::MAX);
+        // Leaving through the first surface means the ray started inside
+        let inside = matches!(&next, Some(hit) if !hit.front_face);
+
+        Crossings {
+            hittable,
+            inside,
+            next,
+            found: 1,
+        }
+    }
+
+    fn t(&self) -> f32 {
+        self.next.as_ref().map_or(f32::INFINITY, |hit| hit.t)
+    }
+
+    /// Moves past the next crossing and returns it, `None` once the operand is left behind
+    fn advance(&mut self, r: &Ray) -> Option<HitRecord<'a>> {
+        let hit = self.next.take()?;
+        self.inside = hit.front_face;
+
+        if self.found < MAX_CROSSINGS {
+            self.found += 1;
+            self.next = self.hittable.hit(r, hit.t + CROSSING_EPSILON, f32::MAX);
+        }
+
+        Some(hit)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.bbox.hit(r, t_min, t_max)?;
+
+        let mut a = Crossings::new(&self.a, r, t_min);
+        if a.next.is_none() && !matches!(self.op, CsgOp::Union) {
+            return None;
+        }
+        let mut b = Crossings::new(&self.b, r, t_min);
+
+        let mut inside = self.op.is_inside(a.inside, b.inside);
+        loop {
+            let from_a = a.t() <= b.t();
+            let mut hit = if from_a { a.advance(r) } else { b.advance(r) }?;
+            if hit.t > t_max {
+                return None;
+            }
+
+            let was_inside = inside;
+            inside = self.op.is_inside(a.inside, b.inside);
+            if was_inside == inside {
+                continue;
+            }
+
+            // The inside of `b` becomes the outside of the result
+            if !from_a && matches!(self.op, CsgOp::Difference) {
+                hit.front_face = !hit.front_face;
+            }
+            return Some(hit);
+        }
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hittable::{box_rect::BoxRect, sphere::Sphere},
+        material::Lambertian,
+        texture::constant_texture::ConstantTexture,
+        vec3::Vec3,
+    };
+
+    fn sphere(radius: f32) -> Hittables {
+        Hittables::from(Sphere {
+            center: Vec3::ZERO,
+            radius,
+            mat: Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+        })
+    }
+
+    fn cube(min: Vec3, max: Vec3) -> Hittables {
+        BoxRect::new(
+            min,
+            max,
+            Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    fn ray() -> Ray {
+        Ray::new(Vec3::new(0., 0., -5.), Vec3::new(0., 0., 1.), 0.)
+    }
+
+    #[test]
+    fn sphere_minus_box() {
+        let csg = Csg::difference(
+            sphere(1.),
+            cube(Vec3::new(-2., -2., -2.), Vec3::new(2., 2., -0.5)),
+        );
+        let hit = csg.hit(&ray(), 0.001, f32::MAX).expect("no hit");
+        assert!((hit.t - 4.5).abs() < 1e-4, "hit at {}", hit.t);
+        assert!(hit.front_face);
+    }
+
+    #[test]
+    fn box_intersect_sphere() {
+        let csg = Csg::intersection(cube(Vec3::splat(-0.5), Vec3::splat(0.5)), sphere(1.));
+        let hit = csg.hit(&ray(), 0.001, f32::MAX).expect("no hit");
+        assert!((hit.t - 4.5).abs() < 1e-4, "hit at {}", hit.t);
+    }
+
+    #[test]
+    fn union_starting_inside() {
+        let csg = Csg::union(sphere(1.), cube(Vec3::splat(-0.5), Vec3::splat(0.5)));
+        let r = Ray::new(Vec3::ZERO, Vec3::new(0., 0., 1.), 0.);
+        let hit = csg.hit(&r, 0.001, f32::MAX).expect("no hit");
+        assert!((hit.t - 1.).abs() < 1e-4, "hit at {}", hit.t);
+    }
+
+    #[test]
+    fn miss_past_t_max() {
+        let csg = Csg::difference(sphere(1.), cube(Vec3::splat(-0.5), Vec3::splat(0.5)));
+        assert!(csg.hit(&ray(), 0.001, 3.).is_none());
+    }
+}
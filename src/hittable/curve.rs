@@ -0,0 +1,329 @@
+use super::{
+    aabb::{surrounding_box, AABB},
+    get_orthonormal_basis, HitRecord, Hittable, Hittables,
+};
+use crate::{material::MaterialType, ray::Ray, utils::clamp, vec3::Vec3};
+
+/// Deepest subdivision used when intersecting a curve segment
+const MAX_DEPTH: u32 = 10;
+
+/// Part of a cubic Bézier curve swept by a width varying linearly along it
+///
+/// The surface is a ribbon always facing the ray, shaded with normals that turn across its width
+/// like on a cylinder. `u` goes along the whole curve and `v` across the ribbon.
+#[derive(Clone)]
+pub struct Curve {
+    /// Control points of the part of the curve covered by this segment
+    points: [Vec3; 4],
+    /// Range of the whole curve covered by this segment
+    u_range: (f32, f32),
+    /// Width at both ends of the segment
+    widths: (f32, f32),
+    max_depth: u32,
+    material: MaterialType,
+}
+
+impl Curve {
+    /// Whole curve as a single hittable
+    pub fn new(points: [Vec3; 4], width0: f32, width1: f32, material: MaterialType) -> Hittables {
+        Curve::segment(points, (0., 1.), (width0, width1), material)
+    }
+
+    /// The curve split in `count` hittables, so a BVH built over them can skip most of a long
+    /// curve
+    pub fn split(
+        points: [Vec3; 4],
+        width0: f32,
+        width1: f32,
+        count: usize,
+        material: MaterialType,
+    ) -> Vec<Hittables> {
+        (0..count)
+            .map(|i| {
+                let u0 = i as f32 / count as f32;
+                let u1 = (i + 1) as f32 / count as f32;
+                let width = |u: f32| width0 + u * (width1 - width0);
+                Curve::segment(points, (u0, u1), (width(u0), width(u1)), material.clone())
+            })
+            .collect()
+    }
+
+    fn segment(
+        points: [Vec3; 4],
+        u_range: (f32, f32),
+        widths: (f32, f32),
+        material: MaterialType,
+    ) -> Hittables {
+        let (u0, u1) = u_range;
+        let points = [
+            blossom(&points, u0, u0, u0),
+            blossom(&points, u0, u0, u1),
+            blossom(&points, u0, u1, u1),
+            blossom(&points, u1, u1, u1),
+        ];
+
+        // Enough subdivisions for the segments to stay within a fraction of the width of the
+        // curve, from the magnitude of the second differences of the control points
+        let curvature = (points[0] - 2. * points[1] + points[2])
+            .length()
+            .max((points[1] - 2. * points[2] + points[3]).length());
+        let epsilon = widths.0.max(widths.1) / 20.;
+        let depth = if curvature > 0. && epsilon > 0. {
+            (6. * curvature / (8. * epsilon) * 2f32.sqrt()).log2() / 2.
+        } else {
+            0.
+        };
+
+        Hittables::from(Curve {
+            points,
+            u_range,
+            widths,
+            max_depth: clamp(depth.ceil(), 0., MAX_DEPTH as f32) as u32,
+            material,
+        })
+    }
+
+    fn width_at(&self, u: f32) -> f32 {
+        let (u0, u1) = self.u_range;
+        let t = (u - u0) / (u1 - u0);
+        self.widths.0 + t * (self.widths.1 - self.widths.0)
+    }
+
+    /// Closest hit with the part `[u0, u1]` of the segment, `points` are in ray space where the
+    /// ray starts at the origin and goes along +z with a unit speed
+    fn recursive_hit(
+        &self,
+        points: &[Vec3; 4],
+        u0: f32,
+        u1: f32,
+        depth: u32,
+        z_min: f32,
+        z_max: f32,
+    ) -> Option<(f32, f32, f32)> {
+        let half_width = 0.5 * self.width_at(u0).max(self.width_at(u1));
+
+        // Convex hull of the control points grown by the width
+        let min = points.iter().fold(points[0], |acc, p| acc.min(*p)) - Vec3::splat(half_width);
+        let max = points.iter().fold(points[0], |acc, p| acc.max(*p)) + Vec3::splat(half_width);
+        if min.x > 0. || max.x < 0. || min.y > 0. || max.y < 0. || max.z < z_min || min.z > z_max {
+            return None;
+        }
+
+        if depth > 0 {
+            let (left, right) = subdivide(points);
+            let middle = 0.5 * (u0 + u1);
+            return match self.recursive_hit(&left, u0, middle, depth - 1, z_min, z_max) {
+                Some(hit) => {
+                    let z_max = hit.0.min(z_max);
+                    self.recursive_hit(&right, middle, u1, depth - 1, z_min, z_max)
+                        .or(Some(hit))
+                }
+                None => self.recursive_hit(&right, middle, u1, depth - 1, z_min, z_max),
+            };
+        }
+
+        // The ray must be between the planes perpendicular to the curve at both ends
+        let start_edge =
+            (points[1].y - points[0].y) * -points[0].y + points[0].x * (points[0].x - points[1].x);
+        let end_edge =
+            (points[2].y - points[3].y) * -points[3].y + points[3].x * (points[3].x - points[2].x);
+        if start_edge < 0. || end_edge < 0. {
+            return None;
+        }
+
+        // Closest point to the ray of the segment approximated as a line
+        let segment = points[3] - points[0];
+        let denom = segment.x * segment.x + segment.y * segment.y;
+        if denom == 0. {
+            return None;
+        }
+        let w = clamp(
+            -(points[0].x * segment.x + points[0].y * segment.y) / denom,
+            0.,
+            1.,
+        );
+        let u = u0 + w * (u1 - u0);
+        let width = self.width_at(u);
+
+        let (point, tangent) = evaluate(points, w);
+        let distance_squared = point.x * point.x + point.y * point.y;
+        if distance_squared > 0.25 * width * width || point.z < z_min || point.z > z_max {
+            return None;
+        }
+
+        // Which side of the center line the ray passes on
+        let distance = distance_squared.sqrt();
+        let side = tangent.x * -point.y + point.x * tangent.y;
+        let v = if side > 0. {
+            0.5 + distance / width
+        } else {
+            0.5 - distance / width
+        };
+
+        Some((point.z, u, v))
+    }
+}
+
+impl Hittable for Curve {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let length = r.direction.length();
+        let z_axis = r.direction / length;
+        let (x_axis, y_axis) = get_orthonormal_basis(z_axis);
+        let to_ray_space = |p: Vec3| {
+            let q = p - r.origin;
+            Vec3::new(q.dot(x_axis), q.dot(y_axis), q.dot(z_axis))
+        };
+        let points = [
+            to_ray_space(self.points[0]),
+            to_ray_space(self.points[1]),
+            to_ray_space(self.points[2]),
+            to_ray_space(self.points[3]),
+        ];
+
+        let (u0, u1) = self.u_range;
+        let (z, u, v) = self.recursive_hit(
+            &points,
+            u0,
+            u1,
+            self.max_depth,
+            t_min * length,
+            t_max * length,
+        )?;
+        let t = z / length;
+
+        // Flat ribbon facing the ray, the shading normal turns around the tangent across it
+        let w = (u - u0) / (u1 - u0);
+        let (_, dpdw) = evaluate(&self.points, w);
+        let dpdu = dpdw / (u1 - u0);
+        let tangent = dpdu.normalize();
+        let facing = -z_axis + tangent * tangent.dot(z_axis);
+        let facing = if facing.length_squared() > 0. {
+            facing.normalize()
+        } else {
+            x_axis
+        };
+        let across = tangent.cross(facing);
+        let angle = (v - 0.5) * std::f32::consts::PI;
+        let shading_normal = angle.cos() * facing + angle.sin() * across;
+
+        let mut rec = HitRecord::new(
+            t,
+            u,
+            v,
+            r.point_at(t),
+            facing,
+            dpdu,
+            self.width_at(u) * across,
+            &self.material,
+        );
+        rec.shading_normal = shading_normal;
+        Some(rec.with_face_normal(r))
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let half_width = Vec3::splat(0.5 * self.widths.0.max(self.widths.1));
+        let start = AABB {
+            min: self.points[0].min(self.points[1]) - half_width,
+            max: self.points[0].max(self.points[1]) + half_width,
+        };
+        let end = AABB {
+            min: self.points[2].min(self.points[3]) - half_width,
+            max: self.points[2].max(self.points[3]) + half_width,
+        };
+        Some(surrounding_box(start, end))
+    }
+}
+
+fn lerp(t: f32, a: Vec3, b: Vec3) -> Vec3 {
+    (1. - t) * a + t * b
+}
+
+/// Control point of the Bézier curve with parameters `(u0, u1, u2)`, with three equal
+/// parameters this is the point of the curve at that parameter
+fn blossom(p: &[Vec3; 4], u0: f32, u1: f32, u2: f32) -> Vec3 {
+    let a = [
+        lerp(u0, p[0], p[1]),
+        lerp(u0, p[1], p[2]),
+        lerp(u0, p[2], p[3]),
+    ];
+    let b = [lerp(u1, a[0], a[1]), lerp(u1, a[1], a[2])];
+    lerp(u2, b[0], b[1])
+}
+
+/// Splits the curve in its two halves
+fn subdivide(p: &[Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let p01 = 0.5 * (p[0] + p[1]);
+    let p12 = 0.5 * (p[1] + p[2]);
+    let p23 = 0.5 * (p[2] + p[3]);
+    let p012 = 0.5 * (p01 + p12);
+    let p123 = 0.5 * (p12 + p23);
+    let middle = 0.5 * (p012 + p123);
+
+    ([p[0], p01, p012, middle], [middle, p123, p23, p[3]])
+}
+
+/// Point of the curve at `t` and its derivative
+fn evaluate(p: &[Vec3; 4], t: f32) -> (Vec3, Vec3) {
+    let a = [
+        lerp(t, p[0], p[1]),
+        lerp(t, p[1], p[2]),
+        lerp(t, p[2], p[3]),
+    ];
+    let b = [lerp(t, a[0], a[1]), lerp(t, a[1], a[2])];
+    let derivative = 3. * (b[1] - b[0]);
+
+    // Degenerate control points at the ends, fall back to the chord
+    let derivative = if derivative.length_squared() > 0. {
+        derivative
+    } else {
+        p[3] - p[0]
+    };
+
+    (lerp(t, b[0], b[1]), derivative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, texture::constant_texture::ConstantTexture};
+
+    fn straight() -> [Vec3; 4] {
+        [
+            Vec3::new(0., 0., 0.),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(3., 0., 0.),
+        ]
+    }
+
+    #[test]
+    fn split_segments_share_their_ends() {
+        let p = [
+            Vec3::new(0., 0., 0.),
+            Vec3::new(1., 2., 0.),
+            Vec3::new(2., -1., 1.),
+            Vec3::new(3., 0., 0.),
+        ];
+        let (left, right) = subdivide(&p);
+        assert!((left[3] - right[0]).length() < 1e-6);
+        assert!((left[3] - evaluate(&p, 0.5).0).length() < 1e-5);
+        assert!((blossom(&p, 0.25, 0.25, 0.25) - evaluate(&p, 0.25).0).length() < 1e-5);
+    }
+
+    #[test]
+    fn hits_the_ribbon() {
+        let curve = Curve::new(
+            straight(),
+            0.2,
+            0.2,
+            Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+        );
+        let r = Ray::new(Vec3::new(1.5, 0.05, 5.), Vec3::new(0., 0., -1.), 0.);
+        let hit = curve.hit(&r, 0.001, f32::MAX).expect("no hit");
+        assert!((hit.t - 5.).abs() < 1e-2, "hit at {}", hit.t);
+        assert!((hit.u - 0.5).abs() < 1e-2, "u {}", hit.u);
+
+        let r = Ray::new(Vec3::new(1.5, 0.5, 5.), Vec3::new(0., 0., -1.), 0.);
+        assert!(curve.hit(&r, 0.001, f32::MAX).is_none());
+    }
+}
@@ -0,0 +1,115 @@
+use super::{aabb::AABB, get_azimuth, HitRecord, Hittable, Hittables};
+use crate::{material::MaterialType, ray::Ray, vec3::Vec3};
+
+/// Closed cylinder along the y axis, `center` is the middle of the bottom cap
+///
+/// On the side `u` goes around the axis and `v` from the bottom to the top, on the caps `v` goes
+/// from the axis to the rim.
+#[derive(Clone)]
+pub struct Cylinder {
+    center: Vec3,
+    radius: f32,
+    height: f32,
+    material: MaterialType,
+}
+
+impl Cylinder {
+    pub fn new(center: Vec3, radius: f32, height: f32, material: MaterialType) -> Hittables {
+        Hittables::from(Cylinder {
+            center,
+            radius,
+            height,
+            material,
+        })
+    }
+
+    fn side_hit(&self, r: &Ray, t: f32) -> HitRecord {
+        let local = r.point_at(t) - self.center;
+        let (u, dpdu) = get_azimuth(local);
+        let normal = Vec3::new(local.x, 0., local.z) / self.radius;
+
+        HitRecord::new(
+            t,
+            u,
+            local.y / self.height,
+            r.point_at(t),
+            normal,
+            dpdu,
+            Vec3::new(0., self.height, 0.),
+            &self.material,
+        )
+        .with_face_normal(r)
+    }
+
+    fn cap_hit(&self, r: &Ray, t: f32, top: bool) -> HitRecord {
+        let local = r.point_at(t) - self.center;
+        let (u, dpdu) = get_azimuth(local);
+        let radial = Vec3::new(local.x, 0., local.z);
+        let distance = radial.length();
+        let dpdv = if distance > 0. {
+            self.radius / distance * radial
+        } else {
+            Vec3::new(self.radius, 0., 0.)
+        };
+        let normal = if top { Vec3::Y } else { -Vec3::Y };
+
+        HitRecord::new(
+            t,
+            u,
+            distance / self.radius,
+            r.point_at(t),
+            normal,
+            dpdu,
+            dpdv,
+            &self.material,
+        )
+        .with_face_normal(r)
+    }
+}
+
+impl Hittable for Cylinder {
+    #[allow(clippy::many_single_char_names)]
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let o = r.origin - self.center;
+        let d = r.direction;
+        let mut closest = t_max;
+        let mut result = None;
+
+        let a = d.x * d.x + d.z * d.z;
+        let b = o.x * d.x + o.z * d.z;
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+        let discriminant = b * b - a * c;
+        if a > 0. && discriminant > 0. {
+            let root = discriminant.sqrt();
+            for t in [(-b - root) / a, (-b + root) / a].iter().copied() {
+                let y = o.y + t * d.y;
+                if t > t_min && t < closest && y >= 0. && y <= self.height {
+                    closest = t;
+                    result = Some(self.side_hit(r, t));
+                    break;
+                }
+            }
+        }
+
+        if d.y != 0. {
+            for (y, top) in [(0., false), (self.height, true)].iter().copied() {
+                let t = (y - o.y) / d.y;
+                let x = o.x + t * d.x;
+                let z = o.z + t * d.z;
+                if t > t_min && t < closest && x * x + z * z <= self.radius * self.radius {
+                    closest = t;
+                    result = Some(self.cap_hit(r, t, top));
+                }
+            }
+        }
+
+        result
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(AABB {
+            min: self.center - Vec3::new(self.radius, 0., self.radius),
+            max: self.center + Vec3::new(self.radius, self.height, self.radius),
+        })
+    }
+}
@@ -0,0 +1,85 @@
+use super::{aabb::AABB, get_orthonormal_basis, HitRecord, Hittable, Hittables};
+use crate::{material::MaterialType, ray::Ray, vec3::Vec3};
+
+/// Flat disk, `u` goes around the center and `v` from the center to the rim
+#[derive(Clone)]
+pub struct Disk {
+    center: Vec3,
+    normal: Vec3,
+    radius: f32,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: MaterialType,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f32, material: MaterialType) -> Hittables {
+        let normal = normal.normalize();
+        let (tangent, bitangent) = get_orthonormal_basis(normal);
+        Hittables::from(Disk {
+            center,
+            normal,
+            radius,
+            tangent,
+            bitangent,
+            material,
+        })
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        use std::f32::consts::PI;
+
+        let denom = self.normal.dot(r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = self.normal.dot(self.center - r.origin) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let point = r.point_at(t);
+        let offset = point - self.center;
+        let x = offset.dot(self.tangent);
+        let y = offset.dot(self.bitangent);
+        let distance = (x * x + y * y).sqrt();
+        if distance > self.radius {
+            return None;
+        }
+
+        let phi = y.atan2(x);
+        let phi = if phi < 0. { phi + 2. * PI } else { phi };
+        let u = phi / (2. * PI);
+        let v = distance / self.radius;
+        let dpdu = 2. * PI * (x * self.bitangent - y * self.tangent);
+        let dpdv = if distance > 0. {
+            self.radius / distance * offset
+        } else {
+            self.radius * self.tangent
+        };
+
+        Some(
+            HitRecord::new(t, u, v, point, self.normal, dpdu, dpdv, &self.material)
+                .with_face_normal(r),
+        )
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let n = self.normal;
+        let extent = self.radius
+            * Vec3::new(
+                (1. - n.x * n.x).max(0.).sqrt(),
+                (1. - n.y * n.y).max(0.).sqrt(),
+                (1. - n.z * n.z).max(0.).sqrt(),
+            )
+            + Vec3::splat(0.0001);
+
+        Some(AABB {
+            min: self.center - extent,
+            max: self.center + extent,
+        })
+    }
+}
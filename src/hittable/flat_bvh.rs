@@ -0,0 +1,137 @@
+use super::aabb::{surrounding_box, AABB};
+use crate::{ray::Ray, stats, vec3::Vec3};
+
+/// Most items stored in a leaf
+const LEAF_SIZE: usize = 4;
+
+/// Node of the tree, children of an inner node are stored right after it for the left one and
+/// at `index` for the right one
+#[derive(Clone, Copy)]
+struct Node {
+    bbox: AABB,
+    /// First item of a leaf or right child of an inner node
+    index: u32,
+    /// Number of items of a leaf, 0 for inner nodes
+    count: u32,
+}
+
+/// BVH stored in a single array over the items of one primitive, like the spheres of a particle
+/// set or the triangles of a mesh, which are far too many to each be a `Hittables`
+pub struct FlatBvh {
+    nodes: Vec<Node>,
+}
+
+impl FlatBvh {
+    /// Reorders `items` so that the items of each leaf are contiguous, `items` must not be empty
+    pub fn new<T>(items: &mut [T], bounds: impl Fn(&T) -> AABB) -> Self {
+        let mut nodes = Vec::with_capacity(2 * items.len() / LEAF_SIZE + 1);
+        stats::time_bvh_build(|| build(items, 0, &bounds, &mut nodes));
+        FlatBvh { nodes }
+    }
+
+    pub fn bounding_box(&self) -> AABB {
+        self.nodes[0].bbox
+    }
+
+    /// Closest item hit by `r` as its index and `t`, `hit_item(index, t_max)` intersects a
+    /// single item
+    pub fn hit(
+        &self,
+        r: &Ray,
+        t_min: f32,
+        t_max: f32,
+        mut hit_item: impl FnMut(usize, f32) -> Option<f32>,
+    ) -> Option<(usize, f32)> {
+        let mut closest = t_max;
+        let mut result = None;
+
+        let mut stack = [0usize; 64];
+        let mut stack_len = 1;
+        while stack_len > 0 {
+            stack_len -= 1;
+            let index = stack[stack_len];
+            let node = &self.nodes[index];
+            stats::count(|c| c.bvh_nodes += 1);
+            if node.bbox.hit(r, t_min, closest).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                let start = node.index as usize;
+                stats::count(|c| c.primitive_tests += node.count as u64);
+                for item in start..start + node.count as usize {
+                    if let Some(t) = hit_item(item, closest) {
+                        closest = t;
+                        result = Some(item);
+                    }
+                }
+            } else {
+                stack[stack_len] = node.index as usize;
+                stack[stack_len + 1] = index + 1;
+                stack_len += 2;
+            }
+        }
+
+        result.map(|item| (item, closest))
+    }
+}
+
+/// Builds the subtree of `items` by splitting them at the median of the longest axis of their
+/// centers, `offset` is the index of the first item in the whole array
+fn build<T>(
+    items: &mut [T],
+    offset: usize,
+    bounds: &impl Fn(&T) -> AABB,
+    nodes: &mut Vec<Node>,
+) -> usize {
+    let bbox = items
+        .iter()
+        .map(bounds)
+        .fold(bounds(&items[0]), surrounding_box);
+    let index = nodes.len();
+
+    if items.len() <= LEAF_SIZE {
+        nodes.push(Node {
+            bbox,
+            index: offset as u32,
+            count: items.len() as u32,
+        });
+        return index;
+    }
+
+    let center = |item: &T| {
+        let b = bounds(item);
+        0.5 * (b.min + b.max)
+    };
+    let (min, max) = items.iter().map(center).fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), c| (min.min(c), max.max(c)),
+    );
+    let extent = max - min;
+    let axis = if extent.x > extent.y && extent.x > extent.z {
+        0
+    } else if extent.y > extent.z {
+        1
+    } else {
+        2
+    };
+
+    let half = items.len() / 2;
+    items.select_nth_unstable_by(half, |a, b| {
+        center(a)[axis]
+            .partial_cmp(&center(b)[axis])
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    nodes.push(Node {
+        bbox,
+        index: 0,
+        count: 0,
+    });
+    let (left, right) = items.split_at_mut(half);
+    build(left, offset, bounds, nodes);
+    let right = build(right, offset + half, bounds, nodes);
+    nodes[index].index = right as u32;
+
+    index
+}
@@ -1,5 +1,9 @@
 use super::{Hittable, Hittables};
 
+/// Swaps the front and back faces of a surface
+///
+/// Kept for compatibility, shapes that need it can declare their outward direction instead, like
+/// `Rect::new_facing`.
 #[derive(Clone)]
 pub struct FlipNormals {
     pub ptr: Box<Hittables>,
@@ -15,7 +19,7 @@ impl Hittable for FlipNormals {
     fn hit(&self, r: &crate::ray::Ray, t_min: f32, t_max: f32) -> Option<super::HitRecord> {
         if let Some(rec) = self.ptr.hit(r, t_min, t_max) {
             let mut rec = rec;
-            rec.normal = -rec.normal;
+            rec.front_face = !rec.front_face;
             Some(rec)
         } else {
             None
@@ -0,0 +1,306 @@
+use super::{aabb::AABB, intersect_triangle, HitRecord, Hittable, Hittables};
+use crate::{material::MaterialType, ray::Ray, vec3::Vec3};
+use image::ImageResult;
+use std::{path::Path, sync::Arc};
+
+/// Grid of heights with their smooth normals, shared between clones of a heightfield
+struct HeightGrid {
+    /// Number of samples along x
+    width: usize,
+    /// Number of samples along z
+    depth: usize,
+    /// World space heights, row by row along z
+    heights: Vec<f32>,
+    normals: Vec<Vec3>,
+}
+
+/// Terrain made of two triangles per grid cell, `u` follows x and `v` follows z over the whole
+/// field
+#[derive(Clone)]
+pub struct Heightfield {
+    grid: Arc<HeightGrid>,
+    /// Corner of the field with the lowest x and z, at height 0
+    min: Vec3,
+    /// Extent along x and z, `size.y` is the height of a sample of 1
+    size: Vec3,
+    bbox: AABB,
+    material: MaterialType,
+}
+
+impl Heightfield {
+    /// `heights` has `width * depth` samples in `[0, 1]`, row by row along z
+    pub fn new(
+        heights: Vec<f32>,
+        width: usize,
+        depth: usize,
+        min: Vec3,
+        size: Vec3,
+        material: MaterialType,
+    ) -> Hittables {
+        assert!(
+            width >= 2 && depth >= 2 && heights.len() == width * depth,
+            "a heightfield needs at least 2x2 samples"
+        );
+
+        let heights: Vec<f32> = heights.into_iter().map(|h| h * size.y).collect();
+        let cell_x = size.x / (width - 1) as f32;
+        let cell_z = size.z / (depth - 1) as f32;
+        let height = |x: usize, z: usize| heights[x + z * width];
+
+        // Central differences, one sided on the borders
+        let mut normals = Vec::with_capacity(width * depth);
+        for z in 0..depth {
+            for x in 0..width {
+                let (x0, x1) = (x.saturating_sub(1), (x + 1).min(width - 1));
+                let (z0, z1) = (z.saturating_sub(1), (z + 1).min(depth - 1));
+                let dx = (height(x1, z) - height(x0, z)) / ((x1 - x0) as f32 * cell_x);
+                let dz = (height(x, z1) - height(x, z0)) / ((z1 - z0) as f32 * cell_z);
+                normals.push(Vec3::new(-dx, 1., -dz).normalize());
+            }
+        }
+
+        let min_height = heights.iter().copied().fold(f32::MAX, f32::min);
+        let max_height = heights.iter().copied().fold(f32::MIN, f32::max);
+        let bbox = AABB {
+            min: Vec3::new(min.x, min.y + min_height - 0.0001, min.z),
+            max: Vec3::new(min.x + size.x, min.y + max_height + 0.0001, min.z + size.z),
+        };
+
+        Hittables::from(Heightfield {
+            grid: Arc::new(HeightGrid {
+                width,
+                depth,
+                heights,
+                normals,
+            }),
+            min,
+            size,
+            bbox,
+            material,
+        })
+    }
+
+    /// One sample per pixel, black is 0 and white is 1, the top of the image is at the lowest z
+    pub fn from_image(
+        path: impl AsRef<Path>,
+        min: Vec3,
+        size: Vec3,
+        material: MaterialType,
+    ) -> ImageResult<Hittables> {
+        let image = image::open(path)?;
+        let (width, depth, heights) = match image.as_luma16() {
+            Some(luma) => (
+                luma.width(),
+                luma.height(),
+                luma.pixels().map(|p| p[0] as f32 / 65535.).collect(),
+            ),
+            None => {
+                let luma = image.to_luma();
+                (
+                    luma.width(),
+                    luma.height(),
+                    luma.pixels().map(|p| p[0] as f32 / 255.).collect(),
+                )
+            }
+        };
+
+        Ok(Heightfield::new(
+            heights,
+            width as usize,
+            depth as usize,
+            min,
+            size,
+            material,
+        ))
+    }
+
+    /// Samples `height(u, v)` on a `width` by `depth` grid, with `u` and `v` in `[0, 1]`
+    pub fn from_fn(
+        width: usize,
+        depth: usize,
+        min: Vec3,
+        size: Vec3,
+        height: impl Fn(f32, f32) -> f32,
+        material: MaterialType,
+    ) -> Hittables {
+        let mut heights = Vec::with_capacity(width * depth);
+        for z in 0..depth {
+            for x in 0..width {
+                let u = x as f32 / (width - 1) as f32;
+                let v = z as f32 / (depth - 1) as f32;
+                heights.push(height(u, v));
+            }
+        }
+
+        Heightfield::new(heights, width, depth, min, size, material)
+    }
+
+    fn vertex(&self, x: usize, z: usize) -> Vec3 {
+        let grid = &self.grid;
+        Vec3::new(
+            self.min.x + self.size.x * x as f32 / (grid.width - 1) as f32,
+            self.min.y + grid.heights[x + z * grid.width],
+            self.min.z + self.size.z * z as f32 / (grid.depth - 1) as f32,
+        )
+    }
+
+    /// Closest hit with the two triangles of the cell at `(x, z)`
+    fn hit_cell(&self, r: &Ray, x: usize, z: usize, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let corners = [(x, z), (x + 1, z), (x, z + 1), (x + 1, z + 1)];
+        let triangles = [
+            [corners[0], corners[2], corners[1]],
+            [corners[1], corners[2], corners[3]],
+        ];
+
+        let mut closest = t_max;
+        let mut result = None;
+        for triangle in triangles.iter() {
+            let [a, b, c] = *triangle;
+            let (p0, p1, p2) = (
+                self.vertex(a.0, a.1),
+                self.vertex(b.0, b.1),
+                self.vertex(c.0, c.1),
+            );
+
+            if let Some((t, beta, gamma)) = intersect_triangle(r, p0, p1, p2) {
+                if t <= t_min || t >= closest {
+                    continue;
+                }
+                closest = t;
+
+                let grid = &self.grid;
+                let normal_at = |(x, z): (usize, usize)| grid.normals[x + z * grid.width];
+                let smooth = ((1. - beta - gamma) * normal_at(a)
+                    + beta * normal_at(b)
+                    + gamma * normal_at(c))
+                .normalize();
+
+                let geometric = (p1 - p0).cross(p2 - p0).normalize();
+                let point = r.point_at(t);
+                let local = point - self.min;
+                let dpdu = self.size.x * Vec3::new(1., -geometric.x / geometric.y, 0.);
+                let dpdv = self.size.z * Vec3::new(0., -geometric.z / geometric.y, 1.);
+
+                let mut rec = HitRecord::new(
+                    t,
+                    local.x / self.size.x,
+                    local.z / self.size.z,
+                    point,
+                    geometric,
+                    dpdu,
+                    dpdv,
+                    &self.material,
+                );
+                rec.shading_normal = smooth;
+                result = Some(rec.with_face_normal(r));
+            }
+        }
+
+        result
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (t0, t1) = self.bbox.hit(r, t_min, t_max)?;
+        let cells_x = self.grid.width - 1;
+        let cells_z = self.grid.depth - 1;
+        let cell_size_x = self.size.x / cells_x as f32;
+        let cell_size_z = self.size.z / cells_z as f32;
+
+        // Walk the cells crossed by the ray in the xz plane, from the entry of the bounding box
+        let start = r.point_at(t0) - self.min;
+        let cell = |p: f32, size: f32, count: usize| -> usize {
+            ((p / size).floor().max(0.) as usize).min(count - 1)
+        };
+        let mut x = cell(start.x, cell_size_x, cells_x);
+        let mut z = cell(start.z, cell_size_z, cells_z);
+
+        // For each axis the step direction, the ray distance to the next cell boundary and the
+        // ray distance between two boundaries
+        let axis = |origin: f32, direction: f32, cell: usize, size: f32| -> (isize, f32, f32) {
+            if direction > 0. {
+                let boundary = (cell + 1) as f32 * size;
+                (1, (boundary - origin) / direction, size / direction)
+            } else if direction < 0. {
+                let boundary = cell as f32 * size;
+                (-1, (boundary - origin) / direction, -size / direction)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let origin = r.origin - self.min;
+        let (step_x, mut next_x, delta_x) = axis(origin.x, r.direction.x, x, cell_size_x);
+        let (step_z, mut next_z, delta_z) = axis(origin.z, r.direction.z, z, cell_size_z);
+
+        loop {
+            let cell_exit = next_x.min(next_z);
+            if let Some(rec) = self.hit_cell(r, x, z, t_min, t_max) {
+                return Some(rec);
+            }
+            if cell_exit >= t1 {
+                return None;
+            }
+
+            if next_x < next_z {
+                let next = x as isize + step_x;
+                if next < 0 || next >= cells_x as isize {
+                    return None;
+                }
+                x = next as usize;
+                next_x += delta_x;
+            } else {
+                let next = z as isize + step_z;
+                if next < 0 || next >= cells_z as isize {
+                    return None;
+                }
+                z = next as usize;
+                next_z += delta_z;
+            }
+        }
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, texture::constant_texture::ConstantTexture};
+
+    fn ramp() -> Hittables {
+        // Rises from 0 at x = 0 to 1 at x = 4
+        Heightfield::from_fn(
+            9,
+            5,
+            Vec3::ZERO,
+            Vec3::new(4., 1., 4.),
+            |u, _| u,
+            Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    #[test]
+    fn hits_the_interpolated_height() {
+        let field = ramp();
+        for &x in [0.3, 1.7, 2.5, 3.9].iter() {
+            let r = Ray::new(Vec3::new(x, 5., 2.2), Vec3::new(0., -1., 0.), 0.);
+            let hit = field.hit(&r, 0.001, f32::MAX).expect("no hit");
+            assert!(
+                (hit.point.y - x / 4.).abs() < 1e-4,
+                "{} at x {}",
+                hit.point.y,
+                x
+            );
+            assert!(hit.front_face);
+        }
+    }
+
+    #[test]
+    fn misses_outside_the_grid() {
+        let r = Ray::new(Vec3::new(4.5, 5., 2.), Vec3::new(0., -1., 0.), 0.);
+        assert!(ramp().hit(&r, 0.001, f32::MAX).is_none());
+    }
+}
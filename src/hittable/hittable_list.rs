@@ -4,6 +4,7 @@ use crate::{
         HitRecord, Hittable, Hittables,
     },
     ray::Ray,
+    stats,
 };
 
 #[derive(Clone)]
@@ -23,6 +24,9 @@ impl Hittable for HittableList {
         let mut result = None;
 
         for hittable in self.list.iter() {
+            if hittable.is_primitive() {
+                stats::count(|c| c.primitive_tests += 1);
+            }
             if let Some(rec) = hittable.hit(r, t_min, closest_so_far) {
                 closest_so_far = rec.t;
                 result = Some(rec);
@@ -0,0 +1,337 @@
+use super::{
+    aabb::AABB, flat_bvh::FlatBvh, get_orthonormal_basis, intersect_triangle, HitRecord, Hittable,
+    Hittables,
+};
+use crate::{
+    material::MaterialType,
+    ray::Ray,
+    utils::ply::{invalid, Ply},
+    vec3::Vec3,
+};
+use glam::Vec2;
+use std::{io, path::Path, sync::Arc};
+
+/// Vertex data of a triangle mesh, the optional attributes are either empty or have one value
+/// per position
+#[derive(Clone, Default)]
+pub struct MeshAttributes {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    /// Linear colours in `[0, 1]`, passed to the material through `HitRecord::color`
+    pub colors: Vec<Vec3>,
+    /// Indices of the three positions of each triangle
+    pub triangles: Vec<[u32; 3]>,
+}
+
+/// Triangles of a mesh with their BVH, shared by the meshes made from the same attributes
+pub struct MeshData {
+    attributes: MeshAttributes,
+    bvh: FlatBvh,
+}
+
+impl MeshData {
+    /// Fails when there is no triangle, an index is out of range or an attribute does not have
+    /// one value per position
+    pub fn new(attributes: MeshAttributes) -> io::Result<Self> {
+        if attributes.triangles.is_empty() {
+            return Err(invalid("mesh without triangles"));
+        }
+        let count = attributes.positions.len();
+        if attributes
+            .triangles
+            .iter()
+            .flatten()
+            .any(|&i| i as usize >= count)
+        {
+            return Err(invalid("mesh triangle with a vertex out of range"));
+        }
+        for (name, len) in [
+            ("normals", attributes.normals.len()),
+            ("uvs", attributes.uvs.len()),
+            ("colours", attributes.colors.len()),
+        ]
+        .iter()
+        {
+            if *len != 0 && *len != count {
+                return Err(invalid(format!(
+                    "mesh with {} {} for {} positions",
+                    len, name, count
+                )));
+            }
+        }
+
+        let mut attributes = attributes;
+        let positions = &attributes.positions;
+        let bvh = FlatBvh::new(&mut attributes.triangles, |triangle| {
+            let [a, b, c] = triangle.map(|i| positions[i as usize]);
+            AABB {
+                min: a.min(b).min(c) - Vec3::splat(0.0001),
+                max: a.max(b).max(c) + Vec3::splat(0.0001),
+            }
+        });
+
+        Ok(MeshData { attributes, bvh })
+    }
+}
+
+/// Triangle mesh sharing a single material, with its own BVH over the triangles
+///
+/// Without uvs the barycentric coordinates of the hit are used as `u` and `v`.
+#[derive(Clone)]
+pub struct Mesh {
+    data: Arc<MeshData>,
+    material: MaterialType,
+}
+
+impl Mesh {
+    /// Fails like `MeshData::new`
+    pub fn new(attributes: MeshAttributes, material: MaterialType) -> io::Result<Hittables> {
+        Ok(Mesh::with_data(
+            Arc::new(MeshData::new(attributes)?),
+            material,
+        ))
+    }
+
+    /// Mesh sharing its triangles and BVH with other meshes
+    pub fn with_data(data: Arc<MeshData>, material: MaterialType) -> Hittables {
+        Hittables::from(Mesh { data, material })
+    }
+
+    /// Loads the faces of an ascii or binary PLY file, polygons are split in triangle fans
+    pub fn from_ply(path: impl AsRef<Path>, material: MaterialType) -> io::Result<Hittables> {
+        let attributes = read_ply(&Ply::open(path)?)?;
+        if attributes.triangles.is_empty() {
+            return Err(invalid("PLY file without faces"));
+        }
+        Mesh::new(attributes, material)
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let mesh = &self.data.attributes;
+        let (index, _) = self.data.bvh.hit(r, t_min, t_max, |i, t_max| {
+            let [a, b, c] = mesh.triangles[i].map(|v| mesh.positions[v as usize]);
+            let (t, _, _) = intersect_triangle(r, a, b, c)?;
+            if t > t_min && t < t_max {
+                Some(t)
+            } else {
+                None
+            }
+        })?;
+
+        let triangle = mesh.triangles[index];
+        let [a, b, c] = triangle.map(|v| mesh.positions[v as usize]);
+        let (t, beta, gamma) = intersect_triangle(r, a, b, c)?;
+        let alpha = 1. - beta - gamma;
+        let interpolate = |values: &[Vec3]| {
+            let [va, vb, vc] = triangle.map(|v| values[v as usize]);
+            alpha * va + beta * vb + gamma * vc
+        };
+
+        let normal = (b - a).cross(c - a).normalize();
+        let uvs = if mesh.uvs.is_empty() {
+            [Vec2::ZERO, Vec2::new(1., 0.), Vec2::new(0., 1.)]
+        } else {
+            triangle.map(|v| mesh.uvs[v as usize])
+        };
+        let uv = alpha * uvs[0] + beta * uvs[1] + gamma * uvs[2];
+
+        // Solve the edges for the derivatives of the position along the uv axes
+        let (duv1, duv2) = (uvs[1] - uvs[0], uvs[2] - uvs[0]);
+        let (dp1, dp2) = (b - a, c - a);
+        let determinant = duv1.x * duv2.y - duv1.y * duv2.x;
+        let (dpdu, dpdv) = if determinant.abs() > 1e-12 {
+            (
+                (duv2.y * dp1 - duv1.y * dp2) / determinant,
+                (duv1.x * dp2 - duv2.x * dp1) / determinant,
+            )
+        } else {
+            get_orthonormal_basis(normal)
+        };
+
+        let mut rec = HitRecord::new(
+            t,
+            uv.x,
+            uv.y,
+            r.point_at(t),
+            normal,
+            dpdu,
+            dpdv,
+            &self.material,
+        );
+        if !mesh.normals.is_empty() {
+            let smooth = interpolate(&mesh.normals);
+            if smooth.length_squared() > 0. {
+                // Vertex normals can point to the other side of the triangle than its winding
+                let smooth = smooth.normalize();
+                rec.shading_normal = if smooth.dot(normal) < 0. {
+                    -smooth
+                } else {
+                    smooth
+                };
+            }
+        }
+        if !mesh.colors.is_empty() {
+            rec.color = Some(interpolate(&mesh.colors));
+        }
+        Some(rec.with_face_normal(r))
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(self.data.bvh.bounding_box())
+    }
+}
+
+/// Mesh of the `vertex` and `face` elements, with the optional `nx`, `ny`, `nz`, `u`, `v` (or
+/// `s`, `t`) and `red`, `green`, `blue` vertex properties
+fn read_ply(ply: &Ply) -> io::Result<MeshAttributes> {
+    let vertices = ply
+        .element("vertex")
+        .ok_or_else(|| invalid("PLY file without vertices"))?;
+    let vectors = |x: &str, y: &str, z: &str| -> Vec<Vec3> {
+        match (
+            vertices.scalars(x),
+            vertices.scalars(y),
+            vertices.scalars(z),
+        ) {
+            (Some(x), Some(y), Some(z)) => x
+                .iter()
+                .zip(y)
+                .zip(z)
+                .map(|((x, y), z)| Vec3::new(*x as f32, *y as f32, *z as f32))
+                .collect(),
+            _ => Vec::new(),
+        }
+    };
+
+    let positions = vectors("x", "y", "z");
+    if positions.is_empty() {
+        return Err(invalid("PLY vertices need x, y and z"));
+    }
+    let normals = vectors("nx", "ny", "nz");
+    let colors = match vertices.property("red") {
+        Some(red) => {
+            let scale = red.ty.max_value() as f32;
+            vectors("red", "green", "blue")
+                .into_iter()
+                .map(|c| c / scale)
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    let uvs = ["u", "s", "texture_u", "texture_s"]
+        .iter()
+        .zip(["v", "t", "texture_v", "texture_t"].iter())
+        .find_map(|(u, v)| Some((vertices.scalars(u)?, vertices.scalars(v)?)))
+        .map(|(u, v)| {
+            u.iter()
+                .zip(v)
+                .map(|(u, v)| Vec2::new(*u as f32, *v as f32))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut triangles = Vec::new();
+    if let Some(faces) = ply.element("face") {
+        let indices = faces
+            .lists("vertex_indices")
+            .or_else(|| faces.lists("vertex_index"))
+            .ok_or_else(|| invalid("PLY faces need vertex indices"))?;
+        for face in indices {
+            if face.iter().any(|i| *i as usize >= positions.len()) {
+                return Err(invalid("PLY face with a vertex out of range"));
+            }
+            for i in 1..face.len().saturating_sub(1) {
+                triangles.push([face[0], face[i], face[i + 1]]);
+            }
+        }
+    }
+
+    Ok(MeshAttributes {
+        positions,
+        normals,
+        uvs,
+        colors,
+        triangles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, texture::constant_texture::ConstantTexture};
+
+    fn material() -> MaterialType {
+        Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))
+    }
+
+    /// Unit square in the xy plane made of two triangles
+    fn square() -> MeshAttributes {
+        MeshAttributes {
+            positions: vec![
+                Vec3::new(0., 0., 0.),
+                Vec3::new(1., 0., 0.),
+                Vec3::new(1., 1., 0.),
+                Vec3::new(0., 1., 0.),
+            ],
+            uvs: vec![
+                Vec2::new(0., 0.),
+                Vec2::new(1., 0.),
+                Vec2::new(1., 1.),
+                Vec2::new(0., 1.),
+            ],
+            triangles: vec![[0, 1, 2], [0, 2, 3]],
+            ..MeshAttributes::default()
+        }
+    }
+
+    #[test]
+    fn hits_the_closest_triangle() {
+        let mesh = Mesh::new(square(), material()).unwrap();
+        let r = Ray::new(Vec3::new(0.25, 0.75, 1.), Vec3::new(0., 0., -1.), 0.);
+        let hit = mesh.hit(&r, 0.001, f32::MAX).expect("no hit");
+        assert!((hit.t - 1.).abs() < 1e-5);
+        assert!((hit.u - 0.25).abs() < 1e-5 && (hit.v - 0.75).abs() < 1e-5);
+        assert!(hit.front_face);
+
+        assert!(mesh.hit(&r, 0.001, 0.5).is_none());
+        let r = Ray::new(Vec3::new(1.25, 0.5, 1.), Vec3::new(0., 0., -1.), 0.);
+        assert!(mesh.hit(&r, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn invalid_attributes_are_errors() {
+        let mut attributes = square();
+        attributes.triangles.push([0, 2, 4]);
+        assert!(Mesh::new(attributes, material()).is_err());
+
+        let mut attributes = square();
+        attributes.normals = vec![Vec3::Z; 3];
+        assert!(Mesh::new(attributes, material()).is_err());
+
+        let mut attributes = square();
+        attributes.triangles.clear();
+        assert!(Mesh::new(attributes, material()).is_err());
+    }
+
+    const PLY_HEADER: &[u8] = b"ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\n\
+                                property float y\nproperty float z\nelement face 1\n\
+                                property list uchar int vertex_indices\nend_header\n\
+                                0 0 0\n1 0 0\n1 1 0\n0 1 0\n";
+
+    #[test]
+    fn ply_polygons_become_fans() {
+        let ply = Ply::parse(&[PLY_HEADER, b"4 0 1 2 3\n"].concat()).unwrap();
+        let attributes = read_ply(&ply).unwrap();
+        assert_eq!(attributes.positions.len(), 4);
+        assert_eq!(attributes.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn ply_indices_out_of_range() {
+        let ply = Ply::parse(&[PLY_HEADER, b"3 0 1 4\n"].concat()).unwrap();
+        assert!(read_ply(&ply).is_err());
+    }
+}
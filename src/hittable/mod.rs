@@ -1,39 +1,104 @@
-#![allow(clippy::new_ret_no_self)]
+#![allow(clippy::new_ret_no_self, clippy::too_many_arguments)]
 
 use derive_new::*;
 use enum_dispatch::enum_dispatch;
 
 use crate::{
     hittable::{
-        aabb::AABB, box_rect::BoxRect, bvh_node::BvhNode, constant_medium::ConstantMedium,
-        flip_normals::FlipNormals, hittable_list::HittableList, moving_sphere::MovingSphere,
-        rect::Rect, rotate::RotateY, sphere::Sphere, translate::Translate,
+        aabb::AABB, box_rect::BoxRect, bvh_node::BvhNode, cone::Cone,
+        constant_medium::ConstantMedium, csg::Csg, curve::Curve, cylinder::Cylinder, disk::Disk,
+        flip_normals::FlipNormals, heightfield::Heightfield, hittable_list::HittableList,
+        mesh::Mesh, moving_sphere::MovingSphere, object::Object, particles::Particles, quad::Quad,
+        rect::Rect, rotate::RotateY, sdf::SdfShape, sphere::Sphere, torus::Torus,
+        translate::Translate,
     },
     material::MaterialType,
     ray::Ray,
+    texture::TextureContext,
     vec3::Vec3,
 };
 
 pub mod aabb;
 pub mod box_rect;
 pub mod bvh_node;
+pub mod cone;
 pub mod constant_medium;
+pub mod csg;
+pub mod curve;
+pub mod cylinder;
+pub mod disk;
+pub mod flat_bvh;
 pub mod flip_normals;
+pub mod heightfield;
 pub mod hittable_list;
+pub mod mesh;
 pub mod moving_sphere;
+pub mod object;
+pub mod particles;
+pub mod quad;
 pub mod rect;
 pub mod rotate;
+pub mod sdf;
 pub mod sphere;
+pub mod torus;
 pub mod translate;
 
-#[derive(new)]
+#[derive(Clone, Copy, new)]
 pub struct HitRecord<'a> {
     pub t: f32,
     pub u: f32,
     pub v: f32,
     pub point: Vec3,
+    /// Geometric normal of the surface
     pub normal: Vec3,
+    /// Partial derivative of the surface position along `u`
+    pub dpdu: Vec3,
+    /// Partial derivative of the surface position along `v`
+    pub dpdv: Vec3,
     pub mat: &'a MaterialType,
+    /// Normal used by materials, starts as the geometric normal and can be perturbed by a
+    /// normal map or a bump map
+    #[new(value = "normal")]
+    pub shading_normal: Vec3,
+    /// Whether the ray hit the outward side of the surface, normals always face against the ray
+    /// so this is the only way to tell both sides apart
+    #[new(value = "true")]
+    pub front_face: bool,
+    /// Colour carried by the primitive itself, like a per particle or per vertex colour
+    #[new(value = "None")]
+    pub color: Option<Vec3>,
+    /// ID of the object hit, given by `object::number_objects`, 0 when it has none
+    #[new(value = "0")]
+    pub object_id: u32,
+}
+
+impl<'a> HitRecord<'a> {
+    /// Turns the outward normals given to `new` against `ray` and records which side was hit
+    pub fn with_face_normal(mut self, ray: &Ray) -> Self {
+        self.front_face = ray.direction.dot(self.normal) < 0.;
+        if !self.front_face {
+            self.normal = -self.normal;
+            self.shading_normal = -self.shading_normal;
+        }
+        self
+    }
+
+    /// Texture lookup context for this hit as seen along `ray`
+    pub fn texture_context(&self, ray: &Ray) -> TextureContext {
+        let width = ray.spread * self.t * ray.direction.length();
+        let area = self.dpdu.cross(self.dpdv).length();
+        let footprint = if area > 0. { width / area.sqrt() } else { 0. };
+
+        TextureContext {
+            u: self.u,
+            v: self.v,
+            point: self.point,
+            footprint,
+            world_footprint: width,
+            normal: self.shading_normal,
+            color: self.color,
+        }
+    }
 }
 
 #[enum_dispatch]
@@ -55,6 +120,35 @@ pub enum Hittables {
     Translate,
     RotateY,
     ConstantMedium,
+    Disk,
+    Cylinder,
+    Cone,
+    Torus,
+    Quad,
+    Csg,
+    SdfShape,
+    Heightfield,
+    Curve,
+    Particles,
+    Mesh,
+    Object,
+}
+
+impl Hittables {
+    /// Whether hitting this is a single intersection test rather than a walk through a BVH or a
+    /// list which count their own tests
+    pub fn is_primitive(&self) -> bool {
+        match self {
+            Hittables::Object(object) => object.ptr.is_primitive(),
+            _ => !matches!(
+                self,
+                Hittables::HittableList(_)
+                    | Hittables::BvhNode(_)
+                    | Hittables::Particles(_)
+                    | Hittables::Mesh(_)
+            ),
+        }
+    }
 }
 
 pub fn get_sphere_uv(p: Vec3) -> (f32, f32) {
@@ -67,3 +161,113 @@ pub fn get_sphere_uv(p: Vec3) -> (f32, f32) {
 
     (u, v)
 }
+
+/// Returns the `(dpdu, dpdv)` tangents matching the parameterisation of `get_sphere_uv`
+pub fn get_sphere_tangents(normal: Vec3, radius: f32) -> (Vec3, Vec3) {
+    use std::f32::consts::PI;
+
+    let dpdu = 2. * PI * radius * Vec3::new(normal.z, 0., -normal.x);
+    let cos_theta = (normal.x * normal.x + normal.z * normal.z).sqrt();
+    let dpdv = if cos_theta > 1e-6 {
+        PI * radius
+            * Vec3::new(
+                -normal.y * normal.x / cos_theta,
+                cos_theta,
+                -normal.y * normal.z / cos_theta,
+            )
+    } else {
+        // At the poles u is degenerate, pick any direction perpendicular to the normal
+        PI * radius * Vec3::new(1., 0., 0.)
+    };
+
+    (dpdu, dpdv)
+}
+
+/// Returns the angle of `p` around the y axis as `u` in `[0, 1)`, and `dp/du` for the circle
+/// around the y axis that goes through `p`
+pub fn get_azimuth(p: Vec3) -> (f32, Vec3) {
+    use std::f32::consts::PI;
+
+    let phi = p.z.atan2(p.x);
+    let phi = if phi < 0. { phi + 2. * PI } else { phi };
+
+    (phi / (2. * PI), 2. * PI * Vec3::new(-p.z, 0., p.x))
+}
+
+/// Returns two unit vectors completing the unit vector `n` into an orthonormal basis
+pub fn get_orthonormal_basis(n: Vec3) -> (Vec3, Vec3) {
+    let sign = 1f32.copysign(n.z);
+    let a = -1. / (sign + n.z);
+    let b = n.x * n.y * a;
+
+    (
+        Vec3::new(1. + sign * n.x * n.x * a, sign * b, -sign * n.x),
+        Vec3::new(b, sign + n.y * n.y * a, -n.y),
+    )
+}
+
+/// Möller-Trumbore intersection, returns `t` and the barycentric coordinates of `p1` and `p2`
+pub fn intersect_triangle(r: &Ray, p0: Vec3, p1: Vec3, p2: Vec3) -> Option<(f32, f32, f32)> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = r.direction.cross(edge2);
+    let det = edge1.dot(pvec);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1. / det;
+    let tvec = r.origin - p0;
+    let beta = tvec.dot(pvec) * inv_det;
+    if !(0. ..=1.).contains(&beta) {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let gamma = r.direction.dot(qvec) * inv_det;
+    if gamma < 0. || beta + gamma > 1. {
+        return None;
+    }
+
+    Some((edge2.dot(qvec) * inv_det, beta, gamma))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orthonormal_basis_is_orthonormal() {
+        let normals = [
+            Vec3::new(0., 0., 1.),
+            Vec3::new(0., 0., -1.),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(0., -1., 0.),
+            Vec3::new(1., 2., -3.).normalize(),
+            Vec3::new(-0.3, 0.1, 0.9).normalize(),
+        ];
+        for &n in normals.iter() {
+            let (t, b) = get_orthonormal_basis(n);
+            assert!((t.length() - 1.).abs() < 1e-5);
+            assert!((b.length() - 1.).abs() < 1e-5);
+            assert!(t.dot(b).abs() < 1e-5);
+            assert!(t.dot(n).abs() < 1e-5);
+            assert!(b.dot(n).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn triangle_barycentrics() {
+        let (p0, p1, p2) = (Vec3::ZERO, Vec3::new(1., 0., 0.), Vec3::new(0., 1., 0.));
+        let r = Ray::new(Vec3::new(0.25, 0.5, 2.), Vec3::new(0., 0., -1.), 0.);
+        let (t, beta, gamma) = intersect_triangle(&r, p0, p1, p2).expect("no hit");
+        assert!((t - 2.).abs() < 1e-6);
+        assert!((beta - 0.25).abs() < 1e-6);
+        assert!((gamma - 0.5).abs() < 1e-6);
+
+        let r = Ray::new(Vec3::new(0.75, 0.5, 2.), Vec3::new(0., 0., -1.), 0.);
+        assert!(intersect_triangle(&r, p0, p1, p2).is_none());
+        let r = Ray::new(Vec3::new(0.25, 0.5, 2.), Vec3::new(1., 0., 0.), 0.);
+        assert!(intersect_triangle(&r, p0, p1, p2).is_none());
+    }
+}
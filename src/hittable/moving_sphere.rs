@@ -1,7 +1,7 @@
 use crate::{
     hittable::{
         aabb::{surrounding_box, AABB},
-        get_sphere_uv, HitRecord, Hittable,
+        get_sphere_tangents, get_sphere_uv, HitRecord, Hittable,
     },
     material::MaterialType,
     ray::Ray,
@@ -32,7 +32,11 @@ impl Hittable for MovingSphere {
                 let point = r.point_at(t);
                 let normal = (point - self.center(r.time)) / self.radius;
                 let (u, v) = get_sphere_uv(normal);
-                Some(HitRecord::new(t, u, v, point, normal, &self.material))
+                let (dpdu, dpdv) = get_sphere_tangents(normal, self.radius);
+                Some(
+                    HitRecord::new(t, u, v, point, normal, dpdu, dpdv, &self.material)
+                        .with_face_normal(r),
+                )
             };
 
             let mut t = (-b - discriminant.sqrt()) / a;
@@ -0,0 +1,107 @@
+use super::{
+    aabb::AABB, bvh_node::BvhNode, hittable_list::HittableList, HitRecord, Hittable, Hittables,
+};
+use crate::ray::Ray;
+
+/// Tags the hits of a hittable with the ID of the object it belongs to
+#[derive(Clone)]
+pub struct Object {
+    id: u32,
+    pub(super) ptr: Box<Hittables>,
+}
+
+impl Object {
+    pub fn new(ptr: Hittables, id: u32) -> Hittables {
+        Hittables::from(Object {
+            ptr: Box::new(ptr),
+            id,
+        })
+    }
+}
+
+impl Hittable for Object {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.ptr.hit(r, t_min, t_max).map(|mut rec| {
+            rec.object_id = self.id;
+            rec
+        })
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.ptr.bounding_box(t0, t1)
+    }
+}
+
+/// Gives an ID to every object of `world`, the leaves of its lists and BVH nodes, numbered from
+/// 1 in the order of the tree so the same scene always gets the same IDs
+///
+/// Objects numbered before keep their IDs.
+pub fn number_objects(world: Hittables) -> Hittables {
+    fn number(hittable: Hittables, next: &mut u32) -> Hittables {
+        match hittable {
+            Hittables::HittableList(list) => HittableList::new(
+                list.list
+                    .into_iter()
+                    .map(|hittable| number(hittable, next))
+                    .collect(),
+            ),
+            Hittables::BvhNode(node) => Hittables::from(BvhNode {
+                left: Box::new(number(*node.left, next)),
+                right: Box::new(number(*node.right, next)),
+                bounding_box: node.bounding_box,
+            }),
+            Hittables::Object(object) => {
+                *next = (*next).max(object.id + 1);
+                Hittables::from(object)
+            }
+            hittable => {
+                let id = *next;
+                *next += 1;
+                Object::new(hittable, id)
+            }
+        }
+    }
+
+    number(world, &mut 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hittable::sphere::Sphere, material::Lambertian, texture::constant_texture::ConstantTexture,
+        vec3::Vec3,
+    };
+
+    #[test]
+    fn objects_keep_their_ids() {
+        let sphere = |x: f32| {
+            Hittables::from(Sphere {
+                center: Vec3::new(x, 0., 0.),
+                radius: 0.5,
+                mat: Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+            })
+        };
+        let world = || {
+            number_objects(HittableList::new(vec![
+                sphere(0.),
+                BvhNode::new(vec![sphere(2.), sphere(4.), sphere(6.)], 0., 1., 0),
+            ]))
+        };
+
+        let id_at = |world: &Hittables, x: f32| {
+            let r = Ray::new(Vec3::new(x, 0.2, 5.), Vec3::new(0., 0., -1.), 0.);
+            world.hit(&r, 0.001, f32::MAX).unwrap().object_id
+        };
+        // Numbering again keeps the IDs
+        let (first, second) = (world(), number_objects(world()));
+        let ids: Vec<u32> = [0., 2., 4., 6.].iter().map(|x| id_at(&first, *x)).collect();
+        assert!(ids.iter().all(|id| *id > 0));
+        for (i, id) in ids.iter().enumerate() {
+            assert!(!ids[..i].contains(id), "{:?}", ids);
+        }
+        for x in [0., 2., 4., 6.].iter() {
+            assert_eq!(id_at(&first, *x), id_at(&second, *x));
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use super::{
+    aabb::AABB, flat_bvh::FlatBvh, get_sphere_tangents, get_sphere_uv, HitRecord, Hittable,
+    Hittables,
+};
+use crate::{
+    material::MaterialType,
+    ray::Ray,
+    utils::{
+        clamp,
+        ply::{invalid, Ply},
+    },
+    vec3::Vec3,
+};
+use std::{fs, io, path::Path, sync::Arc};
+
+/// Single sphere of a particle set, the colour is stored as 8 bit rgb to keep it small
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub position: Vec3,
+    pub radius: f32,
+    pub color: [u8; 3],
+}
+
+impl Particle {
+    /// `color` is in `[0, 1]`
+    pub fn new(position: Vec3, radius: f32, color: Vec3) -> Self {
+        let channel = |c: f32| (clamp(c, 0., 1.) * 255.).round() as u8;
+        Particle {
+            position,
+            radius,
+            color: [channel(color.x), channel(color.y), channel(color.z)],
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        AABB {
+            min: self.position - Vec3::splat(self.radius),
+            max: self.position + Vec3::splat(self.radius),
+        }
+    }
+}
+
+struct ParticleData {
+    particles: Vec<Particle>,
+    bvh: FlatBvh,
+    /// Whether the particles came with their own colours
+    colored: bool,
+}
+
+/// Set of spheres sharing a single material, stored compactly with their own BVH
+///
+/// Each hit passes the colour of its particle through `HitRecord::color`, use a
+/// `VertexColorTexture` in the material to show it.
+#[derive(Clone)]
+pub struct Particles {
+    data: Arc<ParticleData>,
+    material: MaterialType,
+}
+
+impl Particles {
+    pub fn new(particles: Vec<Particle>, colored: bool, material: MaterialType) -> Hittables {
+        assert!(!particles.is_empty(), "a particle set needs particles");
+
+        let mut particles = particles;
+        let bvh = FlatBvh::new(&mut particles, Particle::bounding_box);
+
+        Hittables::from(Particles {
+            data: Arc::new(ParticleData {
+                particles,
+                bvh,
+                colored,
+            }),
+            material,
+        })
+    }
+
+    /// Loads particles from a `.csv`, a `.ply` or a raw binary file depending on the extension,
+    /// `radius` is used when the file has no radii
+    pub fn load(
+        path: impl AsRef<Path>,
+        radius: f32,
+        material: MaterialType,
+    ) -> io::Result<Hittables> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        let (particles, colored) = match extension.as_deref() {
+            Some("csv") => read_csv(&fs::read_to_string(path)?, radius)?,
+            Some("ply") => read_ply(&Ply::open(path)?, radius)?,
+            _ => (read_binary(&fs::read(path)?)?, true),
+        };
+
+        if particles.is_empty() {
+            return Err(invalid(format!("no particles in {}", path.display())));
+        }
+        Ok(Particles::new(particles, colored, material))
+    }
+
+    fn hit_particle(&self, particle: &Particle, r: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        let oc = r.origin - particle.position;
+        let a = r.direction.dot(r.direction);
+        let b = oc.dot(r.direction);
+        let c = oc.dot(oc) - particle.radius * particle.radius;
+        let discriminant = b * b - a * c;
+        if discriminant <= 0. {
+            return None;
+        }
+
+        let root = discriminant.sqrt();
+        [(-b - root) / a, (-b + root) / a]
+            .iter()
+            .copied()
+            .find(|t| *t > t_min && *t < t_max)
+    }
+}
+
+impl Hittable for Particles {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let data = &self.data;
+        let (index, closest) = data.bvh.hit(r, t_min, t_max, |i, t_max| {
+            self.hit_particle(&data.particles[i], r, t_min, t_max)
+        })?;
+
+        let particle = &data.particles[index];
+        let point = r.point_at(closest);
+        let normal = (point - particle.position) / particle.radius;
+        let (u, v) = get_sphere_uv(normal);
+        let (dpdu, dpdv) = get_sphere_tangents(normal, particle.radius);
+
+        let mut rec = HitRecord::new(closest, u, v, point, normal, dpdu, dpdv, &self.material);
+        if data.colored {
+            let [red, green, blue] = particle.color;
+            rec.color = Some(Vec3::new(red as f32, green as f32, blue as f32) / 255.);
+        }
+        Some(rec.with_face_normal(r))
+    }
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(self.data.bvh.bounding_box())
+    }
+}
+
+/// One particle per line as `x,y,z[,radius[,r,g,b]]` with colours in `[0, 1]`. A first line
+/// that is not numeric is a header naming the columns, in any order, among `x`, `y`, `z`,
+/// `radius`, `r`, `g` and `b`
+fn read_csv(text: &str, radius: f32) -> io::Result<(Vec<Particle>, bool)> {
+    // Numbered before skipping blank lines and comments so errors point at the file line
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+
+    let split = |line: &str| -> Vec<String> {
+        line.split(',')
+            .map(|field| field.trim().to_lowercase())
+            .collect()
+    };
+    let mut columns: Vec<String> = ["x", "y", "z", "radius", "r", "g", "b"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    if let Some((_, first)) = lines.peek() {
+        let fields = split(first);
+        if fields.iter().any(|field| field.parse::<f32>().is_err()) {
+            columns = fields;
+            lines.next();
+        }
+    }
+    let column = |names: &[&str]| columns.iter().position(|c| names.contains(&c.as_str()));
+    let (x, y, z) = match (column(&["x"]), column(&["y"]), column(&["z"])) {
+        (Some(x), Some(y), Some(z)) => (x, y, z),
+        _ => return Err(invalid("particle CSV needs x, y and z columns")),
+    };
+    let radius_column = column(&["radius", "size"]);
+    let rgb = match (
+        column(&["r", "red"]),
+        column(&["g", "green"]),
+        column(&["b", "blue"]),
+    ) {
+        (Some(r), Some(g), Some(b)) => Some((r, g, b)),
+        _ => None,
+    };
+
+    let mut particles = Vec::new();
+    let mut colored = false;
+    for (number, line) in lines {
+        let values = line
+            .split(',')
+            .map(|field| field.trim().parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|_| invalid(format!("invalid particle on line {}", number)))?;
+        let value = |i: usize| values.get(i).copied();
+
+        let position = match (value(x), value(y), value(z)) {
+            (Some(x), Some(y), Some(z)) => Vec3::new(x, y, z),
+            _ => return Err(invalid(format!("missing coordinates on line {}", number))),
+        };
+        let color = match rgb.and_then(|(r, g, b)| Some(Vec3::new(value(r)?, value(g)?, value(b)?)))
+        {
+            Some(color) => {
+                colored = true;
+                color
+            }
+            None => Vec3::ONE,
+        };
+        let radius = radius_column.and_then(value).unwrap_or(radius);
+
+        particles.push(Particle::new(position, radius, color));
+    }
+
+    Ok((particles, colored))
+}
+
+/// Vertices of a PLY file as particles, with the optional `radius` (or `scale`) and
+/// `red`, `green` and `blue` properties
+fn read_ply(ply: &Ply, radius: f32) -> io::Result<(Vec<Particle>, bool)> {
+    let vertices = ply
+        .element("vertex")
+        .ok_or_else(|| invalid("PLY file without vertices"))?;
+    let (x, y, z) = match (
+        vertices.scalars("x"),
+        vertices.scalars("y"),
+        vertices.scalars("z"),
+    ) {
+        (Some(x), Some(y), Some(z)) => (x, y, z),
+        _ => return Err(invalid("PLY vertices need x, y and z")),
+    };
+    let radii = vertices
+        .scalars("radius")
+        .or_else(|| vertices.scalars("scale"));
+
+    let channel = |name: &str| {
+        let property = vertices.property(name)?;
+        let scale = property.ty.max_value();
+        Some(
+            vertices
+                .scalars(name)?
+                .iter()
+                .map(move |c| (*c / scale) as f32),
+        )
+    };
+    let colors: Option<Vec<Vec3>> = match (channel("red"), channel("green"), channel("blue")) {
+        (Some(r), Some(g), Some(b)) => Some(
+            r.zip(g)
+                .zip(b)
+                .map(|((r, g), b)| Vec3::new(r, g, b))
+                .collect(),
+        ),
+        _ => None,
+    };
+
+    let particles = (0..vertices.count)
+        .map(|i| {
+            Particle::new(
+                Vec3::new(x[i] as f32, y[i] as f32, z[i] as f32),
+                radii.map_or(radius, |radii| radii[i] as f32),
+                colors.as_ref().map_or(Vec3::ONE, |colors| colors[i]),
+            )
+        })
+        .collect();
+
+    Ok((particles, colors.is_some()))
+}
+
+/// Raw particles of 7 little endian `f32` each, `x y z radius r g b` with colours in `[0, 1]`
+fn read_binary(bytes: &[u8]) -> io::Result<Vec<Particle>> {
+    const RECORD: usize = 7 * 4;
+    if !bytes.chunks_exact(RECORD).remainder().is_empty() {
+        return Err(invalid("binary particle file is not made of whole records"));
+    }
+
+    Ok(bytes
+        .chunks_exact(RECORD)
+        .map(|record| {
+            let value = |i: usize| {
+                f32::from_le_bytes([
+                    record[4 * i],
+                    record[4 * i + 1],
+                    record[4 * i + 2],
+                    record[4 * i + 3],
+                ])
+            };
+            Particle::new(
+                Vec3::new(value(0), value(1), value(2)),
+                value(3),
+                Vec3::new(value(4), value(5), value(6)),
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_with_header_and_colours() {
+        let text = "# particles\nz, x, y, red, green, blue\n\n3,1,2,1,0,0.5\n6,4,5,0,1,0\n";
+        let (particles, colored) = read_csv(text, 0.1).unwrap();
+        assert!(colored);
+        assert_eq!(particles.len(), 2);
+        assert_eq!(particles[0].position, Vec3::new(1., 2., 3.));
+        assert_eq!(particles[0].radius, 0.1);
+        assert_eq!(particles[0].color, [255, 0, 128]);
+        assert_eq!(particles[1].position, Vec3::new(4., 5., 6.));
+    }
+
+    #[test]
+    fn csv_without_header() {
+        let (particles, colored) = read_csv("1,2,3\n4,5,6,0.5\n", 0.1).unwrap();
+        assert!(!colored);
+        assert_eq!(particles[0].radius, 0.1);
+        assert_eq!(particles[1].radius, 0.5);
+        assert_eq!(particles[1].color, [255, 255, 255]);
+    }
+
+    #[test]
+    fn csv_errors_count_file_lines() {
+        let text = "x,y,z\n# comment\n\n1,2,3\n4,five,6\n";
+        let error = read_csv(text, 0.1).unwrap_err();
+        assert!(error.to_string().ends_with("line 5"), "{}", error);
+
+        let error = read_csv("x,y,z\n1,2\n", 0.1).unwrap_err();
+        assert!(error.to_string().ends_with("line 2"), "{}", error);
+    }
+
+    #[test]
+    fn ply_vertices() {
+        let ply = Ply::parse(
+            b"ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\n\
+              property float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\n\
+              end_header\n1 2 3 255 0 0\n4 5 6 0 0 255\n",
+        )
+        .unwrap();
+        let (particles, colored) = read_ply(&ply, 0.2).unwrap();
+        assert!(colored);
+        assert_eq!(particles[1].position, Vec3::new(4., 5., 6.));
+        assert_eq!(particles[1].color, [0, 0, 255]);
+    }
+
+    #[test]
+    fn binary_needs_whole_records() {
+        let mut bytes = Vec::new();
+        for value in [1f32, 2., 3., 0.5, 1., 0., 0.].iter() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        let particles = read_binary(&bytes).unwrap();
+        assert_eq!(particles[0].position, Vec3::new(1., 2., 3.));
+        assert_eq!(particles[0].radius, 0.5);
+
+        assert!(read_binary(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
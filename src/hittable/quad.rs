@@ -0,0 +1,81 @@
+use super::{aabb::AABB, HitRecord, Hittable, Hittables};
+use crate::{material::MaterialType, ray::Ray, vec3::Vec3};
+
+/// Parallelogram spanned by the edges `u` and `v` starting at `origin`, the normal is `u x v`
+#[derive(Clone)]
+pub struct Quad {
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    /// `(u x v) / |u x v|^2`, projects a point of the plane on the edges
+    w: Vec3,
+    material: MaterialType,
+}
+
+impl Quad {
+    pub fn new(origin: Vec3, u: Vec3, v: Vec3, material: MaterialType) -> Hittables {
+        let n = u.cross(v);
+        Hittables::from(Quad {
+            origin,
+            u,
+            v,
+            normal: n.normalize(),
+            w: n / n.dot(n),
+            material,
+        })
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let denom = self.normal.dot(r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = self.normal.dot(self.origin - r.origin) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let point = r.point_at(t);
+        let planar = point - self.origin;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0. ..=1.).contains(&alpha) || !(0. ..=1.).contains(&beta) {
+            return None;
+        }
+
+        Some(
+            HitRecord::new(
+                t,
+                alpha,
+                beta,
+                point,
+                self.normal,
+                self.u,
+                self.v,
+                &self.material,
+            )
+            .with_face_normal(r),
+        )
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let corners = [
+            self.origin,
+            self.origin + self.u,
+            self.origin + self.v,
+            self.origin + self.u + self.v,
+        ];
+        let min = corners.iter().fold(corners[0], |acc, c| acc.min(*c));
+        let max = corners.iter().fold(corners[0], |acc, c| acc.max(*c));
+
+        // Pad the box so it is never flat along an axis
+        Some(AABB {
+            min: min - Vec3::splat(0.0001),
+            max: max + Vec3::splat(0.0001),
+        })
+    }
+}
@@ -9,12 +9,20 @@ pub enum StaticAxis {
     Z,
 }
 
+/// Outward direction of a rect along its static axis
+#[derive(Clone, Copy)]
+pub enum Facing {
+    Positive,
+    Negative,
+}
+
 #[derive(Clone)]
 pub struct Rect {
     range1: Range<f32>,
     range2: Range<f32>,
     k: f32,
     static_axis: StaticAxis,
+    facing: Facing,
     material: MaterialType,
 }
 
@@ -25,12 +33,25 @@ impl Rect {
         k: f32,
         static_axis: StaticAxis,
         material: MaterialType,
+    ) -> Hittables {
+        Rect::new_facing(range1, range2, k, static_axis, Facing::Positive, material)
+    }
+
+    /// Rect whose outward side looks along `facing`, `new` faces the positive side
+    pub fn new_facing(
+        range1: Range<f32>,
+        range2: Range<f32>,
+        k: f32,
+        static_axis: StaticAxis,
+        facing: Facing,
+        material: MaterialType,
     ) -> Hittables {
         Hittables::from(Rect {
             range1,
             range2,
             k,
             static_axis,
+            facing,
             material,
         })
     }
@@ -60,9 +81,16 @@ impl Hittable for Rect {
         let v = (axis2 - self.range2.start) / (self.range2.end - self.range2.start);
         let point = r.point_at(t);
         let mut normal = Vec3::ZERO;
-        normal[axis_index.0] = 1.0;
+        normal[axis_index.0] = match self.facing {
+            Facing::Positive => 1.0,
+            Facing::Negative => -1.0,
+        };
+        let mut dpdu = Vec3::ZERO;
+        dpdu[axis_index.1] = self.range1.end - self.range1.start;
+        let mut dpdv = Vec3::ZERO;
+        dpdv[axis_index.2] = self.range2.end - self.range2.start;
 
-        Some(HitRecord::new(t, u, v, point, normal, &self.material))
+        Some(HitRecord::new(t, u, v, point, normal, dpdu, dpdv, &self.material).with_face_normal(r))
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
@@ -55,6 +55,15 @@ impl RotateY {
             bbox,
         })
     }
+
+    /// Rotates a vector from object space back to world space
+    fn rotate(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x + self.sin_theta * v.z,
+            v.y,
+            -self.sin_theta * v.x + self.cos_theta * v.z,
+        )
+    }
 }
 
 impl Hittable for RotateY {
@@ -67,20 +76,19 @@ impl Hittable for RotateY {
         direction.x = self.cos_theta * r.direction.x - self.sin_theta * r.direction.z;
         direction.z = self.sin_theta * r.direction.x + self.cos_theta * r.direction.z;
 
-        let rotated_r = Ray::new(origin, direction, r.time);
+        let rotated_r = Ray {
+            origin,
+            direction,
+            ..*r
+        };
 
         if let Some(rec) = self.ptr.hit(&rotated_r, t_min, t_max) {
-            let mut p = rec.point;
-            let mut normal = rec.normal;
-
-            p.x = self.cos_theta * rec.point.x + self.sin_theta * rec.point.z;
-            p.z = -self.sin_theta * rec.point.x + self.cos_theta * rec.point.z;
-            normal.x = self.cos_theta * rec.normal.x + self.sin_theta * rec.normal.z;
-            normal.z = -self.sin_theta * rec.normal.x + self.cos_theta * rec.normal.z;
-
             let mut rec = rec;
-            rec.point = p;
-            rec.normal = normal;
+            rec.point = self.rotate(rec.point);
+            rec.normal = self.rotate(rec.normal);
+            rec.shading_normal = self.rotate(rec.shading_normal);
+            rec.dpdu = self.rotate(rec.dpdu);
+            rec.dpdv = self.rotate(rec.dpdv);
             return Some(rec);
         }
 
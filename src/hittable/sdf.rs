@@ -0,0 +1,391 @@
+use super::{aabb::AABB, HitRecord, Hittable, Hittables};
+use crate::{material::MaterialType, ray::Ray, utils::clamp, vec3::Vec3};
+use std::sync::Arc;
+
+/// Distance function built from a small library of shapes and operators
+///
+/// Shapes are centered on the origin, use `translate` to move them. `Twist` and `Repeat` do not
+/// preserve exact distances, lower `SdfShape::step_scale` when using them.
+#[derive(Clone)]
+pub enum Sdf {
+    Sphere {
+        radius: f32,
+    },
+    Box {
+        half_extents: Vec3,
+    },
+    /// Ring in the xz plane
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    /// Capped cylinder along the y axis
+    Cylinder {
+        radius: f32,
+        half_height: f32,
+    },
+    /// Segment from `a` to `b` inflated by `radius`
+    Capsule {
+        a: Vec3,
+        b: Vec3,
+        radius: f32,
+    },
+    /// Distance estimate of the power `power` Mandelbulb, about 1.2 units wide
+    Mandelbulb {
+        power: f32,
+        iterations: u32,
+    },
+    Union(Box<Sdf>, Box<Sdf>),
+    Intersection(Box<Sdf>, Box<Sdf>),
+    Difference(Box<Sdf>, Box<Sdf>),
+    /// Union blending the shapes over a distance of about `k`
+    SmoothUnion(Box<Sdf>, Box<Sdf>, f32),
+    Translate(Box<Sdf>, Vec3),
+    Scale(Box<Sdf>, f32),
+    /// Rounds the edges by growing the shape by `radius`
+    Round(Box<Sdf>, f32),
+    /// Rotates around the y axis by `amount` radians per unit of height
+    Twist(Box<Sdf>, f32),
+    /// Repeats the shape every `period` units, a period of 0 disables the repetition on that axis
+    Repeat(Box<Sdf>, Vec3),
+    Custom(Arc<dyn Fn(Vec3) -> f32 + Send + Sync>),
+}
+
+impl Sdf {
+    pub fn sphere(radius: f32) -> Sdf {
+        Sdf::Sphere { radius }
+    }
+
+    pub fn cube(half_extents: Vec3) -> Sdf {
+        Sdf::Box { half_extents }
+    }
+
+    pub fn torus(major_radius: f32, minor_radius: f32) -> Sdf {
+        Sdf::Torus {
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    pub fn cylinder(radius: f32, half_height: f32) -> Sdf {
+        Sdf::Cylinder {
+            radius,
+            half_height,
+        }
+    }
+
+    pub fn capsule(a: Vec3, b: Vec3, radius: f32) -> Sdf {
+        Sdf::Capsule { a, b, radius }
+    }
+
+    pub fn mandelbulb(power: f32, iterations: u32) -> Sdf {
+        Sdf::Mandelbulb { power, iterations }
+    }
+
+    pub fn custom(f: impl Fn(Vec3) -> f32 + Send + Sync + 'static) -> Sdf {
+        Sdf::Custom(Arc::new(f))
+    }
+
+    pub fn union(self, other: Sdf) -> Sdf {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn intersection(self, other: Sdf) -> Sdf {
+        Sdf::Intersection(Box::new(self), Box::new(other))
+    }
+
+    pub fn difference(self, other: Sdf) -> Sdf {
+        Sdf::Difference(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Sdf, k: f32) -> Sdf {
+        Sdf::SmoothUnion(Box::new(self), Box::new(other), k)
+    }
+
+    pub fn translate(self, offset: Vec3) -> Sdf {
+        Sdf::Translate(Box::new(self), offset)
+    }
+
+    pub fn scale(self, factor: f32) -> Sdf {
+        Sdf::Scale(Box::new(self), factor)
+    }
+
+    pub fn round(self, radius: f32) -> Sdf {
+        Sdf::Round(Box::new(self), radius)
+    }
+
+    pub fn twist(self, amount: f32) -> Sdf {
+        Sdf::Twist(Box::new(self), amount)
+    }
+
+    pub fn repeat(self, period: Vec3) -> Sdf {
+        Sdf::Repeat(Box::new(self), period)
+    }
+
+    /// Signed distance from `p` to the surface, negative inside
+    pub fn distance(&self, p: Vec3) -> f32 {
+        match self {
+            Sdf::Sphere { radius } => p.length() - radius,
+            Sdf::Box { half_extents } => {
+                let q = p.abs() - *half_extents;
+                q.max(Vec3::ZERO).length() + q.max_element().min(0.)
+            }
+            Sdf::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+                (ring * ring + p.y * p.y).sqrt() - minor_radius
+            }
+            Sdf::Cylinder {
+                radius,
+                half_height,
+            } => {
+                let dx = (p.x * p.x + p.z * p.z).sqrt() - radius;
+                let dy = p.y.abs() - half_height;
+                dx.max(dy).min(0.) + (dx.max(0.).powi(2) + dy.max(0.).powi(2)).sqrt()
+            }
+            Sdf::Capsule { a, b, radius } => {
+                let pa = p - *a;
+                let ba = *b - *a;
+                let h = clamp(pa.dot(ba) / ba.dot(ba), 0., 1.);
+                (pa - h * ba).length() - radius
+            }
+            Sdf::Mandelbulb { power, iterations } => mandelbulb(p, *power, *iterations),
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Sdf::Difference(a, b) => a.distance(p).max(-b.distance(p)),
+            Sdf::SmoothUnion(a, b, k) => {
+                let (da, db) = (a.distance(p), b.distance(p));
+                let h = clamp(0.5 + 0.5 * (db - da) / k, 0., 1.);
+                db + (da - db) * h - k * h * (1. - h)
+            }
+            Sdf::Translate(sdf, offset) => sdf.distance(p - *offset),
+            Sdf::Scale(sdf, factor) => sdf.distance(p / *factor) * factor,
+            Sdf::Round(sdf, radius) => sdf.distance(p) - radius,
+            Sdf::Twist(sdf, amount) => {
+                let (sin, cos) = (amount * p.y).sin_cos();
+                let q = Vec3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z);
+                sdf.distance(q)
+            }
+            Sdf::Repeat(sdf, period) => {
+                let mut q = p;
+                for i in 0..3 {
+                    if period[i] > 0. {
+                        q[i] -= period[i] * (p[i] / period[i]).round();
+                    }
+                }
+                sdf.distance(q)
+            }
+            Sdf::Custom(f) => f(p),
+        }
+    }
+
+    /// Normalized gradient of the distance, with the tetrahedron technique
+    pub fn normal(&self, p: Vec3, h: f32) -> Vec3 {
+        let k0 = Vec3::new(1., -1., -1.);
+        let k1 = Vec3::new(-1., -1., 1.);
+        let k2 = Vec3::new(-1., 1., -1.);
+        let k3 = Vec3::new(1., 1., 1.);
+
+        let gradient = k0 * self.distance(p + h * k0)
+            + k1 * self.distance(p + h * k1)
+            + k2 * self.distance(p + h * k2)
+            + k3 * self.distance(p + h * k3);
+
+        if gradient.length_squared() > 0. {
+            gradient.normalize()
+        } else {
+            Vec3::Y
+        }
+    }
+}
+
+fn mandelbulb(p: Vec3, power: f32, iterations: u32) -> f32 {
+    let mut z = p;
+    let mut dr = 1.;
+    let mut r = 0.;
+
+    // Without an iteration `r` stays at zero and the estimate below is NaN
+    for _ in 0..iterations.max(1) {
+        r = z.length();
+        if r > 2. {
+            break;
+        }
+        // Only the origin itself stays there, it belongs to the set so its distance is zero
+        if r == 0. {
+            return 0.;
+        }
+
+        let theta = (z.z / r).acos() * power;
+        let phi = z.y.atan2(z.x) * power;
+        dr = r.powf(power - 1.) * power * dr + 1.;
+
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        z = r.powf(power) * Vec3::new(sin_theta * cos_phi, sin_phi * sin_theta, cos_theta) + p;
+    }
+
+    0.5 * r.ln() * r / dr
+}
+
+/// Surface where `sdf` is zero, found by sphere tracing inside `bbox`
+///
+/// Uvs are projected along the axis closest to the normal and cover the bounding box once.
+#[derive(Clone)]
+pub struct SdfShape {
+    pub sdf: Sdf,
+    /// The surface must be entirely inside this box, nothing is traced outside of it
+    pub bbox: AABB,
+    pub mat: MaterialType,
+    /// Fraction of the distance advanced at each step, below 1 for inexact distances
+    pub step_scale: f32,
+    pub max_steps: u32,
+    /// Distance to the surface under which a point counts as a hit
+    pub epsilon: f32,
+}
+
+impl SdfShape {
+    pub fn new(sdf: Sdf, bbox: AABB, mat: MaterialType) -> Hittables {
+        SdfShape::with_step_scale(sdf, bbox, mat, 1.)
+    }
+
+    /// Shape advancing only `step_scale` of the distance at each step, for fields that
+    /// overestimate it
+    pub fn with_step_scale(sdf: Sdf, bbox: AABB, mat: MaterialType, step_scale: f32) -> Hittables {
+        Hittables::from(SdfShape {
+            sdf,
+            bbox,
+            mat,
+            step_scale,
+            max_steps: 256,
+            epsilon: 1e-4,
+        })
+    }
+
+    /// Uvs of `point` and their derivatives, projected along the dominant axis of `normal`
+    fn triplanar_uv(&self, point: Vec3, normal: Vec3) -> (f32, f32, Vec3, Vec3) {
+        let abs = normal.abs();
+        let (axis, u_axis, v_axis) = if abs.x >= abs.y && abs.x >= abs.z {
+            (0, 2, 1)
+        } else if abs.y >= abs.z {
+            (1, 0, 2)
+        } else {
+            (2, 0, 1)
+        };
+
+        let extent = self.bbox.max - self.bbox.min;
+        let local = (point - self.bbox.min) / extent;
+
+        // Moving along a uv axis also moves along the projection axis to stay on the surface
+        let derivative = |i: usize| -> Vec3 {
+            let mut d = Vec3::ZERO;
+            d[i] = extent[i];
+            d[axis] = -extent[i] * normal[i] / normal[axis];
+            d
+        };
+
+        (
+            local[u_axis],
+            local[v_axis],
+            derivative(u_axis),
+            derivative(v_axis),
+        )
+    }
+}
+
+impl Hittable for SdfShape {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (t0, t1) = self.bbox.hit(r, t_min, t_max)?;
+        let inv_length = 1. / r.direction.length();
+
+        // Rays entering the box come from outside the surface, rays starting on the surface march
+        // away from it on the side they are heading to
+        let start = r.point_at(t0);
+        let start_distance = self.sdf.distance(start);
+        let side = if t0 > t_min {
+            1.
+        } else if start_distance.abs() > self.epsilon {
+            start_distance.signum()
+        } else if self.sdf.normal(start, self.epsilon).dot(r.direction) > 0. {
+            1.
+        } else {
+            -1.
+        };
+
+        let mut t = t0;
+        for step in 0..self.max_steps {
+            let distance = side * self.sdf.distance(r.point_at(t));
+            if distance < self.epsilon && step > 0 {
+                let point = r.point_at(t);
+                let normal = self.sdf.normal(point, self.epsilon);
+                let (u, v, dpdu, dpdv) = self.triplanar_uv(point, normal);
+                return Some(
+                    HitRecord::new(t, u, v, point, normal, dpdu, dpdv, &self.mat)
+                        .with_face_normal(r),
+                );
+            }
+
+            t += distance.max(self.epsilon) * self.step_scale * inv_length;
+            if t > t1 {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, texture::constant_texture::ConstantTexture};
+
+    #[test]
+    fn distances_of_basic_shapes() {
+        let sphere = Sdf::sphere(1.).translate(Vec3::new(1., 0., 0.));
+        assert!((sphere.distance(Vec3::new(3., 0., 0.)) - 1.).abs() < 1e-5);
+        assert!((sphere.distance(Vec3::new(1., 0., 0.)) + 1.).abs() < 1e-5);
+
+        let cube = Sdf::cube(Vec3::splat(1.));
+        assert!((cube.distance(Vec3::new(0., 2., 0.)) - 1.).abs() < 1e-5);
+        assert!(cube.difference(Sdf::sphere(0.5)).distance(Vec3::ZERO) > 0.);
+    }
+
+    #[test]
+    fn mandelbulb_is_finite() {
+        for &iterations in [0, 1, 10].iter() {
+            let bulb = Sdf::mandelbulb(8., iterations);
+            for &p in [Vec3::ZERO, Vec3::new(0.3, 0.2, 0.1), Vec3::splat(2.)].iter() {
+                let d = bulb.distance(p);
+                assert!(
+                    d.is_finite(),
+                    "{} at {:?} with {} iterations",
+                    d,
+                    p,
+                    iterations
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn traces_a_sphere() {
+        let shape = SdfShape::new(
+            Sdf::sphere(1.),
+            AABB {
+                min: Vec3::splat(-1.),
+                max: Vec3::splat(1.),
+            },
+            Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+        );
+        let r = Ray::new(Vec3::new(0., 0., -5.), Vec3::new(0., 0., 1.), 0.);
+        let hit = shape.hit(&r, 0.001, f32::MAX).expect("no hit");
+        assert!((hit.t - 4.).abs() < 1e-3, "hit at {}", hit.t);
+        assert!((hit.normal - Vec3::new(0., 0., -1.)).length() < 1e-2);
+    }
+}
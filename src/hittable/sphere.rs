@@ -1,5 +1,5 @@
 use crate::{
-    hittable::{aabb::AABB, get_sphere_uv, HitRecord, Hittable},
+    hittable::{aabb::AABB, get_sphere_tangents, get_sphere_uv, HitRecord, Hittable},
     material::MaterialType,
     ray::Ray,
     vec3::Vec3,
@@ -26,7 +26,11 @@ impl Hittable for Sphere {
                 let point = r.point_at(t);
                 let normal = (point - self.center) / self.radius;
                 let (u, v) = get_sphere_uv(normal);
-                Some(HitRecord::new(t, u, v, point, normal, &self.mat))
+                let (dpdu, dpdv) = get_sphere_tangents(normal, self.radius);
+                Some(
+                    HitRecord::new(t, u, v, point, normal, dpdu, dpdv, &self.mat)
+                        .with_face_normal(r),
+                )
             };
 
             let mut t = (-b - discriminant.sqrt()) / a;
@@ -0,0 +1,230 @@
+use super::{aabb::AABB, get_azimuth, HitRecord, Hittable, Hittables};
+use crate::{material::MaterialType, ray::Ray, vec3::Vec3};
+
+/// Torus lying in the xz plane around `center`, `u` goes around the y axis and `v` around the
+/// tube, starting on its outer side
+#[derive(Clone)]
+pub struct Torus {
+    center: Vec3,
+    /// Distance from the center to the middle of the tube
+    major_radius: f32,
+    /// Radius of the tube
+    minor_radius: f32,
+    material: MaterialType,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vec3,
+        major_radius: f32,
+        minor_radius: f32,
+        material: MaterialType,
+    ) -> Hittables {
+        Hittables::from(Torus {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+        })
+    }
+
+    fn hit_record(&self, r: &Ray, t: f32) -> HitRecord {
+        use std::f32::consts::PI;
+
+        let point = r.point_at(t);
+        let local = point - self.center;
+        let (u, dpdu) = get_azimuth(local);
+
+        let radial = Vec3::new(local.x, 0., local.z).normalize();
+        let ring = local - self.major_radius * radial;
+        let normal = ring.normalize();
+
+        let theta = local.y.atan2(ring.dot(radial));
+        let theta = if theta < 0. { theta + 2. * PI } else { theta };
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let dpdv = 2. * PI * self.minor_radius * (cos_theta * Vec3::Y - sin_theta * radial);
+
+        HitRecord::new(
+            t,
+            u,
+            theta / (2. * PI),
+            point,
+            normal,
+            dpdu,
+            dpdv,
+            &self.material,
+        )
+        .with_face_normal(r)
+    }
+}
+
+impl Hittable for Torus {
+    #[allow(clippy::many_single_char_names)]
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let bbox = self.bounding_box(0., 0.)?;
+        let (t_near, t_far) = bbox.hit(r, t_min, t_max)?;
+
+        // Solving from the entry of the bounding box keeps the coefficients small
+        let o = r.point_at(t_near) - self.center;
+        let (ox, oy, oz) = (o.x as f64, o.y as f64, o.z as f64);
+        let d = r.direction;
+        let (dx, dy, dz) = (d.x as f64, d.y as f64, d.z as f64);
+        let big_r2 = (self.major_radius as f64).powi(2);
+        let small_r2 = (self.minor_radius as f64).powi(2);
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) with p = o + t d
+        let a = dx * dx + dy * dy + dz * dz;
+        let b = 2. * (ox * dx + oy * dy + oz * dz);
+        let c = ox * ox + oy * oy + oz * oz + big_r2 - small_r2;
+        let alpha = dx * dx + dz * dz;
+        let beta = 2. * (ox * dx + oz * dz);
+        let gamma = ox * ox + oz * oz;
+
+        let coefficients = [
+            c * c - 4. * big_r2 * gamma,
+            2. * b * c - 4. * big_r2 * beta,
+            b * b + 2. * a * c - 4. * big_r2 * alpha,
+            2. * a * b,
+            a * a,
+        ];
+
+        let t = solve_quartic(coefficients)
+            .into_iter()
+            .map(|t| polish_root(&coefficients, t) as f32 + t_near)
+            .filter(|t| *t > t_min && *t < t_far.min(t_max))
+            .min_by(|a, b| a.partial_cmp(b).unwrap())?;
+
+        Some(self.hit_record(r, t))
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = Vec3::new(outer, self.minor_radius, outer);
+        Some(AABB {
+            min: self.center - extent,
+            max: self.center + extent,
+        })
+    }
+}
+
+const EPSILON: f64 = 1e-9;
+
+fn is_zero(x: f64) -> bool {
+    x.abs() < EPSILON
+}
+
+/// Refines a root of `c[0] + c[1] t + ... + c[4] t^4` with a few Newton iterations
+fn polish_root(c: &[f64; 5], t: f64) -> f64 {
+    let mut t = t;
+    for _ in 0..2 {
+        let f = (((c[4] * t + c[3]) * t + c[2]) * t + c[1]) * t + c[0];
+        let df = ((4. * c[4] * t + 3. * c[3]) * t + 2. * c[2]) * t + c[1];
+        if df == 0. {
+            break;
+        }
+        t -= f / df;
+    }
+    t
+}
+
+/// Real roots of `c[0] + c[1] x + c[2] x^2`
+fn solve_quadratic(c: [f64; 3]) -> Vec<f64> {
+    let p = c[1] / (2. * c[2]);
+    let q = c[0] / c[2];
+    let discriminant = p * p - q;
+
+    if is_zero(discriminant) {
+        vec![-p]
+    } else if discriminant < 0. {
+        vec![]
+    } else {
+        let root = discriminant.sqrt();
+        vec![root - p, -root - p]
+    }
+}
+
+/// Real roots of `c[0] + c[1] x + c[2] x^2 + c[3] x^3`, with Cardano's method
+fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    use std::f64::consts::PI;
+
+    // Normal form x^3 + A x^2 + B x + C, then substitute x = y - A / 3 to remove the square
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let c = c[0] / c[3];
+
+    let p = (-a * a / 3. + b) / 3.;
+    let q = (2. / 27. * a * a * a - a * b / 3. + c) / 2.;
+    let p3 = p * p * p;
+    let discriminant = q * q + p3;
+
+    let roots = if is_zero(discriminant) {
+        if is_zero(q) {
+            vec![0.]
+        } else {
+            let u = (-q).cbrt();
+            vec![2. * u, -u]
+        }
+    } else if discriminant < 0. {
+        // Three real roots
+        let phi = (-q / (-p3).sqrt()).acos() / 3.;
+        let t = 2. * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + PI / 3.).cos(),
+            -t * (phi - PI / 3.).cos(),
+        ]
+    } else {
+        let root = discriminant.sqrt();
+        vec![(root - q).cbrt() - (root + q).cbrt()]
+    };
+
+    roots.into_iter().map(|y| y - a / 3.).collect()
+}
+
+/// Real roots of `c[0] + c[1] x + ... + c[4] x^4`, with Ferrari's method
+fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    // Normal form x^4 + A x^3 + B x^2 + C x + D, then substitute x = y - A / 4 to remove the cube
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let c_ = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    let a2 = a * a;
+    let p = -3. / 8. * a2 + b;
+    let q = a2 * a / 8. - a * b / 2. + c_;
+    let r = -3. / 256. * a2 * a2 + a2 * b / 16. - a * c_ / 4. + d;
+
+    let roots = if is_zero(r) {
+        // y (y^3 + p y + q) = 0
+        let mut roots = solve_cubic([q, p, 0., 1.]);
+        roots.push(0.);
+        roots
+    } else {
+        // Any root of the resolvent cubic splits the quartic into two quadratics
+        let z = solve_cubic([r * p / 2. - q * q / 8., -r, -p / 2., 1.])[0];
+
+        let u = z * z - r;
+        let v = 2. * z - p;
+        let u = if is_zero(u) {
+            0.
+        } else if u > 0. {
+            u.sqrt()
+        } else {
+            return vec![];
+        };
+        let v = if is_zero(v) {
+            0.
+        } else if v > 0. {
+            v.sqrt()
+        } else {
+            return vec![];
+        };
+
+        let v = if q < 0. { -v } else { v };
+        let mut roots = solve_quadratic([z - u, v, 1.]);
+        roots.extend(solve_quadratic([z + u, -v, 1.]));
+        roots
+    };
+
+    roots.into_iter().map(|y| y - a / 4.).collect()
+}
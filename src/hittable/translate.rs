@@ -18,7 +18,10 @@ impl Translate {
 
 impl Hittable for Translate {
     fn hit(&self, r: &crate::ray::Ray, t_min: f32, t_max: f32) -> Option<super::HitRecord> {
-        let moved_ray = Ray::new(r.origin - self.offset, r.direction, r.time);
+        let moved_ray = Ray {
+            origin: r.origin - self.offset,
+            ..*r
+        };
         match self.ptr.hit(&moved_ray, t_min, t_max) {
             Some(rec) => {
                 let mut rec = rec;
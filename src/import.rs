@@ -0,0 +1,477 @@
+//! Loading of glTF 2.0 scenes, see `Mesh::from_ply` for single PLY meshes
+
+use crate::{
+    camera::{CameraConfig, CameraConfigBuilder, CameraType},
+    hittable::{
+        mesh::{Mesh, MeshAttributes, MeshData},
+        sphere::Sphere,
+        Hittables,
+    },
+    material::{Dielectric, DiffuseLight, Lambertian, MaterialType, Metal, NormalMapped},
+    texture::{
+        constant_texture::ConstantTexture,
+        image_texture::{ColorSpace, ImageTexture, MipMap},
+        vertex_color_texture::VertexColorTexture,
+        TextureType,
+    },
+    vec3::Vec3,
+};
+use glam::{Mat4, Vec2};
+use gltf::{camera::Projection, image::Format, khr_lights_punctual::Kind, texture, Node};
+use image::{DynamicImage, ImageBuffer};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Luminous efficacy used to turn the photometric units of glTF lights into radiometric ones
+const LUMENS_PER_WATT: f32 = 683.;
+
+/// Radius of the spheres standing in for point and spot lights
+const POINT_LIGHT_RADIUS: f32 = 0.05;
+
+/// Angular radius in degrees of the spheres standing in for directional lights, wider than the
+/// sun so paths find them without too much noise
+const DIRECTIONAL_LIGHT_ANGLE: f32 = 5.;
+
+/// Distance of the spheres standing in for directional lights
+const DIRECTIONAL_LIGHT_DISTANCE: f32 = 1000.;
+
+/// Perspective or orthographic camera of an imported scene
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImportedCamera {
+    pub lookfrom: Vec3,
+    pub lookat: Vec3,
+    pub vup: Vec3,
+    /// Vertical field of view in degrees, unused by orthographic cameras
+    pub vfov: f32,
+    pub aspect: Option<f32>,
+    pub camera_type: CameraType,
+}
+
+impl ImportedCamera {
+    pub fn config(&self, width: u32, height: u32) -> CameraConfig {
+        let mut builder = CameraConfigBuilder::default();
+        builder
+            .lookfrom(self.lookfrom)
+            .lookat(self.lookat)
+            .vup(self.vup)
+            .vfov(self.vfov)
+            .camera_type(self.camera_type)
+            .focus_dist((self.lookat - self.lookfrom).length())
+            .width(width)
+            .height(height);
+        if let Some(aspect) = self.aspect {
+            builder.aspect(aspect);
+        }
+        builder.build().unwrap()
+    }
+}
+
+pub struct ImportedScene {
+    pub hittables: Vec<Hittables>,
+    pub cameras: Vec<ImportedCamera>,
+    /// The scene file and the external buffers and images it references
+    pub files: Vec<PathBuf>,
+}
+
+/// Decoded textures and mesh BVHs of the last import, keyed by a hash of their content
+///
+/// Reusing the cache when a scene is loaded again only rebuilds the textures and meshes that
+/// changed.
+#[derive(Default)]
+pub struct ImportCache {
+    textures: HashMap<(u64, ColorSpace), Arc<MipMap>>,
+    meshes: HashMap<u64, Arc<MeshData>>,
+}
+
+/// Loads the default scene of a `.gltf` or `.glb` file
+///
+/// Every mesh primitive becomes a `Mesh` with the transforms of its nodes applied to its
+/// vertices. Metallic-roughness materials are mapped to the closest `MaterialType`, emissive
+/// ones become lights, transmissive ones glass and metallic ones metal, the rest is
+/// lambertian. Punctual lights become small emissive spheres, spot lights shine in every
+/// direction like point lights.
+pub fn load_gltf(path: impl AsRef<Path>) -> gltf::Result<ImportedScene> {
+    load_gltf_cached(path, &mut ImportCache::default())
+}
+
+/// See `load_gltf`, the textures and meshes found in `cache` are reused and `cache` is left with
+/// the ones of this scene
+pub fn load_gltf_cached(
+    path: impl AsRef<Path>,
+    cache: &mut ImportCache,
+) -> gltf::Result<ImportedScene> {
+    let path = path.as_ref();
+    let (document, buffers, images) = gltf::import(path)?;
+
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    let buffer_uris = document
+        .buffers()
+        .filter_map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Uri(uri) => Some(uri),
+            gltf::buffer::Source::Bin => None,
+        });
+    let image_uris = document.images().filter_map(|image| match image.source() {
+        gltf::image::Source::Uri { uri, .. } => Some(uri),
+        gltf::image::Source::View { .. } => None,
+    });
+    let mut files = vec![path.to_path_buf()];
+    files.extend(
+        buffer_uris
+            .chain(image_uris)
+            .filter_map(|uri| external_file(base, uri)),
+    );
+
+    let mut importer = Importer {
+        buffers: &buffers,
+        images: &images,
+        textures: HashMap::new(),
+        cache,
+        used: ImportCache::default(),
+        scene: ImportedScene {
+            hittables: Vec::new(),
+            cameras: Vec::new(),
+            files,
+        },
+    };
+
+    if let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        for node in scene.nodes() {
+            importer.node(&node, Mat4::IDENTITY)?;
+        }
+    }
+
+    let Importer { used, scene, .. } = importer;
+    *cache = used;
+    Ok(scene)
+}
+
+/// Path of the file `uri` points to, relative to `base`, if it is not embedded
+fn external_file(base: &Path, uri: &str) -> Option<PathBuf> {
+    if let Some(path) = uri
+        .strip_prefix("file://")
+        .or_else(|| uri.strip_prefix("file:"))
+    {
+        Some(PathBuf::from(path))
+    } else if uri.contains(':') {
+        None
+    } else {
+        Some(base.join(uri))
+    }
+}
+
+struct Importer<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    /// Decoded images by index and colour space, shared between materials
+    textures: HashMap<(usize, ColorSpace), Arc<MipMap>>,
+    /// Content of the previous import
+    cache: &'a ImportCache,
+    /// Content of this import, the next cache
+    used: ImportCache,
+    scene: ImportedScene,
+}
+
+impl<'a> Importer<'a> {
+    fn node(&mut self, node: &Node, parent: Mat4) -> gltf::Result<()> {
+        let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+                let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+                let positions: Vec<Vec3> = match reader.read_positions() {
+                    Some(positions) => positions
+                        .map(|p| transform.transform_point3(Vec3::from(p)))
+                        .collect(),
+                    None => continue,
+                };
+
+                let normal_matrix = transform.inverse().transpose();
+                let normals = reader.read_normals().map_or_else(Vec::new, |normals| {
+                    normals
+                        .map(|n| normal_matrix.transform_vector3(Vec3::from(n)).normalize())
+                        .collect()
+                });
+                // glTF puts the origin of uvs at the top of images
+                let uvs = reader.read_tex_coords(0).map_or_else(Vec::new, |uvs| {
+                    uvs.into_f32().map(|[u, v]| Vec2::new(u, 1. - v)).collect()
+                });
+                let colors: Vec<Vec3> = reader.read_colors(0).map_or_else(Vec::new, |colors| {
+                    colors.into_rgb_f32().map(Vec3::from).collect()
+                });
+                let indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..positions.len() as u32).collect(),
+                };
+                let triangles: Vec<[u32; 3]> = indices
+                    .chunks_exact(3)
+                    .map(|t| [t[0], t[1], t[2]])
+                    .collect();
+                if triangles.is_empty() {
+                    continue;
+                }
+
+                let material = self.material(&primitive.material(), !colors.is_empty())?;
+                let data = self.mesh_data(MeshAttributes {
+                    positions,
+                    normals,
+                    uvs,
+                    colors,
+                    triangles,
+                })?;
+                self.scene.hittables.push(Mesh::with_data(data, material));
+            }
+        }
+
+        if let Some(camera) = node.camera() {
+            let lookfrom = transform.transform_point3(Vec3::ZERO);
+            let forward = transform.transform_vector3(-Vec3::Z).normalize();
+            let (vfov, aspect, camera_type) = match camera.projection() {
+                Projection::Perspective(perspective) => (
+                    perspective.yfov().to_degrees(),
+                    perspective.aspect_ratio(),
+                    CameraType::Perspective,
+                ),
+                Projection::Orthographic(orthographic) => (
+                    0.,
+                    Some(orthographic.xmag() / orthographic.ymag()),
+                    CameraType::Orthographic {
+                        height: 2. * orthographic.ymag(),
+                    },
+                ),
+            };
+            self.scene.cameras.push(ImportedCamera {
+                lookfrom,
+                lookat: lookfrom + forward,
+                vup: transform.transform_vector3(Vec3::Y).normalize(),
+                vfov,
+                aspect,
+                camera_type,
+            });
+        }
+
+        if let Some(light) = node.light() {
+            let color = Vec3::from(light.color()) * light.intensity() / LUMENS_PER_WATT;
+            let position = transform.transform_point3(Vec3::ZERO);
+            let (center, radius, radiance) = match light.kind() {
+                // Candela to radiance of a sphere with the same intensity
+                Kind::Point | Kind::Spot { .. } => (
+                    position,
+                    POINT_LIGHT_RADIUS,
+                    color / (std::f32::consts::PI * POINT_LIGHT_RADIUS * POINT_LIGHT_RADIUS),
+                ),
+                // Lux to radiance of a distant sphere giving the same irradiance
+                Kind::Directional => {
+                    let direction = transform.transform_vector3(-Vec3::Z).normalize();
+                    let ratio = DIRECTIONAL_LIGHT_ANGLE.to_radians().tan();
+                    (
+                        -direction * DIRECTIONAL_LIGHT_DISTANCE,
+                        ratio * DIRECTIONAL_LIGHT_DISTANCE,
+                        color / (std::f32::consts::PI * ratio * ratio),
+                    )
+                }
+            };
+
+            self.scene.hittables.push(Hittables::from(Sphere {
+                center,
+                radius,
+                mat: DiffuseLight::new(ConstantTexture::new(radiance.x, radiance.y, radiance.z)),
+            }));
+        }
+
+        for child in node.children() {
+            self.node(&child, transform)?;
+        }
+
+        Ok(())
+    }
+
+    fn material(
+        &mut self,
+        material: &gltf::Material,
+        vertex_colors: bool,
+    ) -> gltf::Result<MaterialType> {
+        let pbr = material.pbr_metallic_roughness();
+        let [red, green, blue, _] = pbr.base_color_factor();
+
+        let emissive = Vec3::from(material.emissive_factor());
+        if emissive.max_element() > 0. {
+            let emit = ConstantTexture::new(emissive.x, emissive.y, emissive.z);
+            return Ok(DiffuseLight::new(match material.emissive_texture() {
+                Some(info) => self.texture(&info.texture(), ColorSpace::Srgb)? * emit,
+                None => emit,
+            }));
+        }
+
+        let transmission = material
+            .transmission()
+            .map_or(0., |t| t.transmission_factor());
+        if transmission > 0.5 {
+            return Ok(MaterialType::from(Dielectric {
+                ref_idx: material.ior().unwrap_or(1.5),
+            }));
+        }
+
+        let mut albedo = ConstantTexture::new(red, green, blue);
+        if let Some(info) = pbr.base_color_texture() {
+            albedo = self.texture(&info.texture(), ColorSpace::Srgb)? * albedo;
+        }
+        if vertex_colors {
+            albedo = VertexColorTexture::new(ConstantTexture::new(1., 1., 1.)) * albedo;
+        }
+
+        let surface = if pbr.metallic_factor() > 0.5 {
+            let roughness = pbr.roughness_factor();
+            MaterialType::from(Metal {
+                albedo,
+                fuzz: roughness * roughness,
+            })
+        } else {
+            Lambertian::new(albedo)
+        };
+
+        Ok(match material.normal_texture() {
+            Some(normal) => NormalMapped::normal_map(
+                surface,
+                self.texture(&normal.texture(), ColorSpace::Linear)?,
+            ),
+            None => surface,
+        })
+    }
+
+    fn texture(
+        &mut self,
+        texture: &texture::Texture,
+        color_space: ColorSpace,
+    ) -> gltf::Result<TextureType> {
+        let index = texture.source().index();
+        if let Some(image) = self.textures.get(&(index, color_space)) {
+            return Ok(TextureType::from(ImageTexture::new(image.clone())));
+        }
+
+        let data = &self.images[index];
+        let mut hasher = DefaultHasher::new();
+        (&data.pixels, data.format, data.width, data.height).hash(&mut hasher);
+        let key = (hasher.finish(), color_space);
+        let image = match self.cache.textures.get(&key) {
+            Some(image) => image.clone(),
+            None => Arc::new(MipMap::from_image(&to_dynamic_image(data), color_space)?),
+        };
+        self.used.textures.insert(key, image.clone());
+        self.textures.insert((index, color_space), image.clone());
+        Ok(TextureType::from(ImageTexture::new(image)))
+    }
+
+    fn mesh_data(&mut self, attributes: MeshAttributes) -> io::Result<Arc<MeshData>> {
+        let mut hasher = DefaultHasher::new();
+        [
+            attributes.positions.len(),
+            attributes.normals.len(),
+            attributes.uvs.len(),
+            attributes.colors.len(),
+        ]
+        .hash(&mut hasher);
+        let vectors = attributes
+            .positions
+            .iter()
+            .chain(&attributes.normals)
+            .chain(&attributes.colors)
+            .flat_map(|v| v.to_array());
+        for value in vectors.chain(attributes.uvs.iter().flat_map(|uv| uv.to_array())) {
+            value.to_bits().hash(&mut hasher);
+        }
+        attributes.triangles.hash(&mut hasher);
+        let key = hasher.finish();
+
+        let data = match self.cache.meshes.get(&key) {
+            Some(data) => data.clone(),
+            None => Arc::new(MeshData::new(attributes)?),
+        };
+        self.used.meshes.insert(key, data.clone());
+        Ok(data)
+    }
+}
+
+fn to_dynamic_image(data: &gltf::image::Data) -> DynamicImage {
+    let (width, height) = (data.width, data.height);
+    let pixels = data.pixels.clone();
+    let image = match data.format {
+        Format::R8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8),
+        Format::R8G8B8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => {
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+        }
+        Format::B8G8R8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageBgr8),
+        Format::B8G8R8A8 => {
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageBgra8)
+        }
+        Format::R16 | Format::R16G16 | Format::R16G16B16 | Format::R16G16B16A16 => {
+            // 16 bit channels come in native byte order
+            let samples: Vec<u16> = pixels
+                .chunks_exact(2)
+                .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+                .collect();
+            match data.format {
+                Format::R16 => {
+                    ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLuma16)
+                }
+                Format::R16G16 => {
+                    ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageLumaA16)
+                }
+                Format::R16G16B16 => {
+                    ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgb16)
+                }
+                _ => ImageBuffer::from_raw(width, height, samples).map(DynamicImage::ImageRgba16),
+            }
+        }
+    };
+
+    image.unwrap_or_else(|| DynamicImage::new_rgba8(width.max(1), height.max(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    #[test]
+    fn sixteen_bit_images_keep_their_channels() {
+        let samples = [0x1234u16, 0xfedc, 0x0001, 0x8000];
+        let data = gltf::image::Data {
+            pixels: samples
+                .iter()
+                .flat_map(|s| s.to_ne_bytes().to_vec())
+                .collect(),
+            format: Format::R16G16,
+            width: 2,
+            height: 1,
+        };
+        match to_dynamic_image(&data) {
+            DynamicImage::ImageLumaA16(image) => assert_eq!(image.into_raw(), samples.to_vec()),
+            _ => panic!("expected a 16 bit luma alpha image"),
+        }
+    }
+
+    #[test]
+    fn mismatched_sizes_fall_back_to_an_empty_image() {
+        let data = gltf::image::Data {
+            pixels: vec![0; 5],
+            format: Format::R8G8B8,
+            width: 2,
+            height: 1,
+        };
+        let image = to_dynamic_image(&data);
+        assert_eq!((image.width(), image.height()), (2, 1));
+    }
+}
@@ -1,9 +1,17 @@
+pub mod aov;
 pub mod camera;
+pub mod checkpoint;
+pub mod denoise;
+pub mod distributed;
+pub mod filter;
 pub mod hittable;
+pub mod import;
 pub mod material;
 pub mod random;
 pub mod ray;
 pub mod renderer;
+pub mod sampler;
+pub mod stats;
 pub mod texture;
 pub mod utils;
 pub mod vec3;
@@ -1,16 +1,18 @@
-mod camera;
-mod hittable;
-mod material;
-mod random;
-mod ray;
-mod renderer;
+mod controls;
 mod scenes;
-mod texture;
-mod utils;
-mod vec3;
+mod watch;
 
 use std::io::prelude::*;
-use std::{fs::File, time::Instant};
+use std::{
+    env,
+    fs::File,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
 
 use pixels::{Error, Pixels, SurfaceTexture};
 use rand::{rngs::SmallRng, SeedableRng};
@@ -18,22 +20,40 @@ use structopt::StructOpt;
 use winit::{
     dpi::LogicalSize,
     event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     window::{Window, WindowBuilder},
 };
 
-use crate::{renderer::render, scenes::get_scene_from_name};
+use raytracing_weekend_rs::{
+    aov::{Aov, RenderOutput},
+    camera::{Camera, CameraConfig},
+    checkpoint::{files_hash, setup_hash, Checkpoint, RenderSetup},
+    denoise::Denoiser,
+    distributed::{self, Split},
+    filter::Filter,
+    hittable::Hittables,
+    import::ImportCache,
+    renderer::{render_samples, Accumulation, RenderSettings, SEED},
+    sampler::SamplerType,
+    stats::{self, Phases},
+};
+
+use crate::{
+    controls::Controls,
+    scenes::{get_scene_from_name, is_scene_file, scene_from_file, Scene},
+    watch::{FileWatcher, POLL_INTERVAL},
+};
 
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 800;
 
-fn init_pixels(window: &Window) -> Pixels {
-    let surface_texture = SurfaceTexture::new(WIDTH, HEIGHT, window);
-    Pixels::new(WIDTH, HEIGHT, surface_texture).expect("Failed to create a new Pixels instance")
+fn init_pixels(window: &Window, width: u32, height: u32) -> Pixels {
+    let surface_texture = SurfaceTexture::new(width, height, window);
+    Pixels::new(width, height, surface_texture).expect("Failed to create a new Pixels instance")
 }
 
-fn init_window(event_loop: &EventLoop<()>) -> Window {
-    let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
+fn init_window<T>(event_loop: &EventLoop<T>, width: u32, height: u32) -> Window {
+    let size = LogicalSize::new(width as f64, height as f64);
     WindowBuilder::new()
         .with_title("Rendering...")
         .with_inner_size(size)
@@ -63,27 +83,234 @@ struct Opts {
     /// Name of the scene to render
     #[structopt(short, long, default_value = "default")]
     scene_name: String,
+    /// Pixel filter: box, tent, gaussian, mitchell or lanczos
+    #[structopt(short, long, default_value = "box")]
+    filter: Filter,
+    /// Radius of the pixel filter in pixels, each filter has its own default
+    #[structopt(long)]
+    filter_radius: Option<f32>,
+    /// Sampler: independent, stratified, halton, sobol or blue_noise
+    #[structopt(long, default_value = "stratified")]
+    sampler: SamplerType,
+    /// Passes to render along the image, separated by commas: albedo, normal, camera_normal,
+    /// depth, position, material_id, object_id, emission, direct or indirect
+    #[structopt(long, use_delimiter = true)]
+    aov: Vec<Aov>,
+    /// Saves the linear image and its passes. A `.exr` file gets one layer per pass, any other
+    /// format gets the image with each pass in `<name>_<pass>.exr` next to it
+    #[structopt(short, long)]
+    output: Option<PathBuf>,
+    /// Denoises the image, guided by the albedo and normal passes which are rendered for it
+    #[structopt(long)]
+    denoise: bool,
+    /// Shows an image saved with `--output` instead of rendering a scene, to denoise it
+    #[structopt(short, long)]
+    input: Option<PathBuf>,
+    /// Prints the rays traced, the BVH traversal work and the time of each phase once the
+    /// image is complete
+    #[structopt(long)]
+    stats: bool,
+    /// Saves an image of the time spent on each pixel once the image is complete
+    #[structopt(long)]
+    heatmap: Option<PathBuf>,
+    /// Saves the samples rendered so far to this file every `--checkpoint-interval` seconds
+    /// and once the image is complete. Moving the camera starts the checkpoint over too
+    #[structopt(long)]
+    checkpoint: Option<PathBuf>,
+    /// Seconds between two checkpoints
+    #[structopt(long, default_value = "60")]
+    checkpoint_interval: u64,
+    /// Continues the render saved in a checkpoint, up to `--num-samples` which can be raised to
+    /// add samples to a finished image. The checkpoint keeps being updated unless
+    /// `--checkpoint` names another file
+    #[structopt(long)]
+    resume: Option<PathBuf>,
+    /// Renders without a window on the workers connecting to this address, like
+    /// `127.0.0.1:7878`, and saves the image to `--output`
+    #[structopt(long)]
+    coordinator: Option<String>,
+    /// Workers to start on this machine for `--coordinator`, with the same options
+    #[structopt(long, default_value = "0")]
+    spawn_workers: u32,
+    /// How `--coordinator` splits the work: tiles of the image, or samples for units covering
+    /// the whole image
+    #[structopt(long, default_value = "tiles")]
+    split: Split,
+    /// Renders for the coordinator at this address, the scene and the settings must be the
+    /// same as its own
+    #[structopt(long)]
+    worker: Option<String>,
+}
+
+/// Image shown in the window and how far its render got
+struct Frame {
+    pixels: Vec<u8>,
+    samples: u32,
+    num_samples: u32,
+    elapsed: Duration,
+}
+
+/// What the render thread works on, `generation` changes whenever it must start over
+struct Job {
+    generation: u64,
+    world: Arc<Hittables>,
+    camera: CameraConfig,
+    settings: RenderSettings,
+    /// Time taken to build `world`
+    phases: Phases,
+    /// Seed `world` was generated with and hash of the files it was read from, for the
+    /// checkpoints
+    scene_seed: u64,
+    files_hash: u64,
+    /// Samples of a checkpoint to continue from instead of starting over
+    resume: Option<Accumulation>,
+}
+
+/// Scene with the time taken to build it and the files it was read from
+struct LoadedScene {
+    scene: Scene,
+    phases: Phases,
+    files: Vec<PathBuf>,
+    files_hash: u64,
+}
+
+enum PreviewEvent {
+    /// A new frame is ready
+    Frame,
+    /// The scene file or one of its textures changed and was loaded again
+    Reloaded(Result<Box<LoadedScene>, String>),
 }
 
 fn main() -> Result<(), Error> {
     let opts: Opts = Opts::from_args();
-    let rng = &mut SmallRng::from_entropy();
-    let scene = get_scene_from_name(opts.scene_name.as_str(), rng);
+
+    match (&opts.worker, &opts.coordinator, &opts.input) {
+        (Some(address), _, _) => {
+            work(&opts, address);
+            Ok(())
+        }
+        (None, Some(address), _) => {
+            coordinate(&opts, address);
+            Ok(())
+        }
+        (None, None, Some(path)) => show_image(&opts, path),
+        (None, None, None) => preview(opts),
+    }
+}
+
+/// Shows an image from the disk, denoised and saved again when asked to
+fn show_image(opts: &Opts, path: &Path) -> Result<(), Error> {
+    let mut output = RenderOutput::open(path).unwrap_or_else(|e| {
+        eprintln!("Failed to open {}: {}", path.display(), e);
+        std::process::exit(1);
+    });
+    if opts.denoise {
+        output.beauty = Denoiser::default().denoise(&output);
+    }
+    if let Some(path) = &opts.output {
+        if let Err(e) = output.save(path) {
+            eprintln!("Failed to save {}: {}", path.display(), e);
+        }
+    }
 
     let event_loop = EventLoop::new();
-    let window = init_window(&event_loop);
-    let mut pixels = init_pixels(&window);
+    let window = init_window(&event_loop, output.width, output.height);
+    let mut pixels = init_pixels(&window, output.width, output.height);
+    pixels.get_frame().copy_from_slice(&output.to_rgba8());
+    window.set_title(&path.display().to_string());
 
-    let start = Instant::now();
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                window_id,
+            } if window_id == window.id() => *control_flow = ControlFlow::Exit,
+            Event::RedrawRequested(_) => {
+                pixels.render().expect("Failed to render with pixels");
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Resized(new_size),
+                ..
+            } => pixels.resize_surface(new_size.width, new_size.height),
+            _ => (),
+        }
+    });
+}
+
+/// Renders the scene a sample per pixel at a time, starting over whenever the controls change
+/// the camera or the settings
+fn preview(opts: Opts) -> Result<(), Error> {
+    let checkpoint = opts.resume.as_ref().map(|path| {
+        Checkpoint::open(path).unwrap_or_else(|e| {
+            eprintln!("Failed to open {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+    // Generated scenes are only the same again from the same seed
+    let scene_seed = match &checkpoint {
+        Some(checkpoint) => checkpoint.setup.scene_seed,
+        None => rand::random(),
+    };
+    let mut cache = ImportCache::default();
+    let LoadedScene {
+        scene,
+        phases,
+        files,
+        files_hash,
+    } = load_scene(&opts.scene_name, scene_seed, &mut cache).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
 
-    let rendered_pixels = render(scene.camera, &scene.hittables, opts.num_samples, opts.depth);
-    // render_to_file(&rendered_pixels);
-    pixels.get_frame().copy_from_slice(&rendered_pixels[..]);
+    let strata = checkpoint
+        .as_ref()
+        .map(|checkpoint| checkpoint.setup.strata);
+    let settings = render_settings(&opts, strata);
+    let resume = checkpoint.map(|checkpoint| {
+        let setup = render_setup(&opts, scene_seed, files_hash, &scene.camera, &settings);
+        if let Err(e) = checkpoint.validate(&setup) {
+            eprintln!("Cannot resume: {}", e);
+            std::process::exit(1);
+        }
+        checkpoint.image
+    });
+    // Reloading the scene only moves the view when the camera of the file changed
+    let mut file_camera = scene.camera.config.clone();
+    let mut controls = Controls::new(file_camera.clone(), settings.clone());
+    let mut error: Option<String> = None;
 
-    let end = Instant::now();
-    let time_to_render = end.duration_since(start);
+    let event_loop = EventLoop::with_user_event();
+    let window = init_window(&event_loop, scene.camera.width, scene.camera.height);
+    let mut pixels = init_pixels(&window, scene.camera.width, scene.camera.height);
 
-    window.set_title(&format!("Completed in {:?}", time_to_render));
+    let job = Arc::new((
+        Mutex::new(Job {
+            generation: 0,
+            world: Arc::new(scene.hittables),
+            camera: controls.camera.clone(),
+            settings,
+            phases,
+            scene_seed,
+            files_hash,
+            resume,
+        }),
+        Condvar::new(),
+    ));
+    let frame = Arc::new(Mutex::new(None));
+    let scene_name = opts.scene_name.clone();
+    if !files.is_empty() {
+        let proxy = event_loop.create_proxy();
+        let path = scene_name.clone();
+        thread::spawn(move || watch_scene(&path, scene_seed, files, cache, &proxy));
+    }
+    {
+        let job = job.clone();
+        let frame = frame.clone();
+        let proxy = event_loop.create_proxy();
+        thread::spawn(move || render_progressively(&job, &frame, &proxy, &opts));
+    }
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
@@ -100,7 +327,410 @@ fn main() -> Result<(), Error> {
                 event: WindowEvent::Resized(new_size),
                 ..
             } => pixels.resize_surface(new_size.width, new_size.height),
+            Event::WindowEvent { event, .. } if controls.handle(&event) => {
+                restart(&job, |job| {
+                    job.camera = controls.camera.clone();
+                    job.settings = controls.settings.clone();
+                });
+            }
+            Event::UserEvent(PreviewEvent::Frame) => {
+                if let Some(frame) = frame.lock().unwrap().take() {
+                    pixels.get_frame().copy_from_slice(&frame.pixels[..]);
+                    if let Some(error) = &error {
+                        window.set_title(error);
+                    } else if frame.samples >= frame.num_samples {
+                        window.set_title(&format!("Completed in {:?}", frame.elapsed));
+                    } else {
+                        window.set_title(&format!(
+                            "Rendering... {}/{} samples",
+                            frame.samples, frame.num_samples
+                        ));
+                    }
+                    window.request_redraw();
+                }
+            }
+            Event::UserEvent(PreviewEvent::Reloaded(Ok(loaded))) => {
+                println!("{} reloaded", scene_name);
+                error = None;
+                let LoadedScene {
+                    scene,
+                    phases,
+                    files_hash,
+                    ..
+                } = *loaded;
+                if scene.camera.config != file_camera {
+                    file_camera = scene.camera.config.clone();
+                    controls.camera = file_camera.clone();
+                }
+                restart(&job, |job| {
+                    job.world = Arc::new(scene.hittables);
+                    job.camera = controls.camera.clone();
+                    job.phases = phases;
+                    job.files_hash = files_hash;
+                });
+            }
+            Event::UserEvent(PreviewEvent::Reloaded(Err(e))) => {
+                eprintln!("{}", e);
+                window.set_title(&e);
+                error = Some(e);
+            }
             _ => (),
         }
     });
 }
+
+/// Makes the render thread start over on the job changed by `update`
+fn restart(job: &(Mutex<Job>, Condvar), update: impl FnOnce(&mut Job)) {
+    let (lock, changed) = job;
+    let mut job = lock.lock().unwrap();
+    job.generation += 1;
+    job.resume = None;
+    update(&mut job);
+    changed.notify_one();
+}
+
+/// Settings given by the options, `strata` is kept from the render samples are added to
+fn render_settings(opts: &Opts, strata: Option<u32>) -> RenderSettings {
+    let filter = match opts.filter_radius {
+        Some(radius) => opts.filter.with_radius(radius),
+        None => opts.filter,
+    };
+    let mut aovs = opts.aov.clone();
+    if opts.denoise {
+        for guide in [Aov::Albedo, Aov::Normal].iter() {
+            if !aovs.contains(guide) {
+                aovs.push(*guide);
+            }
+        }
+    }
+    RenderSettings {
+        num_samples: opts.num_samples.max(1) as u32,
+        max_depth: opts.depth,
+        filter,
+        sampler: opts.sampler,
+        aovs,
+        strata,
+    }
+}
+
+/// What rendering the scene of the options through `camera` is, to check it against a
+/// checkpoint or a coordinator
+fn render_setup(
+    opts: &Opts,
+    scene_seed: u64,
+    files_hash: u64,
+    camera: &Camera,
+    settings: &RenderSettings,
+) -> RenderSetup {
+    RenderSetup {
+        scene: opts.scene_name.clone(),
+        scene_seed,
+        files_hash,
+        setup_hash: setup_hash(&camera.config, settings),
+        width: camera.width,
+        height: camera.height,
+        strata: settings.strata.unwrap_or(settings.num_samples),
+    }
+}
+
+/// Builds the scene `name`, from `seed` for the generated ones
+fn load_scene(name: &str, seed: u64, cache: &mut ImportCache) -> Result<LoadedScene, String> {
+    let (scene, phases) = timed_build(|| {
+        if is_scene_file(name) {
+            scene_from_file(name, cache)
+        } else {
+            let rng = &mut SmallRng::seed_from_u64(seed);
+            Ok((get_scene_from_name(name, rng), Vec::new()))
+        }
+    });
+    let (scene, files) = scene?;
+    Ok(LoadedScene {
+        scene,
+        phases,
+        files_hash: files_hash(&files),
+        files,
+    })
+}
+
+/// Runs `build`, timing the BVHs it builds apart from the rest
+fn timed_build<T>(build: impl FnOnce() -> T) -> (T, Phases) {
+    let start = Instant::now();
+    let bvh_start = stats::bvh_build_time();
+    let result = build();
+    let bvh_build = stats::bvh_build_time() - bvh_start;
+    let phases = Phases {
+        scene_build: start.elapsed() - bvh_build,
+        bvh_build,
+        ..Phases::default()
+    };
+    (result, phases)
+}
+
+/// Adds samples to the image of the current job and hands each new frame to the window until
+/// it is closed. The image is checkpointed regularly, and denoised and saved once all its
+/// samples are in
+fn render_progressively(
+    job: &(Mutex<Job>, Condvar),
+    frame: &Mutex<Option<Frame>>,
+    proxy: &EventLoopProxy<PreviewEvent>,
+    opts: &Opts,
+) {
+    let (lock, changed) = job;
+    let mut generation = None;
+    let mut current = None;
+    let mut samples = 0;
+    let mut start = Instant::now();
+    let mut last_checkpoint = Instant::now();
+    let checkpoint_path = opts.checkpoint.as_ref().or(opts.resume.as_ref());
+
+    loop {
+        let (world, camera, settings, checkpoint, phases) = {
+            let mut job = lock.lock().unwrap();
+            while generation == Some(job.generation) && samples >= job.settings.num_samples {
+                job = changed.wait(job).unwrap();
+            }
+            if generation != Some(job.generation) {
+                generation = Some(job.generation);
+                current = None;
+                start = Instant::now();
+                last_checkpoint = Instant::now();
+            }
+            current.get_or_insert_with(|| {
+                let camera = Camera::new(job.camera.clone());
+                let image = job.resume.take().unwrap_or_else(|| {
+                    Accumulation::new(0, 0, camera.width, camera.height, &job.settings.aovs)
+                });
+                samples = image.samples.first().copied().unwrap_or(0);
+                let checkpoint = Checkpoint {
+                    setup: render_setup(
+                        opts,
+                        job.scene_seed,
+                        job.files_hash,
+                        &camera,
+                        &job.settings,
+                    ),
+                    sampler_seed: SEED,
+                    image,
+                };
+                (
+                    job.world.clone(),
+                    camera,
+                    job.settings.clone(),
+                    checkpoint,
+                    job.phases,
+                )
+            })
+        };
+        let image = &mut checkpoint.image;
+
+        // A resumed render may already have all its samples
+        if samples < settings.num_samples {
+            let pass_start = Instant::now();
+            let pass = render_samples(camera, world, settings, samples..samples + 1);
+            phases.render += pass_start.elapsed();
+            if generation != Some(lock.lock().unwrap().generation) {
+                continue;
+            }
+            image.add(&pass);
+            samples += 1;
+
+            let interval = Duration::from_secs(opts.checkpoint_interval);
+            if let Some(path) = checkpoint_path {
+                if samples >= settings.num_samples || last_checkpoint.elapsed() >= interval {
+                    if let Err(e) = checkpoint.save(path) {
+                        eprintln!("Failed to save {}: {}", path.display(), e);
+                    }
+                    last_checkpoint = Instant::now();
+                }
+            }
+        }
+
+        let output = if samples >= settings.num_samples {
+            finish(opts, &checkpoint.image, phases)
+        } else {
+            checkpoint.image.output()
+        };
+        let rendered_pixels = output.to_rgba8();
+        // render_to_file(&rendered_pixels);
+
+        *frame.lock().unwrap() = Some(Frame {
+            pixels: rendered_pixels,
+            samples,
+            num_samples: settings.num_samples,
+            elapsed: start.elapsed(),
+        });
+        if proxy.send_event(PreviewEvent::Frame).is_err() {
+            return;
+        }
+    }
+}
+
+/// Denoises and saves the complete `image` as the options ask, and reports its stats
+fn finish(opts: &Opts, image: &Accumulation, phases: &mut Phases) -> RenderOutput {
+    let mut output = image.output();
+    let start = Instant::now();
+    if opts.denoise {
+        output.beauty = Denoiser::default().denoise(&output);
+    }
+    if let Some(path) = &opts.output {
+        if let Err(e) = output.save(path) {
+            eprintln!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+    phases.post_process = start.elapsed();
+
+    if opts.stats {
+        print!("{}", image.stats.report(phases));
+    }
+    if let Some(path) = &opts.heatmap {
+        if let Err(e) = stats::save_heatmap(&image.cost, image.width, image.height, path) {
+            eprintln!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+    output
+}
+
+/// Renders the image on the workers connecting to `address` and saves it
+fn coordinate(opts: &Opts, address: &str) {
+    let fail = |message: String| -> ! {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    };
+    if opts.output.is_none() {
+        fail("A distributed render needs --output".to_string());
+    }
+
+    let scene_seed = rand::random();
+    let LoadedScene {
+        scene,
+        mut phases,
+        files_hash,
+        ..
+    } = load_scene(&opts.scene_name, scene_seed, &mut ImportCache::default())
+        .unwrap_or_else(|e| fail(e));
+    let settings = render_settings(opts, None);
+    let setup = render_setup(opts, scene_seed, files_hash, &scene.camera, &settings);
+
+    let listener = TcpListener::bind(address)
+        .unwrap_or_else(|e| fail(format!("Failed to listen on {}: {}", address, e)));
+    let address = listener
+        .local_addr()
+        .unwrap_or_else(|e| fail(e.to_string()));
+    let mut workers: Vec<Child> = (0..opts.spawn_workers)
+        .map(|_| {
+            let program = env::current_exe()?;
+            Command::new(program)
+                .args(env::args_os().skip(1))
+                .arg("--worker")
+                .arg(address.to_string())
+                .spawn()
+        })
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| fail(format!("Failed to start a worker: {}", e)));
+    if workers.is_empty() {
+        println!("Waiting for workers on {}", address);
+    }
+
+    let units = distributed::split(
+        opts.split,
+        setup.width,
+        setup.height,
+        0..settings.num_samples,
+    );
+    let mut image = Accumulation::new(0, 0, setup.width, setup.height, &settings.aovs);
+    let start = Instant::now();
+    let result = distributed::coordinate(
+        listener,
+        &setup,
+        &settings,
+        units,
+        &mut image,
+        || {
+            // Workers started by hand can join at any time
+            workers.is_empty()
+                || workers
+                    .iter_mut()
+                    .any(|worker| matches!(worker.try_wait(), Ok(None)))
+        },
+        |report| {
+            if report.is_error() {
+                eprintln!("{}", report)
+            } else {
+                println!("{}", report)
+            }
+        },
+    );
+    phases.render = start.elapsed();
+    if let Err(e) = result {
+        for worker in &mut workers {
+            let _ = worker.kill();
+        }
+        fail(format!("Distributed render failed: {}", e));
+    }
+    for worker in &mut workers {
+        let _ = worker.wait();
+    }
+
+    finish(opts, &image, &mut phases);
+    if let Some(path) = &opts.checkpoint {
+        let checkpoint = Checkpoint {
+            setup,
+            sampler_seed: SEED,
+            image,
+        };
+        if let Err(e) = checkpoint.save(path) {
+            eprintln!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Renders the units of the coordinator at `address`
+fn work(opts: &Opts, address: &str) {
+    let result = distributed::work(address, |setup| {
+        let loaded = load_scene(
+            &opts.scene_name,
+            setup.scene_seed,
+            &mut ImportCache::default(),
+        )?;
+        let settings = render_settings(opts, Some(setup.strata));
+        let camera = loaded.scene.camera;
+        setup.validate(&render_setup(
+            opts,
+            setup.scene_seed,
+            loaded.files_hash,
+            &camera,
+            &settings,
+        ))?;
+        Ok((loaded.scene.hittables, camera, settings))
+    });
+    if let Err(e) = result {
+        eprintln!("Worker failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Loads the scene file at `path` again whenever it or one of the files it references changes
+fn watch_scene(
+    path: &str,
+    seed: u64,
+    files: Vec<PathBuf>,
+    mut cache: ImportCache,
+    proxy: &EventLoopProxy<PreviewEvent>,
+) {
+    let mut watcher = FileWatcher::new(files);
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if !watcher.changed() {
+            continue;
+        }
+
+        let scene = load_scene(path, seed, &mut cache).map(|loaded| {
+            // The scene may reference other textures now
+            watcher.set_files(loaded.files.clone());
+            Box::new(loaded)
+        });
+        if proxy.send_event(PreviewEvent::Reloaded(scene)).is_err() {
+            return;
+        }
+    }
+}
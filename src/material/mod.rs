@@ -1,13 +1,13 @@
 #![allow(clippy::new_ret_no_self)]
 
 use enum_dispatch::enum_dispatch;
-use rand::Rng;
 
 use crate::{
     hittable::HitRecord,
-    random::random_double,
     ray::Ray,
-    texture::{Texture, TextureType},
+    sampler::Sampler,
+    texture::{Texture, TextureContext, TextureType},
+    utils::clamp,
     vec3::Vec3,
 };
 use utils::{random_in_unit_sphere, reflect, refract, schlick};
@@ -16,12 +16,29 @@ mod utils;
 
 #[enum_dispatch]
 pub trait Material: Clone {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut impl Rng) -> Option<(Ray, Vec3)>;
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord,
+        sampler: &mut impl Sampler,
+    ) -> Option<(Ray, Vec3)>;
 
     #[allow(unused)]
     fn emitted(&self, u: f32, v: f32, point: Vec3) -> Vec3 {
         Vec3::ZERO
     }
+
+    /// Colour of the surface regardless of the lighting, for the albedo pass and the denoiser
+    #[allow(unused)]
+    fn albedo(&self, ctx: &TextureContext) -> Vec3 {
+        Vec3::ONE
+    }
+
+    /// Normal the material shades `hit` with
+    #[allow(unused)]
+    fn shading_normal(&self, ray: &Ray, hit: &HitRecord) -> Vec3 {
+        hit.shading_normal
+    }
 }
 
 #[enum_dispatch(Material)]
@@ -32,6 +49,8 @@ pub enum MaterialType {
     Dielectric,
     DiffuseLight,
     Isotropic,
+    NormalMapped,
+    Hair,
 }
 
 #[derive(Clone)]
@@ -46,38 +65,56 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut impl Rng) -> Option<(Ray, Vec3)> {
-        let target = hit.point + hit.normal + random_in_unit_sphere(rng);
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord,
+        sampler: &mut impl Sampler,
+    ) -> Option<(Ray, Vec3)> {
+        let target = hit.point + hit.shading_normal + random_in_unit_sphere(sampler);
         Some((
             Ray::new(hit.point, target - hit.point, ray.time),
-            self.albedo.value(hit.u, hit.v, hit.point),
+            self.albedo.sample(&hit.texture_context(ray)),
         ))
     }
+
+    fn albedo(&self, ctx: &TextureContext) -> Vec3 {
+        self.albedo.sample(ctx)
+    }
 }
 
 #[derive(Clone)]
 pub struct Metal {
-    pub albedo: Vec3,
+    pub albedo: TextureType,
     pub fuzz: f32,
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut impl Rng) -> Option<(Ray, Vec3)> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord,
+        sampler: &mut impl Sampler,
+    ) -> Option<(Ray, Vec3)> {
         let fuzz = if self.fuzz < 1. { self.fuzz } else { 1. };
 
-        let reflected = reflect(ray.direction.normalize(), hit.normal);
+        let reflected = reflect(ray.direction.normalize(), hit.shading_normal);
         let scattered = Ray::new(
             hit.point,
-            reflected + fuzz * random_in_unit_sphere(rng),
+            reflected + fuzz * random_in_unit_sphere(sampler),
             ray.time,
         );
 
-        if scattered.direction.dot(hit.normal) > 0. {
-            Some((scattered, self.albedo))
+        if scattered.direction.dot(hit.shading_normal) > 0. {
+            Some((scattered, self.albedo.sample(&hit.texture_context(ray))))
         } else {
             None
         }
     }
+
+    fn albedo(&self, ctx: &TextureContext) -> Vec3 {
+        self.albedo.sample(ctx)
+    }
 }
 
 #[derive(Clone)]
@@ -86,26 +123,26 @@ pub struct Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut impl Rng) -> Option<(Ray, Vec3)> {
-        let reflected = reflect(ray.direction, hit.normal);
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord,
+        sampler: &mut impl Sampler,
+    ) -> Option<(Ray, Vec3)> {
+        let normal = hit.shading_normal;
+        let reflected = reflect(ray.direction, normal);
         let attenuation = Vec3::new(1., 1., 1.);
-        let outward_normal: Vec3;
-        let ni_over_nt: f32;
-        let cosine: f32;
-
-        if ray.direction.dot(hit.normal) > 0. {
-            outward_normal = -hit.normal;
-            ni_over_nt = self.ref_idx;
-            cosine = self.ref_idx * ray.direction.dot(hit.normal) / ray.direction.length()
+        let cosine = -ray.direction.dot(normal) / ray.direction.length();
+
+        let (ni_over_nt, cosine) = if hit.front_face {
+            (1. / self.ref_idx, cosine)
         } else {
-            outward_normal = hit.normal;
-            ni_over_nt = 1. / self.ref_idx;
-            cosine = -ray.direction.dot(hit.normal) / ray.direction.length()
-        }
+            (self.ref_idx, self.ref_idx * cosine)
+        };
 
-        let scattered = match refract(ray.direction, outward_normal, ni_over_nt) {
+        let scattered = match refract(ray.direction, normal, ni_over_nt) {
             Some(refracted) => {
-                if random_double(rng) > schlick(cosine, self.ref_idx) {
+                if sampler.get_1d() > schlick(cosine, self.ref_idx) {
                     refracted
                 } else {
                     reflected
@@ -130,7 +167,12 @@ impl DiffuseLight {
 }
 
 impl Material for DiffuseLight {
-    fn scatter(&self, _ray: &Ray, _hit: &HitRecord, _rng: &mut impl Rng) -> Option<(Ray, Vec3)> {
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        _hit: &HitRecord,
+        _sampler: &mut impl Sampler,
+    ) -> Option<(Ray, Vec3)> {
         None
     }
 
@@ -151,9 +193,219 @@ impl Isotropic {
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, _ray: &Ray, hit: &HitRecord, rng: &mut impl Rng) -> Option<(Ray, Vec3)> {
-        let scattered = Ray::new(hit.point, random_in_unit_sphere(rng), 0.0);
-        let attenuation = self.albedo.value(hit.u, hit.v, hit.point);
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord,
+        sampler: &mut impl Sampler,
+    ) -> Option<(Ray, Vec3)> {
+        let scattered = Ray::new(hit.point, random_in_unit_sphere(sampler), 0.0);
+        let attenuation = self.albedo.sample(&hit.texture_context(ray));
         Some((scattered, attenuation))
     }
+
+    fn albedo(&self, ctx: &TextureContext) -> Vec3 {
+        self.albedo.sample(ctx)
+    }
+}
+
+/// Perturbation applied to the shading normal of a surface
+#[derive(Clone)]
+pub enum NormalPerturbation {
+    /// Tangent space normal map, each channel encodes `[-1, 1]` as `[0, 1]`
+    NormalMap(TextureType),
+    /// Scalar height texture, only the first channel is used. `scale` converts it to world units
+    Bump { height: TextureType, scale: f32 },
+}
+
+impl NormalPerturbation {
+    fn perturb(&self, ray: &Ray, hit: &HitRecord) -> Vec3 {
+        let n = hit.shading_normal;
+        match self {
+            NormalPerturbation::NormalMap(texture) => {
+                let tangent = hit.dpdu - n * n.dot(hit.dpdu);
+                if tangent.length_squared() == 0. {
+                    return n;
+                }
+                let tangent = tangent.normalize();
+                let mut bitangent = n.cross(tangent);
+                if bitangent.dot(hit.dpdv) < 0. {
+                    bitangent = -bitangent;
+                }
+
+                let m = 2. * texture.sample(&hit.texture_context(ray)) - Vec3::ONE;
+                (m.x * tangent + m.y * bitangent + m.z * n).normalize()
+            }
+            NormalPerturbation::Bump { height, scale } => {
+                // Keep the finite difference step small in world space as well as in uv space
+                let du = (0.001 / hit.dpdu.length()).min(0.0005);
+                let dv = (0.001 / hit.dpdv.length()).min(0.0005);
+                let height_at =
+                    |u: f32, v: f32, p: Vec3| -> f32 { scale * height.value(u, v, p).x };
+
+                let displace = height_at(hit.u, hit.v, hit.point);
+                let u_displace = height_at(hit.u + du, hit.v, hit.point + du * hit.dpdu);
+                let v_displace = height_at(hit.u, hit.v + dv, hit.point + dv * hit.dpdv);
+
+                let dpdu = hit.dpdu + (u_displace - displace) / du * n;
+                let dpdv = hit.dpdv + (v_displace - displace) / dv * n;
+                let bumped = dpdu.cross(dpdv);
+                if bumped.length_squared() == 0. {
+                    return n;
+                }
+
+                let bumped = bumped.normalize();
+                if bumped.dot(n) < 0. {
+                    -bumped
+                } else {
+                    bumped
+                }
+            }
+        }
+    }
+}
+
+/// Wraps a material and perturbs the shading normal it sees
+#[derive(Clone)]
+pub struct NormalMapped {
+    pub material: Box<MaterialType>,
+    pub perturbation: NormalPerturbation,
+}
+
+impl NormalMapped {
+    pub fn normal_map(material: MaterialType, normal_map: TextureType) -> MaterialType {
+        MaterialType::from(NormalMapped {
+            material: Box::new(material),
+            perturbation: NormalPerturbation::NormalMap(normal_map),
+        })
+    }
+
+    pub fn bump(material: MaterialType, height: TextureType, scale: f32) -> MaterialType {
+        MaterialType::from(NormalMapped {
+            material: Box::new(material),
+            perturbation: NormalPerturbation::Bump { height, scale },
+        })
+    }
+}
+
+impl Material for NormalMapped {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord,
+        sampler: &mut impl Sampler,
+    ) -> Option<(Ray, Vec3)> {
+        let mut hit = *hit;
+        hit.shading_normal = self.perturbation.perturb(ray, &hit);
+        hit.mat = &self.material;
+        self.material.scatter(ray, &hit, sampler)
+    }
+
+    fn emitted(&self, u: f32, v: f32, point: Vec3) -> Vec3 {
+        self.material.emitted(u, v, point)
+    }
+
+    fn albedo(&self, ctx: &TextureContext) -> Vec3 {
+        self.material.albedo(ctx)
+    }
+
+    fn shading_normal(&self, ray: &Ray, hit: &HitRecord) -> Vec3 {
+        self.perturbation.perturb(ray, hit)
+    }
+}
+
+/// Fibre scattering for hair and fur, lit along the tangent `dpdu` of the hit
+///
+/// Light is reflected at the surface of the fibre (R), goes through it (TT) or is reflected once
+/// inside it (TRT). The last two are tinted by `color`, twice for TRT. Each lobe leaves with the
+/// longitudinal angle of the incoming light mirrored around the normal plane, blurred by
+/// `longitudinal_roughness` and tilted by the scale angle `shift` in radians.
+#[derive(Clone)]
+pub struct Hair {
+    pub color: TextureType,
+    /// Fraction of the light taking the R lobe
+    pub specular: f32,
+    pub longitudinal_roughness: f32,
+    /// Spread around the fibre, in `[0, 1]`
+    pub azimuthal_roughness: f32,
+    pub shift: f32,
+}
+
+impl Hair {
+    pub fn new(color: TextureType) -> MaterialType {
+        MaterialType::from(Hair {
+            color,
+            specular: 0.1,
+            longitudinal_roughness: 0.15,
+            azimuthal_roughness: 0.4,
+            shift: 0.035,
+        })
+    }
+}
+
+impl Material for Hair {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord,
+        sampler: &mut impl Sampler,
+    ) -> Option<(Ray, Vec3)> {
+        use std::f32::consts::PI;
+
+        if hit.dpdu.length_squared() == 0. {
+            return None;
+        }
+        let tangent = hit.dpdu.normalize();
+        let incoming = -ray.direction.normalize();
+        let sin_theta_i = clamp(incoming.dot(tangent), -1., 1.);
+
+        // Incoming direction and normal projected on the plane perpendicular to the fibre
+        let project = |v: Vec3| {
+            let v = v - tangent * tangent.dot(v);
+            if v.length_squared() > 0. {
+                Some(v.normalize())
+            } else {
+                None
+            }
+        };
+        let normal = project(hit.shading_normal)?;
+        let incoming_perp = project(incoming).unwrap_or(normal);
+
+        let color = self.color.sample(&hit.texture_context(ray));
+        let lobe = sampler.get_1d();
+        let (attenuation, shift, perp, spread) = if lobe < self.specular {
+            (
+                Vec3::ONE,
+                -2. * self.shift,
+                reflect(-incoming_perp, normal),
+                self.azimuthal_roughness,
+            )
+        } else if lobe < self.specular + 0.5 * (1. - self.specular) {
+            (color, self.shift, -incoming_perp, self.azimuthal_roughness)
+        } else {
+            (
+                color * color,
+                3. * self.shift,
+                reflect(-incoming_perp, normal),
+                2. * self.azimuthal_roughness,
+            )
+        };
+
+        // Sum of uniforms as a cheap bell shaped blur
+        let mut bell = || {
+            let u = sampler.get_2d();
+            u.x + u.y - 1.
+        };
+        let theta_o = -sin_theta_i.asin() + shift + self.longitudinal_roughness * bell();
+        let phi = spread * PI * bell();
+        let side = tangent.cross(perp);
+        let perp = phi.cos() * perp + phi.sin() * side;
+
+        let direction = theta_o.sin() * tangent + theta_o.cos() * perp;
+        Some((Ray::new(hit.point, direction, ray.time), attenuation))
+    }
+
+    fn albedo(&self, ctx: &TextureContext) -> Vec3 {
+        self.color.sample(ctx)
+    }
 }
@@ -1,5 +1,5 @@
-use crate::{random::random_double, vec3::Vec3};
-use rand::Rng;
+use crate::{sampler::Sampler, vec3::Vec3};
+use std::f32::consts::PI;
 
 pub fn schlick(cosine: f32, ref_idx: f32) -> f32 {
     let r0 = ((1. - ref_idx) / (1. + ref_idx)).powf(2.);
@@ -22,12 +22,11 @@ pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
     v - 2. * v.dot(n) * n
 }
 
-pub fn random_in_unit_sphere(rng: &mut impl Rng) -> Vec3 {
-    loop {
-        let p = 2.0 * Vec3::new(random_double(rng), random_double(rng), random_double(rng))
-            - Vec3::new(1., 1., 1.);
-        if p.length_squared() < 1. {
-            return p;
-        }
-    }
+/// Uniform point in the unit ball, as a uniform direction at a radius growing like the volume
+pub fn random_in_unit_sphere(sampler: &mut impl Sampler) -> Vec3 {
+    let u = sampler.get_2d();
+    let z = 1. - 2. * u.x;
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * u.y;
+    sampler.get_1d().cbrt() * Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
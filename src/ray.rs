@@ -6,6 +6,13 @@ pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
     pub time: f32,
+    /// Growth of the ray footprint per unit of distance, used to filter textures
+    #[new(value = "0.")]
+    pub spread: f32,
+    /// Uniform sample in `[0, 1)` for the free flight distance in participating media, drawn
+    /// from the sampler before the ray is traced
+    #[new(value = "0.5")]
+    pub medium_sample: f32,
 }
 
 impl Ray {
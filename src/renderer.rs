@@ -1,37 +1,129 @@
 use std::f32;
 
-use rand::rngs::SmallRng;
-use rand::{Rng, SeedableRng};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    ops::Range,
+    time::Instant,
+};
+
 use rayon::prelude::*;
 
 use crate::{
+    aov::{Aov, AovBuffer, RenderOutput},
     camera::Camera,
-    hittable::{Hittable, Hittables},
+    filter::Filter,
+    hittable::{HitRecord, Hittable, Hittables},
     material::Material,
-    random::random_double,
     ray::Ray,
+    sampler::{hash, Sampler, SamplerType},
+    stats::{self, RenderStats},
+    texture::TextureContext,
     vec3::{Vec3, Vec3Wrapper},
 };
 
-fn color(mut ray: Ray, world: &Hittables, max_depth: i32, rng: &mut impl Rng) -> Vec3 {
-    let mut color_accumulator = Vec3::ZERO;
-    // let mut color_accumulator = {
-    //     let t = 0.5 * (ray.direction.normalize().y + 1.0);
-    //     (1. - t) * Vec3::one() + t * Vec3::new(0.5, 0.7, 1.)
-    // };
+/// Light carried by a camera path, split by the number of bounces it took to reach a light
+#[derive(Default)]
+struct PathSample {
+    /// Light emitted by the first surface hit
+    emission: Vec3,
+    /// Light reaching the camera after a single bounce
+    direct: Vec3,
+    /// Light reaching the camera after two or more bounces
+    indirect: Vec3,
+    /// First surface hit by the camera ray
+    surface: Option<Surface>,
+}
+
+impl PathSample {
+    fn color(&self) -> Vec3 {
+        self.emission + self.direct + self.indirect
+    }
+}
+
+/// Geometric values of the first surface hit, for the AOVs
+struct Surface {
+    albedo: Vec3,
+    normal: Vec3,
+    position: Vec3,
+    distance: f32,
+    material_id: u32,
+    object_id: u32,
+}
+
+impl Surface {
+    fn new(ray: &Ray, hit: &HitRecord) -> Self {
+        let albedo = hit.mat.albedo(&hit.texture_context(ray));
+
+        // Materials are told apart by their kind and their albedo at the center of the texture
+        let mut hasher = DefaultHasher::new();
+        std::mem::discriminant(hit.mat).hash(&mut hasher);
+        let kind = hasher.finish();
+        let base = hit
+            .mat
+            .albedo(&TextureContext::new(0.5, 0.5, Vec3::ZERO))
+            .map(de_nan);
+        let material_id = hash(
+            &[
+                kind as u32,
+                (kind >> 32) as u32,
+                base.x.to_bits(),
+                base.y.to_bits(),
+                base.z.to_bits(),
+            ],
+            0,
+        );
+        Surface {
+            albedo,
+            normal: hit.mat.shading_normal(ray, hit),
+            position: hit.point,
+            distance: hit.t * ray.direction.length(),
+            // 0 is left for the background
+            material_id: (material_id as u32).max(1),
+            object_id: hit.object_id,
+        }
+    }
+}
+
+fn trace(
+    mut ray: Ray,
+    world: &Hittables,
+    max_depth: i32,
+    sampler: &mut impl Sampler,
+) -> PathSample {
+    let mut sample = PathSample::default();
     let mut bounces = 0;
     let mut strength = Vec3::ONE;
 
-    while let Some(hit) = world.hit(&ray, 0.001, f32::MAX) {
-        let emitted = hit.mat.emitted(hit.u, hit.v, hit.point);
-        color_accumulator += strength * emitted;
+    loop {
+        ray.medium_sample = sampler.get_1d();
+        stats::count(|c| {
+            if bounces == 0 {
+                c.camera_rays += 1
+            } else {
+                c.bounce_rays += 1
+            }
+        });
+        let hit = match world.hit(&ray, 0.001, f32::MAX) {
+            Some(hit) => hit,
+            None => break,
+        };
+        let emitted = strength * hit.mat.emitted(hit.u, hit.v, hit.point);
+        match bounces {
+            0 => {
+                sample.emission += emitted;
+                sample.surface = Some(Surface::new(&ray, &hit));
+            }
+            1 => sample.direct += emitted,
+            _ => sample.indirect += emitted,
+        }
 
-        match hit.mat.scatter(&ray, &hit, rng) {
+        match hit.mat.scatter(&ray, &hit, sampler) {
             Some((scattered, attenuation)) => {
                 ray = scattered;
                 strength *= attenuation;
             }
-            None => return color_accumulator,
+            None => return sample,
         }
 
         bounces += 1;
@@ -39,11 +131,17 @@ fn color(mut ray: Ray, world: &Hittables, max_depth: i32, rng: &mut impl Rng) ->
             break;
         }
     }
-    color_accumulator
+    sample
 }
 
 #[allow(dead_code)]
-fn colorr(r: &Ray, world: &Hittables, depth: i32, max_depth: i32, rng: &mut impl Rng) -> Vec3 {
+fn colorr(
+    r: &Ray,
+    world: &Hittables,
+    depth: i32,
+    max_depth: i32,
+    sampler: &mut impl Sampler,
+) -> Vec3 {
     match world.hit(r, 0.001, f32::MAX) {
         Some(hit) => {
             let emitted = hit.mat.emitted(hit.u, hit.v, hit.point);
@@ -51,9 +149,9 @@ fn colorr(r: &Ray, world: &Hittables, depth: i32, max_depth: i32, rng: &mut impl
                 return emitted;
             };
 
-            match hit.mat.scatter(r, &hit, rng) {
+            match hit.mat.scatter(r, &hit, sampler) {
                 Some((scattered, attenuation)) => {
-                    emitted + attenuation * colorr(&scattered, world, depth + 1, max_depth, rng)
+                    emitted + attenuation * colorr(&scattered, world, depth + 1, max_depth, sampler)
                 }
                 None => emitted,
             }
@@ -70,28 +168,489 @@ pub fn de_nan(v: f32) -> f32 {
     }
 }
 
-pub fn render(cam: Camera, world: &Hittables, num_samples: i32, max_depth: i32) -> Vec<u8> {
-    (0..cam.width * cam.height)
-        .into_par_iter()
-        .map_init(SmallRng::from_entropy, |rng, screen_pos| {
-            let i = screen_pos % cam.width;
-            let j = cam.height - 1 - screen_pos / cam.width; // reverse the height index
-
-            let mut col: Vec3 = Vec3::ZERO;
-            for _ in 0..num_samples {
-                let u = (i as f32 + random_double(rng)) / cam.width as f32;
-                let v = (j as f32 + random_double(rng)) / cam.height as f32;
-                let ray = cam.get_ray(u, v, rng);
-                col += color(ray, world, max_depth, rng).map(de_nan);
-                // col += colorr(&ray, world, 0, max_depth, rng).map(de_nan);
+/// Side of the square tiles rendered in parallel
+const TILE_SIZE: u32 = 32;
+
+/// Smallest sum of the weights of a pixel relative to the sum of their magnitudes, about half of
+/// a converged pixel of the widest Lanczos filter
+const MIN_WEIGHT_FRACTION: f32 = 0.25;
+
+/// Filtered samples of a region of the image, summed but not yet divided by their weights
+///
+/// Tiles keep a margin for the samples reaching their neighbours. Accumulations of separate
+/// samples of the same image add up to the accumulation of all of them.
+#[derive(Clone, Debug)]
+pub struct Accumulation {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Weighted sum of the colors and sum of the weights
+    pub pixels: Vec<(Vec3, f32)>,
+    /// Sum of the absolute values of the weights, the negative lobes of some filters can make
+    /// the sum of the weights tiny next to it
+    pub weight_magnitudes: Vec<f32>,
+    /// Weighted sums of the AOVs, weighted like the colors
+    pub aovs: Vec<(Aov, AovLayer)>,
+    /// Samples taken in each pixel, not counting the samples of its neighbours reaching it
+    pub samples: Vec<u32>,
+    /// Seconds spent on the samples of each pixel
+    pub cost: Vec<f32>,
+    pub stats: RenderStats,
+}
+
+#[derive(Clone, Debug)]
+pub enum AovLayer {
+    Color(Vec<Vec3>),
+    /// ID of the sample with the highest weight and its weight
+    Id(Vec<(u32, f32)>),
+}
+
+impl Accumulation {
+    pub fn new(x: u32, y: u32, width: u32, height: u32, aovs: &[Aov]) -> Self {
+        let size = width as usize * height as usize;
+        Accumulation {
+            x,
+            y,
+            width,
+            height,
+            pixels: vec![(Vec3::ZERO, 0.); size],
+            weight_magnitudes: vec![0.; size],
+            aovs: aovs
+                .iter()
+                .map(|aov| {
+                    let layer = if aov.is_id() {
+                        AovLayer::Id(vec![(0, f32::MIN); size])
+                    } else {
+                        AovLayer::Color(vec![Vec3::ZERO; size])
+                    };
+                    (*aov, layer)
+                })
+                .collect(),
+            samples: vec![0; size],
+            cost: vec![0.; size],
+            stats: RenderStats::default(),
+        }
+    }
+
+    /// Adds the samples of `other`, which must lie inside this region and hold the same AOVs
+    pub fn add(&mut self, other: &Accumulation) {
+        for j in 0..other.height {
+            for i in 0..other.width {
+                let from = (j * other.width + i) as usize;
+                let to = ((other.y - self.y + j) * self.width + other.x - self.x + i) as usize;
+                let (color, weight) = other.pixels[from];
+                self.pixels[to].0 += color;
+                self.pixels[to].1 += weight;
+                self.weight_magnitudes[to] += other.weight_magnitudes[from];
+                self.samples[to] += other.samples[from];
+                self.cost[to] += other.cost[from];
+
+                for ((_, layer), (_, other_layer)) in self.aovs.iter_mut().zip(&other.aovs) {
+                    match (layer, other_layer) {
+                        (AovLayer::Color(layer), AovLayer::Color(other_layer)) => {
+                            layer[to] += other_layer[from]
+                        }
+                        (AovLayer::Id(layer), AovLayer::Id(other_layer)) => {
+                            if other_layer[from].1 > layer[to].1 {
+                                layer[to] = other_layer[from];
+                            }
+                        }
+                        _ => panic!("accumulations with different AOVs"),
+                    }
+                }
+            }
+        }
+        self.stats.add(&other.stats);
+    }
+
+    /// Pixels divided by their weights
+    pub fn output(&self) -> RenderOutput {
+        // Samples cancelling each other out leave a weight close to zero, dividing by it would
+        // blow the pixel up, so it is kept away from zero at the cost of darkening the pixel
+        let weights: Vec<f32> = self
+            .pixels
+            .iter()
+            .zip(&self.weight_magnitudes)
+            .map(|((_, weight), magnitude)| weight.max(MIN_WEIGHT_FRACTION * magnitude))
+            .collect();
+        let normalize = |value: Vec3, weight: f32| {
+            if weight > 0. {
+                value / weight
+            } else {
+                Vec3::ZERO
             }
-            col /= num_samples as f32;
-            col = Vec3::new(col.x.sqrt(), col.y.sqrt(), col.z.sqrt());
+        };
+        let aovs = self
+            .aovs
+            .iter()
+            .map(|(aov, layer)| {
+                let buffer = match layer {
+                    AovLayer::Color(values) => AovBuffer::Color(
+                        values
+                            .iter()
+                            .zip(&weights)
+                            .map(|(value, weight)| normalize(*value, *weight))
+                            .collect(),
+                    ),
+                    AovLayer::Id(ids) => AovBuffer::Id(ids.iter().map(|(id, _)| *id).collect()),
+                };
+                (*aov, buffer)
+            })
+            .collect();
 
-            let vrgb = 255.99 * col;
+        RenderOutput {
+            width: self.width,
+            height: self.height,
+            beauty: self
+                .pixels
+                .iter()
+                .zip(&weights)
+                .map(|((color, _), weight)| normalize(*color, *weight))
+                .collect(),
+            aovs,
+        }
+    }
+}
 
-            vec![vrgb.x as u8, vrgb.y as u8, vrgb.z as u8, 0xff]
+/// Value of an AOV for a single sample
+#[derive(Clone, Copy)]
+enum AovSample {
+    Color(Vec3),
+    Id(u32),
+}
+
+impl AovSample {
+    fn new(aov: Aov, sample: &PathSample, cam: &Camera) -> Self {
+        let surface = match &sample.surface {
+            Some(surface) => surface,
+            None if aov.is_id() => return AovSample::Id(0),
+            None => return AovSample::Color(Vec3::ZERO),
+        };
+        let color = match aov {
+            Aov::Albedo => surface.albedo,
+            Aov::Normal => surface.normal,
+            Aov::CameraNormal => Vec3::new(
+                surface.normal.dot(cam.u),
+                surface.normal.dot(cam.v),
+                surface.normal.dot(cam.w),
+            ),
+            Aov::Depth => Vec3::splat(surface.distance),
+            Aov::Position => surface.position,
+            Aov::MaterialId => return AovSample::Id(surface.material_id),
+            Aov::ObjectId => return AovSample::Id(surface.object_id),
+            Aov::Emission => sample.emission,
+            Aov::Direct => sample.direct,
+            Aov::Indirect => sample.indirect,
+        };
+        AovSample::Color(color.map(de_nan))
+    }
+}
+
+/// Seed of the samplers
+///
+/// Renders used to seed their random numbers from entropy, so no two were alike. The seed is
+/// now fixed, the same scene and settings always give the same image, and changing it gives
+/// another noise pattern.
+pub const SEED: u64 = 0;
+
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    /// Samples per pixel
+    pub num_samples: u32,
+    pub max_depth: i32,
+    pub filter: Filter,
+    pub sampler: SamplerType,
+    pub aovs: Vec<Aov>,
+    /// Samples per pixel the sampler spreads its points over when it is not `num_samples`, to
+    /// add samples to an image made with fewer
+    pub strata: Option<u32>,
+}
+
+pub fn render(
+    cam: Camera,
+    world: &Hittables,
+    num_samples: i32,
+    max_depth: i32,
+    filter: Filter,
+    sampler: SamplerType,
+) -> Vec<u8> {
+    render_aovs(cam, world, num_samples, max_depth, filter, sampler, &[]).to_rgba8()
+}
+
+/// Renders the linear image along with the passes in `aovs`
+pub fn render_aovs(
+    cam: Camera,
+    world: &Hittables,
+    num_samples: i32,
+    max_depth: i32,
+    filter: Filter,
+    sampler: SamplerType,
+    aovs: &[Aov],
+) -> RenderOutput {
+    let settings = RenderSettings {
+        num_samples: num_samples.max(1) as u32,
+        max_depth,
+        filter,
+        sampler,
+        aovs: aovs.to_vec(),
+        strata: None,
+    };
+    render_samples(&cam, world, &settings, 0..settings.num_samples).output()
+}
+
+/// Rectangle of pixels of the image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    /// This region grown by the pixels its samples reach through `filter`, inside a `width` by
+    /// `height` image, which is the region of the accumulation rendered for it
+    pub fn with_margin(self, filter: &Filter, width: u32, height: u32) -> Region {
+        let margin = filter_margin(filter);
+        let x0 = self.x.min(width).saturating_sub(margin);
+        let y0 = self.y.min(height).saturating_sub(margin);
+        let x1 = self.x.saturating_add(self.width).min(width);
+        let y1 = self.y.saturating_add(self.height).min(height);
+        Region {
+            x: x0,
+            y: y0,
+            width: x1.saturating_add(margin).min(width) - x0,
+            height: y1.saturating_add(margin).min(height) - y0,
+        }
+    }
+}
+
+/// Pixels outside of a region that its samples can reach
+fn filter_margin(filter: &Filter) -> u32 {
+    (filter.radius() - 0.5).ceil().max(0.) as u32
+}
+
+/// Accumulates the samples in `samples` of every pixel, out of the `settings.num_samples` the
+/// sampler spreads its points over unless `settings.strata` is set, so an image can be rendered
+/// a few samples at a time
+pub fn render_samples(
+    cam: &Camera,
+    world: &Hittables,
+    settings: &RenderSettings,
+    samples: Range<u32>,
+) -> Accumulation {
+    let image = Region {
+        x: 0,
+        y: 0,
+        width: cam.width,
+        height: cam.height,
+    };
+    render_region(cam, world, settings, image, samples)
+}
+
+/// Like `render_samples` for the pixels of `region` only, the accumulation also covers the
+/// pixels around it that its samples reach
+pub fn render_region(
+    cam: &Camera,
+    world: &Hittables,
+    settings: &RenderSettings,
+    region: Region,
+    samples: Range<u32>,
+) -> Accumulation {
+    let (width, height) = (cam.width, cam.height);
+    let region_x1 = (region.x + region.width).min(width);
+    let region_y1 = (region.y + region.height).min(height);
+    let filter = &settings.filter;
+    let aovs = &settings.aovs;
+    let margin = filter_margin(filter);
+
+    let tiles: Vec<(u32, u32)> = (region.y..region_y1)
+        .step_by(TILE_SIZE as usize)
+        .flat_map(|y| {
+            (region.x..region_x1)
+                .step_by(TILE_SIZE as usize)
+                .map(move |x| (x, y))
         })
-        .flatten()
-        .collect()
+        .collect();
+
+    let tiles: Vec<Accumulation> = tiles
+        .into_par_iter()
+        .map_init(
+            || {
+                let strata = settings.strata.unwrap_or(settings.num_samples);
+                settings.sampler.sampler(strata, SEED)
+            },
+            |sampler, (tile_x, tile_y)| {
+                let start = Instant::now();
+                let counters = stats::thread_counters();
+                let x0 = tile_x.saturating_sub(margin);
+                let y0 = tile_y.saturating_sub(margin);
+                let x1 = ((tile_x + TILE_SIZE).min(region_x1) + margin).min(width);
+                let y1 = ((tile_y + TILE_SIZE).min(region_y1) + margin).min(height);
+                let mut tile = Accumulation::new(x0, y0, x1 - x0, y1 - y0, aovs);
+                let mut aov_samples = Vec::with_capacity(aovs.len());
+
+                for j in tile_y..(tile_y + TILE_SIZE).min(region_y1) {
+                    for i in tile_x..(tile_x + TILE_SIZE).min(region_x1) {
+                        let pixel_start = Instant::now();
+                        for s in samples.clone() {
+                            sampler.start_pixel_sample(i, j, s);
+                            // Position of the sample in the image, y goes down
+                            let offset = sampler.get_2d();
+                            let (x, y) = (i as f32 + offset.x, j as f32 + offset.y);
+                            let ray =
+                                cam.get_ray(x / width as f32, 1. - y / height as f32, sampler);
+                            let sample = trace(ray, world, settings.max_depth, sampler);
+
+                            aov_samples.clear();
+                            aov_samples
+                                .extend(aovs.iter().map(|aov| AovSample::new(*aov, &sample, cam)));
+                            splat(
+                                &mut tile,
+                                filter,
+                                x,
+                                y,
+                                sample.color().map(de_nan),
+                                &aov_samples,
+                            );
+                        }
+                        let index = ((j - y0) * tile.width + i - x0) as usize;
+                        tile.samples[index] += samples.len() as u32;
+                        tile.cost[index] += pixel_start.elapsed().as_secs_f32();
+                    }
+                }
+
+                tile.stats = RenderStats::new(
+                    rayon::current_thread_index().unwrap_or(0),
+                    stats::thread_counters().since(&counters),
+                    start.elapsed(),
+                );
+                tile
+            },
+        )
+        .collect();
+
+    let padded = region.with_margin(filter, width, height);
+    let mut image = Accumulation::new(padded.x, padded.y, padded.width, padded.height, aovs);
+    for tile in &tiles {
+        image.add(tile);
+    }
+    image
+}
+
+/// Adds a sample at `(x, y)` in the image to the pixels of `tile` in reach of the filter
+fn splat(
+    tile: &mut Accumulation,
+    filter: &Filter,
+    x: f32,
+    y: f32,
+    color: Vec3,
+    aovs: &[AovSample],
+) {
+    let radius = filter.radius();
+    let first = |p: f32, start: u32| (p - 0.5 - radius).ceil().max(start as f32) as u32;
+    let last = |p: f32, end: u32| ((p - 0.5 + radius).floor() as i64).min(end as i64 - 1);
+
+    for j in first(y, tile.y) as i64..=last(y, tile.y + tile.height) {
+        for i in first(x, tile.x) as i64..=last(x, tile.x + tile.width) {
+            let weight = filter.evaluate(x - (i as f32 + 0.5), y - (j as f32 + 0.5));
+            if weight != 0. {
+                let index = ((j as u32 - tile.y) * tile.width + (i as u32 - tile.x)) as usize;
+                tile.pixels[index].0 += weight * color;
+                tile.pixels[index].1 += weight;
+                tile.weight_magnitudes[index] += weight.abs();
+
+                for ((_, layer), sample) in tile.aovs.iter_mut().zip(aovs) {
+                    match (layer, sample) {
+                        (AovLayer::Color(layer), AovSample::Color(value)) => {
+                            layer[index] += weight * *value
+                        }
+                        (AovLayer::Id(layer), AovSample::Id(id)) => {
+                            if weight > layer[index].1 {
+                                layer[index] = (*id, weight);
+                            }
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splatted_constant_is_normalised_back() {
+        let color = Vec3::new(0.2, 0.4, 0.8);
+        for name in ["box", "tent", "gaussian", "mitchell", "lanczos"].iter() {
+            let filter: Filter = name.parse().unwrap();
+            let size = 12;
+            let mut tile = Accumulation::new(0, 0, size, size, &[]);
+            // Eight by eight samples in each pixel
+            for j in 0..size * 8 {
+                for i in 0..size * 8 {
+                    let (x, y) = ((i as f32 + 0.5) / 8., (j as f32 + 0.5) / 8.);
+                    splat(&mut tile, &filter, x, y, color, &[]);
+                }
+            }
+
+            // Pixels far enough from the border for every sample in reach to be there
+            let margin = filter.radius().ceil() as u32;
+            for j in margin..size - margin {
+                for i in margin..size - margin {
+                    let index = (j * size + i) as usize;
+                    let (sum, weight) = tile.pixels[index];
+                    assert!(weight >= MIN_WEIGHT_FRACTION * tile.weight_magnitudes[index]);
+                    assert!((sum / weight - color).length() < 1e-4, "{}", name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn accumulations_add_up() {
+        let filter: Filter = "gaussian".parse().unwrap();
+        let aovs = [Aov::Depth, Aov::ObjectId];
+        let samples = [
+            (1.2, 3.7, 0.2, 3),
+            (4.5, 4.5, 0.9, 1),
+            (6.1, 0.3, 0.4, 2),
+            (2.8, 2.2, 0.6, 5),
+        ];
+        let splat_all = |tile: &mut Accumulation, samples: &[(f32, f32, f32, u32)]| {
+            for (x, y, value, id) in samples.iter() {
+                let aov_samples = [AovSample::Color(Vec3::splat(*value)), AovSample::Id(*id)];
+                splat(tile, &filter, *x, *y, Vec3::splat(*value), &aov_samples);
+            }
+        };
+
+        let mut whole = Accumulation::new(0, 0, 8, 6, &aovs);
+        splat_all(&mut whole, &samples);
+        let mut split = Accumulation::new(0, 0, 8, 6, &aovs);
+        for part in samples.chunks(2) {
+            let mut tile = Accumulation::new(0, 0, 8, 6, &aovs);
+            splat_all(&mut tile, part);
+            split.add(&tile);
+        }
+        let (whole, split) = (whole.output(), split.output());
+        for (a, b) in whole.beauty.iter().zip(&split.beauty) {
+            assert!((*a - *b).length() < 1e-5);
+        }
+        match (whole.aov(Aov::ObjectId), split.aov(Aov::ObjectId)) {
+            (Some(AovBuffer::Id(a)), Some(AovBuffer::Id(b))) => assert_eq!(a, b),
+            _ => panic!("missing object IDs"),
+        }
+
+        // Tiles land at their place in the image
+        let mut image = Accumulation::new(0, 0, 8, 6, &[]);
+        let mut tile = Accumulation::new(2, 1, 4, 3, &[]);
+        tile.pixels[5] = (Vec3::ONE, 1.);
+        image.add(&tile);
+        assert_eq!(image.pixels[2 * 8 + 3], (Vec3::ONE, 1.));
+        assert_eq!(image.pixels.iter().filter(|(_, w)| *w != 0.).count(), 1);
+    }
 }
@@ -0,0 +1,161 @@
+use super::{
+    hash,
+    sobol::{owen_sobol_1d, owen_sobol_2d},
+    Sampler,
+};
+use glam::Vec2;
+use lazy_static::lazy_static;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+/// Side of the tiled blue noise mask
+const SIZE: usize = 64;
+
+/// Standard deviation in pixels of the energy used to find clusters and voids
+const SIGMA: f32 = 1.5;
+
+lazy_static! {
+    /// Blue noise thresholds in `[0, 1)`, generated on first use
+    static ref BLUE_NOISE: Vec<f32> = void_and_cluster();
+}
+
+/// Scrambled Sobol points shared by every pixel and shifted by a blue noise mask, so the error
+/// left at low sample counts looks like fine grain instead of blotches
+///
+/// Each dimension reads the mask at its own offset to keep the dimensions apart.
+pub struct BlueNoiseSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> Self {
+        BlueNoiseSampler {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Value of the mask under the pixel for the next dimension
+    fn shift(&mut self) -> f32 {
+        let (x, y) = self.pixel;
+        let offset = hash(&[self.dimension], self.seed);
+        self.dimension += 1;
+        blue_noise(
+            x.wrapping_add(offset as u32),
+            y.wrapping_add((offset >> 32) as u32),
+        )
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let point = owen_sobol_1d(self.index, hash(&[self.dimension], !self.seed));
+        (point + self.shift()).fract()
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        let point = owen_sobol_2d(self.index, hash(&[self.dimension], !self.seed));
+        let shift = Vec2::new(self.shift(), self.shift());
+        let point = point + shift;
+        Vec2::new(point.x.fract(), point.y.fract())
+    }
+}
+
+/// Threshold of the blue noise mask at `(x, y)`, the mask tiles the plane
+pub fn blue_noise(x: u32, y: u32) -> f32 {
+    BLUE_NOISE[(y as usize % SIZE) * SIZE + x as usize % SIZE]
+}
+
+/// Blue noise mask from Ulichney's void and cluster method, on a torus so it tiles
+fn void_and_cluster() -> Vec<f32> {
+    let n = SIZE * SIZE;
+    // Energy added by a point at every offset around it
+    let kernel: Vec<f32> = (0..n)
+        .map(|i| {
+            let (dx, dy) = (i % SIZE, i / SIZE);
+            let dx = dx.min(SIZE - dx) as f32;
+            let dy = dy.min(SIZE - dy) as f32;
+            (-(dx * dx + dy * dy) / (2. * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+    let toggle = |energy: &mut [f32], point: usize, sign: f32| {
+        let (px, py) = (point % SIZE, point / SIZE);
+        for (i, e) in energy.iter_mut().enumerate() {
+            let dx = (i % SIZE + SIZE - px) % SIZE;
+            let dy = (i / SIZE + SIZE - py) % SIZE;
+            *e += sign * kernel[dy * SIZE + dx];
+        }
+    };
+    let tightest_cluster = |points: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|i| points[*i])
+            .max_by(|a, b| energy[*a].partial_cmp(&energy[*b]).unwrap())
+            .unwrap()
+    };
+    let largest_void = |points: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|i| !points[*i])
+            .min_by(|a, b| energy[*a].partial_cmp(&energy[*b]).unwrap())
+            .unwrap()
+    };
+
+    // Random initial points, moved from the tightest cluster to the largest void until stable
+    let rng = &mut SmallRng::seed_from_u64(0);
+    let initial = n / 10;
+    let mut points = vec![false; n];
+    let mut energy = vec![0.; n];
+    let mut count = 0;
+    while count < initial {
+        let point = rng.gen_range(0..n);
+        if !points[point] {
+            points[point] = true;
+            toggle(&mut energy, point, 1.);
+            count += 1;
+        }
+    }
+    for _ in 0..n {
+        let cluster = tightest_cluster(&points, &energy);
+        points[cluster] = false;
+        toggle(&mut energy, cluster, -1.);
+        let void = largest_void(&points, &energy);
+        points[void] = true;
+        toggle(&mut energy, void, 1.);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; n];
+    // The initial points are ranked by removing the tightest clusters first
+    {
+        let mut points = points.clone();
+        let mut energy = energy.clone();
+        for r in (0..initial).rev() {
+            let cluster = tightest_cluster(&points, &energy);
+            points[cluster] = false;
+            toggle(&mut energy, cluster, -1.);
+            rank[cluster] = r;
+        }
+    }
+    // The others by filling the largest voids
+    for r in initial..n {
+        let void = largest_void(&points, &energy);
+        points[void] = true;
+        toggle(&mut energy, void, 1.);
+        rank[void] = r;
+    }
+
+    rank.into_iter()
+        .map(|r| (r as f32 + 0.5) / n as f32)
+        .collect()
+}
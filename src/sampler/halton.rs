@@ -0,0 +1,66 @@
+use super::{hash, to_unit, Sampler};
+use glam::Vec2;
+
+/// Bases of the dimensions of the Halton sequence, the dimensions after them are random
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Halton sequence with each pixel and dimension shifted by a random offset so neighbouring
+/// pixels do not share their patterns
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        HaltonSampler {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (x, y) = self.pixel;
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        let shift = to_unit(hash(&[x, y, dimension], self.seed) as u32);
+        match PRIMES.get(dimension as usize) {
+            Some(base) => (radical_inverse(*base, self.index) + shift).fract(),
+            None => to_unit(hash(&[x, y, dimension, self.index], self.seed) as u32),
+        }
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        Vec2::new(self.get_1d(), self.get_1d())
+    }
+}
+
+/// Digits of `index` in `base` mirrored around the decimal point
+fn radical_inverse(base: u32, mut index: u32) -> f32 {
+    let base = base as u64;
+    let mut reversed = 0u64;
+    let mut scale = 1f64;
+    while index > 0 {
+        let next = index as u64 / base;
+        reversed = reversed * base + (index as u64 - next * base);
+        scale /= base as f64;
+        index = next as u32;
+    }
+    ((reversed as f64 * scale) as f32).min(1. - f32::EPSILON)
+}
@@ -0,0 +1,325 @@
+//! Sources of the numbers in `[0, 1)` used by the camera, the materials and the integrator
+
+use enum_dispatch::enum_dispatch;
+use glam::Vec2;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use std::str::FromStr;
+
+use crate::sampler::{blue_noise::BlueNoiseSampler, halton::HaltonSampler, sobol::SobolSampler};
+
+pub mod blue_noise;
+pub mod halton;
+pub mod sobol;
+
+/// Numbers for one sample of a pixel, each call moves to the next dimension so every decision
+/// along a path gets its own dimensions
+///
+/// The first 2D sample is the position in the pixel, the camera then takes its lens and time
+/// samples and each bounce takes what its material needs.
+#[enum_dispatch]
+pub trait Sampler {
+    /// Starts sample `index` of the pixel `(x, y)` from the first dimension
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32);
+
+    fn get_1d(&mut self) -> f32;
+
+    fn get_2d(&mut self) -> Vec2;
+}
+
+#[enum_dispatch(Sampler)]
+pub enum Samplers {
+    IndependentSampler,
+    StratifiedSampler,
+    HaltonSampler,
+    SobolSampler,
+    BlueNoiseSampler,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SamplerType {
+    Independent,
+    #[default]
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+impl SamplerType {
+    /// `samples_per_pixel` sets the strata of the stratified sampler, `seed` changes the noise
+    pub fn sampler(self, samples_per_pixel: u32, seed: u64) -> Samplers {
+        match self {
+            SamplerType::Independent => Samplers::from(IndependentSampler::new(seed)),
+            SamplerType::Stratified => {
+                Samplers::from(StratifiedSampler::new(samples_per_pixel, seed))
+            }
+            SamplerType::Halton => Samplers::from(HaltonSampler::new(seed)),
+            SamplerType::Sobol => Samplers::from(SobolSampler::new(seed)),
+            SamplerType::BlueNoise => Samplers::from(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+impl FromStr for SamplerType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(match name.to_lowercase().as_str() {
+            "independent" | "random" => SamplerType::Independent,
+            "stratified" => SamplerType::Stratified,
+            "halton" => SamplerType::Halton,
+            "sobol" => SamplerType::Sobol,
+            "blue_noise" | "bluenoise" => SamplerType::BlueNoise,
+            _ => {
+                return Err(format!(
+                    "unknown sampler {}, expected independent, stratified, halton, sobol or \
+                     blue_noise",
+                    name
+                ))
+            }
+        })
+    }
+}
+
+/// Uniform random numbers, seeded again for every pixel sample so that the image does not
+/// depend on which thread rendered which pixel
+pub struct IndependentSampler {
+    seed: u64,
+    rng: SmallRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        IndependentSampler {
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.rng = SmallRng::seed_from_u64(hash(&[x, y, index], self.seed));
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.rng.gen_range(0.0..1.0)
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        Vec2::new(self.rng.gen_range(0.0..1.0), self.rng.gen_range(0.0..1.0))
+    }
+}
+
+/// Jittered samples, one per stratum of each dimension, 2D samples use a grid as square as the
+/// number of samples allows. The strata are shuffled for each pixel and dimension
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    columns: u32,
+    rows: u32,
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+    rng: SmallRng,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let (columns, rows) = strata(samples_per_pixel);
+        StratifiedSampler {
+            samples_per_pixel,
+            columns,
+            rows,
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Stratum of the current sample in the next dimension
+    fn stratum(&mut self) -> u32 {
+        let (x, y) = self.pixel;
+        let permutation = hash(&[x, y, self.dimension], self.seed) as u32;
+        self.dimension += 1;
+        permutation_element(
+            self.index % self.samples_per_pixel,
+            self.samples_per_pixel,
+            permutation,
+        )
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = SmallRng::seed_from_u64(hash(&[x, y, index], self.seed));
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let stratum = self.stratum();
+        (stratum as f32 + self.rng.gen_range(0.0..1.0)) / self.samples_per_pixel as f32
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        let stratum = self.stratum();
+        self.dimension += 1;
+        Vec2::new(
+            ((stratum % self.columns) as f32 + self.rng.gen_range(0.0..1.0)) / self.columns as f32,
+            ((stratum / self.columns) as f32 + self.rng.gen_range(0.0..1.0)) / self.rows as f32,
+        )
+    }
+}
+
+/// Columns and rows of the grid used to stratify `samples` 2D samples, as close to a square as
+/// the number allows
+fn strata(samples: u32) -> (u32, u32) {
+    let rows = (1..=(samples as f32).sqrt() as u32)
+        .rev()
+        .find(|rows| samples.is_multiple_of(*rows))
+        .unwrap_or(1);
+    (samples / rows, rows)
+}
+
+/// Finalizer of MurmurHash3, spreads the bits of `v` over the whole word
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+pub(crate) fn hash(values: &[u32], seed: u64) -> u64 {
+    values.iter().fold(mix_bits(seed), |h, v| {
+        mix_bits(h ^ (*v as u64 + 0x9e37_79b9))
+    })
+}
+
+/// Float in `[0, 1)` from the high bits of `bits`
+pub(crate) fn to_unit(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Element `index` of the random permutation of `0..length` picked by `seed`, from Kensler's
+/// "Correlated Multi-Jittered Sampling"
+fn permutation_element(index: u32, length: u32, seed: u32) -> u32 {
+    let mut w = length - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    let p = seed;
+    let mut i = index;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_stay_in_the_unit_square() {
+        let types = [
+            SamplerType::Independent,
+            SamplerType::Stratified,
+            SamplerType::Halton,
+            SamplerType::Sobol,
+            SamplerType::BlueNoise,
+        ];
+        for &sampler_type in types.iter() {
+            let mut sampler = sampler_type.sampler(16, 7);
+            for (x, y) in [(0, 0), (3, 9), (1000, 517)].iter() {
+                for index in 0..64 {
+                    sampler.start_pixel_sample(*x, *y, index);
+                    for _ in 0..40 {
+                        let u = sampler.get_1d();
+                        let p = sampler.get_2d();
+                        for &v in [u, p.x, p.y].iter() {
+                            assert!((0. ..1.).contains(&v), "{:?} gave {}", sampler_type, v);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pixel_samples_are_repeatable() {
+        let mut sampler = SamplerType::Independent.sampler(1, 3);
+        sampler.start_pixel_sample(4, 5, 6);
+        let first = (sampler.get_1d(), sampler.get_2d());
+        sampler.start_pixel_sample(0, 0, 0);
+        sampler.get_1d();
+        sampler.start_pixel_sample(4, 5, 6);
+        assert_eq!(first, (sampler.get_1d(), sampler.get_2d()));
+    }
+
+    #[test]
+    fn strata_are_as_square_as_possible() {
+        assert_eq!(strata(1), (1, 1));
+        assert_eq!(strata(7), (7, 1));
+        assert_eq!(strata(8), (4, 2));
+        assert_eq!(strata(16), (4, 4));
+        assert_eq!(strata(24), (6, 4));
+    }
+
+    #[test]
+    fn permutation_element_is_a_permutation() {
+        for &length in [1, 2, 5, 16, 100].iter() {
+            for seed in 0..8u32 {
+                let mut elements: Vec<u32> = (0..length)
+                    .map(|i| permutation_element(i, length, seed.wrapping_mul(0x9e37_79b9)))
+                    .collect();
+                elements.sort_unstable();
+                assert_eq!(elements, (0..length).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_samples_fill_every_stratum() {
+        let mut sampler = StratifiedSampler::new(8, 11);
+        let mut strata_1d = vec![0; 8];
+        let mut strata_2d = vec![0; 8];
+        for index in 0..8 {
+            sampler.start_pixel_sample(2, 3, index);
+            strata_1d[(sampler.get_1d() * 8.) as usize] += 1;
+            let p = sampler.get_2d();
+            strata_2d[(p.x * 4.) as usize + 4 * (p.y * 2.) as usize] += 1;
+        }
+        assert_eq!(strata_1d, vec![1; 8]);
+        assert_eq!(strata_2d, vec![1; 8]);
+    }
+}
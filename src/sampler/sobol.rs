@@ -0,0 +1,120 @@
+use super::{hash, to_unit, Sampler};
+use glam::Vec2;
+
+/// Sobol sequence with Owen scrambling, from Burley's "Practical Hash-based Owen Scrambling"
+///
+/// Only the first two dimensions of the sequence are used, every 1D or 2D sample shuffles the
+/// order of the points with its own seed which keeps the dimensions independent.
+pub struct SobolSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        SobolSampler {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next_seed(&mut self) -> u64 {
+        let (x, y) = self.pixel;
+        let seed = hash(&[x, y, self.dimension], self.seed);
+        self.dimension += 1;
+        seed
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let seed = self.next_seed();
+        owen_sobol_1d(self.index, seed)
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        let seed = self.next_seed();
+        self.dimension += 1;
+        owen_sobol_2d(self.index, seed)
+    }
+}
+
+pub(crate) fn owen_sobol_1d(index: u32, seed: u64) -> f32 {
+    let index = nested_uniform_scramble(index, seed as u32);
+    to_unit(nested_uniform_scramble(
+        index.reverse_bits(),
+        (seed >> 32) as u32,
+    ))
+}
+
+pub(crate) fn owen_sobol_2d(index: u32, seed: u64) -> Vec2 {
+    let index = nested_uniform_scramble(index, seed as u32);
+    let second = hash(&[1], seed);
+    Vec2::new(
+        to_unit(nested_uniform_scramble(
+            index.reverse_bits(),
+            (seed >> 32) as u32,
+        )),
+        to_unit(nested_uniform_scramble(sobol_second(index), second as u32)),
+    )
+}
+
+/// Second dimension of the Sobol sequence, the first one is the bit reversed index
+fn sobol_second(index: u32) -> u32 {
+    let mut direction = 1u32 << 31;
+    let mut result = 0;
+    let mut index = index;
+    while index > 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        direction ^= direction >> 1;
+        index >>= 1;
+    }
+    result
+}
+
+/// Owen scrambling of the bits of `x`, each bit is flipped depending on the ones above it
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_are_stratified() {
+        for seed in 0..4 {
+            let mut cells = [0; 16];
+            let mut rows = [0; 16];
+            for index in 0..16 {
+                let p = owen_sobol_2d(index, hash(&[seed], 5));
+                assert!((0. ..1.).contains(&p.x) && (0. ..1.).contains(&p.y));
+                cells[(p.x * 4.) as usize + 4 * (p.y * 4.) as usize] += 1;
+                rows[(owen_sobol_1d(index, hash(&[seed], 9)) * 16.) as usize] += 1;
+            }
+            assert_eq!(cells, [1; 16]);
+            assert_eq!(rows, [1; 16]);
+        }
+    }
+}
@@ -1,27 +1,56 @@
-use crate::{
-    camera::{Camera, CameraConfig, CameraConfigBuilder},
+use glam::Vec2;
+use rand::Rng;
+use raytracing_weekend_rs::{
+    camera::{
+        Aperture, Camera, CameraConfig, CameraConfigBuilder, CameraType, PhysicalLens, Stereo,
+        StereoLayout,
+    },
     hittable::{
+        aabb::AABB,
         box_rect::BoxRect,
         bvh_node::BvhNode,
+        cone::Cone,
         constant_medium::ConstantMedium,
-        flip_normals::FlipNormals,
+        csg::Csg,
+        curve::Curve,
+        cylinder::Cylinder,
+        disk::Disk,
+        heightfield::Heightfield,
         hittable_list::HittableList,
+        mesh::Mesh,
         moving_sphere::MovingSphere,
-        rect::{Rect, StaticAxis},
+        object::number_objects,
+        particles::{Particle, Particles},
+        quad::Quad,
+        rect::{Facing, Rect, StaticAxis},
         rotate::RotateY,
+        sdf::{Sdf, SdfShape},
         sphere::Sphere,
+        torus::Torus,
         translate::Translate,
-        Hittables,
+        Hittable, Hittables,
     },
-    material::{Dielectric, DiffuseLight, Lambertian, MaterialType, Metal},
+    import::{load_gltf_cached, ImportCache},
+    material::{Dielectric, DiffuseLight, Hair, Lambertian, MaterialType, Metal, NormalMapped},
     random::random_double,
     texture::{
-        checker_texture::CheckerTexture, constant_texture::ConstantTexture,
-        image_texture::ImageTexture, noise_texture::NoiseTexture, perlin::Perlin, TextureType,
+        checker_texture::CheckerTexture,
+        color_ramp::ColorRamp,
+        constant_texture::ConstantTexture,
+        image_texture::{ColorSpace, ImageTexture},
+        node_texture::{
+            Channel, InvertTexture, MixTexture, RampTexture, RemapTexture, TriplanarTexture,
+            UvTransformTexture,
+        },
+        noise_texture::NoiseTexture,
+        pattern_texture::{Pattern, PatternSpace, PatternTexture},
+        vertex_color_texture::VertexColorTexture,
+        TextureType,
     },
     vec3::{Vec3, Vec3Wrapper},
 };
-use rand::Rng;
+
+use std::path::{Path, PathBuf};
 
 use crate::{HEIGHT, WIDTH};
 
@@ -34,16 +63,125 @@ pub fn get_scene_from_name(name: &str, rng: &mut impl Rng) -> Scene {
     let scene = match name {
         "two_spheres" => two_spheres(),
         "two_perlin_spheres" => two_perlin_spheres(),
+        "bumpy_spheres" => bumpy_spheres(),
+        "noise" => noise_spheres(),
         "random" => random_scene(rng),
+        "depth_of_field" => depth_of_field(rng),
+        "bokeh" => bokeh(rng),
+        "tilt_shift" => tilt_shift(rng),
         "earth" => earth(),
         "simple_light" => simple_light(),
+        "patterns" => patterns(),
+        "texture_nodes" => texture_nodes(),
+        "primitives" => primitives(),
+        "csg" => csg(),
+        "sdf" => sdf(),
+        "terrain" => terrain(),
+        "hair" => hair(rng),
+        "particles" => particles(rng),
+        path if is_scene_file(path) => {
+            scene_from_file(path, &mut ImportCache::default())
+                .unwrap_or_else(|e| panic!("{}", e))
+                .0
+        }
+        "blueprint" => blueprint(),
+        "panorama" => cornell_box_inside(CameraType::Equirectangular, None),
+        "fisheye" => cornell_box_inside(CameraType::Fisheye { fov: 180. }, None),
+        "vr" => cornell_box_inside(
+            CameraType::Equirectangular,
+            Some(Stereo {
+                layout: StereoLayout::OverUnder,
+                eye_separation: 6.4,
+            }),
+        ),
         "cornell_box" => cornell_box_scene(),
         _ => cornell_box_scene(),
     };
 
     println!("{} scene generated", name);
 
-    scene
+    Scene {
+        hittables: number_objects(scene.hittables),
+        ..scene
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SceneFile {
+    Gltf,
+    Ply,
+}
+
+/// Whether `name` is the path of a glTF or PLY file rather than the name of a scene
+pub fn is_scene_file(name: &str) -> bool {
+    scene_file_kind(name).is_some()
+}
+
+fn scene_file_kind(path: &str) -> Option<SceneFile> {
+    let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "gltf" | "glb" => Some(SceneFile::Gltf),
+        "ply" => Some(SceneFile::Ply),
+        _ => None,
+    }
+}
+
+/// Scene loaded from a glTF file or a single PLY mesh with the files it was read from, scenes
+/// without a camera get one framing everything
+///
+/// The textures and meshes of `cache` are reused, see `ImportCache`.
+pub fn scene_from_file(
+    path: &str,
+    cache: &mut ImportCache,
+) -> Result<(Scene, Vec<PathBuf>), String> {
+    let (hittables, cameras, files) = match scene_file_kind(path) {
+        Some(SceneFile::Gltf) => {
+            let scene = load_gltf_cached(path, cache).map_err(|e| format!("{}: {}", path, e))?;
+            (scene.hittables, scene.cameras, scene.files)
+        }
+        _ => {
+            let material =
+                Lambertian::new(VertexColorTexture::new(ConstantTexture::new(0.7, 0.7, 0.7)));
+            let mesh = Mesh::from_ply(path, material).map_err(|e| format!("{}: {}", path, e))?;
+            (vec![mesh], Vec::new(), vec![PathBuf::from(path)])
+        }
+    };
+    if hittables.is_empty() {
+        return Err(format!("{}: nothing to render", path));
+    }
+
+    let world = BvhNode::new(hittables, 0., 1., 0);
+    let camera = match cameras.first() {
+        Some(camera) => camera.config(WIDTH, HEIGHT),
+        None => {
+            let bbox = world.bounding_box(0., 1.).unwrap();
+            let center = 0.5 * (bbox.min + bbox.max);
+            let size = (bbox.max - bbox.min).length();
+
+            let mut config = default_config();
+            config.lookat = center;
+            config.lookfrom = center + Vec3::new(0.3, 0.4, 1.).normalize() * size * 1.5;
+            config.vfov = 40.;
+            config.focus_dist = size * 1.5;
+            config
+        }
+    };
+
+    let mut hittables = vec![world];
+    // A lone PLY mesh has no light of its own
+    if scene_file_kind(path) == Some(SceneFile::Ply) {
+        hittables.push(Hittables::from(Sphere {
+            center: Vec3::ZERO,
+            radius: 10_000.,
+            mat: DiffuseLight::new(ConstantTexture::new(1., 1., 1.)),
+        }));
+    }
+
+    let scene = Scene {
+        camera: Camera::new(camera),
+        hittables: number_objects(HittableList::new(hittables)),
+    };
+    Ok((scene, files))
 }
 
 fn default_config() -> CameraConfig {
@@ -90,7 +228,7 @@ pub fn random_scene(rng: &mut impl Rng) -> Scene {
             center: Vec3::new(4., 1., 0.),
             radius: 1.,
             mat: MaterialType::from(Metal {
-                albedo: Vec3::new(0.7, 0.6, 0.5),
+                albedo: ConstantTexture::new(0.7, 0.6, 0.5),
                 fuzz: 0.,
             }),
         }),
@@ -112,7 +250,7 @@ pub fn random_scene(rng: &mut impl Rng) -> Scene {
                         random_double(rng) * random_double(rng),
                     )),
                     x if (0.8..0.95).contains(&x) => MaterialType::from(Metal {
-                        albedo: Vec3::new(
+                        albedo: ConstantTexture::new(
                             0.5 * (1. + random_double(rng)),
                             0.5 * (1. + random_double(rng)),
                             0.5 * (1. + random_double(rng)),
@@ -147,6 +285,80 @@ pub fn random_scene(rng: &mut impl Rng) -> Scene {
     }
 }
 
+/// Random scene through a fast short tele lens focused on the glass sphere
+pub fn depth_of_field(rng: &mut impl Rng) -> Scene {
+    random_scene_under_sky(rng, glass_sphere_view().lens(portrait_lens()))
+}
+
+/// Depth of field with a seven blade aperture and cat's eye bokeh toward the corners
+pub fn bokeh(rng: &mut impl Rng) -> Scene {
+    random_scene_under_sky(
+        rng,
+        glass_sphere_view()
+            .lens(portrait_lens())
+            .aperture_shape(Aperture::Polygon {
+                blades: 7,
+                rotation: 90.,
+            })
+            .cat_eye(0.6),
+    )
+}
+
+/// Miniature look of a tilted focus plane seen from above, with the view shifted down to keep
+/// the camera level
+pub fn tilt_shift(rng: &mut impl Rng) -> Scene {
+    random_scene_under_sky(
+        rng,
+        CameraConfigBuilder::default()
+            .lookfrom(Vec3::new(13., 6., 3.))
+            .lookat(Vec3::new(0., 6., 0.))
+            .focus_on(Vec3::new(0., 1., 0.))
+            .lens(PhysicalLens {
+                focal_length: 24.,
+                f_stop: 1.4,
+                units_per_meter: 40.,
+                ..PhysicalLens::default()
+            })
+            .shift(Vec2::new(0., -0.4))
+            .tilt(Vec2::new(-30., 0.)),
+    )
+}
+
+fn glass_sphere_view() -> CameraConfigBuilder {
+    let mut builder = CameraConfigBuilder::default();
+    builder
+        .lookfrom(Vec3::new(13., 2., 3.))
+        .lookat(Vec3::new(0., 0., 0.))
+        .focus_on(Vec3::new(0., 1., 0.));
+    builder
+}
+
+fn portrait_lens() -> PhysicalLens {
+    PhysicalLens {
+        focal_length: 85.,
+        f_stop: 1.4,
+        shutter: 0.25,
+        // The big spheres are about 20cm wide
+        units_per_meter: 10.,
+        ..PhysicalLens::default()
+    }
+}
+
+/// Random scene under a sky, seen by `camera` at the size of the window
+fn random_scene_under_sky(rng: &mut impl Rng, camera: &mut CameraConfigBuilder) -> Scene {
+    let config = camera.width(WIDTH).height(HEIGHT).build().unwrap();
+
+    let sky = Hittables::from(Sphere {
+        center: Vec3::ZERO,
+        radius: 10_000.,
+        mat: DiffuseLight::new(ConstantTexture::new(0.9, 0.95, 1.)),
+    });
+    Scene {
+        camera: Camera::new(config),
+        hittables: HittableList::new(vec![random_scene(rng).hittables, sky]),
+    }
+}
+
 pub fn two_spheres() -> Scene {
     let hittables = HittableList::new(vec![
         Hittables::from(Sphere {
@@ -168,10 +380,7 @@ pub fn two_spheres() -> Scene {
 }
 
 pub fn two_perlin_spheres() -> Scene {
-    let noise_texture = TextureType::from(NoiseTexture {
-        perlin: Perlin,
-        scale: 7.,
-    });
+    let noise_texture = TextureType::from(NoiseTexture::marble(7., 0));
 
     let hittables = HittableList::new(vec![
         Hittables::from(Sphere {
@@ -192,23 +401,105 @@ pub fn two_perlin_spheres() -> Scene {
     }
 }
 
+pub fn bumpy_spheres() -> Scene {
+    let noise_texture = TextureType::from(NoiseTexture::marble(7., 0));
+
+    let light_mat = DiffuseLight::new(ConstantTexture::new(4.0, 4.0, 4.0));
+
+    let hittables = HittableList::new(vec![
+        Hittables::from(Sphere {
+            center: Vec3::newi(0, -1000, 0),
+            radius: 1000.,
+            mat: NormalMapped::bump(
+                Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+                noise_texture.clone(),
+                0.02,
+            ),
+        }),
+        Hittables::from(Sphere {
+            center: Vec3::newi(0, 2, 0),
+            radius: 2.,
+            mat: NormalMapped::bump(
+                MaterialType::from(Metal {
+                    albedo: ConstantTexture::new(0.7, 0.6, 0.5),
+                    fuzz: 0.1,
+                }),
+                noise_texture,
+                0.05,
+            ),
+        }),
+        Hittables::from(Sphere {
+            center: Vec3::newi(0, 7, 0),
+            radius: 2.,
+            mat: light_mat.clone(),
+        }),
+        Rect::new(3.0..5.0, 1.0..3.0, -2.0, StaticAxis::Z, light_mat),
+    ]);
+
+    let mut config = default_config();
+    config.lookfrom = Vec3::newi(16, 3, 2);
+    config.lookat = Vec3::newi(0, 1, 0);
+    config.vfov = 40.0;
+
+    Scene {
+        camera: Camera::new(config),
+        hittables,
+    }
+}
+
+pub fn noise_spheres() -> Scene {
+    let textures = vec![
+        NoiseTexture::marble(4., 1),
+        NoiseTexture::wood(6., 2),
+        NoiseTexture::clouds(2., 3),
+        NoiseTexture::stone(3., 4),
+    ];
+
+    let mut hittables: Vec<Hittables> = textures
+        .into_iter()
+        .enumerate()
+        .map(|(i, texture)| {
+            Hittables::from(Sphere {
+                center: Vec3::new(-3.3 + 2.2 * i as f32, 1., 0.),
+                radius: 1.,
+                mat: Lambertian::new(TextureType::from(texture)),
+            })
+        })
+        .collect();
+
+    hittables.push(Hittables::from(Sphere {
+        center: Vec3::newi(0, -1000, 0),
+        radius: 1000.,
+        mat: Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+    }));
+    hittables.push(Rect::new(
+        -4.0..4.0,
+        -2.0..4.0,
+        6.,
+        StaticAxis::Y,
+        DiffuseLight::new(ConstantTexture::new(4., 4., 4.)),
+    ));
+
+    let mut config = default_config();
+    config.lookfrom = Vec3::newi(0, 3, 12);
+    config.lookat = Vec3::newi(0, 1, 0);
+    config.vfov = 35.0;
+
+    Scene {
+        camera: Camera::new(config),
+        hittables: HittableList::new(hittables),
+    }
+}
+
 pub fn earth() -> Scene {
     // To test this, use -n 1 -d 1
-    let image = image::open("assets/textures/earthmap.jpg")
-        .expect("earthmap.jpg not found")
-        .to_rgb();
-    let (nx, ny) = image.dimensions();
+    let earth_texture = ImageTexture::open("assets/textures/earthmap.jpg", ColorSpace::Srgb)
+        .expect("earthmap.jpg not found");
 
     let earth = Hittables::from(Sphere {
         center: Vec3::new(0.0, 0.0, 0.0),
         radius: 2.0,
-        mat: MaterialType::from(Lambertian {
-            albedo: TextureType::from(ImageTexture {
-                data: image.into_raw(),
-                nx,
-                ny,
-            }),
-        }),
+        mat: Lambertian::new(TextureType::from(earth_texture)),
     });
 
     Scene {
@@ -218,10 +509,7 @@ pub fn earth() -> Scene {
 }
 
 pub fn simple_light() -> Scene {
-    let noise_texture = TextureType::from(NoiseTexture {
-        perlin: Perlin,
-        scale: 7.,
-    });
+    let noise_texture = TextureType::from(NoiseTexture::marble(7., 0));
 
     let light_mat = MaterialType::from(DiffuseLight {
         emit: ConstantTexture::new(4.0, 4.0, 4.0),
@@ -257,6 +545,607 @@ pub fn simple_light() -> Scene {
     }
 }
 
+pub fn patterns() -> Scene {
+    let dark = || ConstantTexture::new(0.1, 0.1, 0.1);
+    let light = || ConstantTexture::new(0.8, 0.8, 0.8);
+
+    let uv_patterns = vec![
+        (Pattern::Checker, 0.),
+        (Pattern::Stripes, 30.),
+        (Pattern::Grid { line_width: 0.1 }, 0.),
+        (Pattern::PolkaDot { radius: 0.3 }, 45.),
+        (Pattern::Brick { mortar: 0.05 }, 0.),
+    ];
+
+    let mut hittables: Vec<Hittables> = uv_patterns
+        .into_iter()
+        .enumerate()
+        .map(|(i, (pattern, rotation))| {
+            let x = 2.5 * i as f32 - 6.;
+            let texture = PatternTexture::new(
+                pattern,
+                PatternSpace::Uv,
+                4.,
+                rotation,
+                ConstantTexture::new(0.65, 0.05, 0.05),
+                light(),
+            );
+            Rect::new(
+                x..x + 2.,
+                0.5..2.5,
+                0.,
+                StaticAxis::Z,
+                Lambertian::new(texture),
+            )
+        })
+        .collect();
+
+    let floor = PatternTexture::new(
+        Pattern::Brick { mortar: 0.05 },
+        PatternSpace::World,
+        2.,
+        0.,
+        light(),
+        dark(),
+    );
+    hittables.push(Hittables::from(Sphere {
+        center: Vec3::newi(0, -1000, 0),
+        radius: 1000.,
+        mat: Lambertian::new(floor),
+    }));
+    hittables.push(Rect::new(
+        -6.0..6.0,
+        -6.0..6.0,
+        8.,
+        StaticAxis::Y,
+        DiffuseLight::new(ConstantTexture::new(3., 3., 3.)),
+    ));
+
+    let mut config = default_config();
+    config.lookfrom = Vec3::newi(0, 4, 14);
+    config.lookat = Vec3::new(0., 1.5, 0.);
+    config.vfov = 40.0;
+
+    Scene {
+        camera: Camera::new(config),
+        hittables: HittableList::new(hittables),
+    }
+}
+
+pub fn texture_nodes() -> Scene {
+    let earth = TextureType::from(
+        ImageTexture::open("assets/textures/earthmap.jpg", ColorSpace::Srgb)
+            .expect("earthmap.jpg not found"),
+    );
+    let clouds = TextureType::from(NoiseTexture::clouds(3., 5));
+    let grayscale_noise = TextureType::from(NoiseTexture::new(6));
+    let dots = PatternTexture::new(
+        Pattern::PolkaDot { radius: 0.35 },
+        PatternSpace::Uv,
+        1.,
+        0.,
+        ConstantTexture::new(1., 1., 1.),
+        ConstantTexture::new(0., 0., 0.),
+    );
+    let bricks = PatternTexture::new(
+        Pattern::Brick { mortar: 0.05 },
+        PatternSpace::Uv,
+        1.,
+        0.,
+        ConstantTexture::new(0.6, 0.25, 0.15),
+        ConstantTexture::new(0.8, 0.8, 0.75),
+    );
+
+    let textures = vec![
+        // Cloud cover darkened by a remapped noise
+        earth.clone() * RemapTexture::new(grayscale_noise.clone(), (0.3, 0.7), (0.4, 1.), true),
+        // Red and blue mixed by dots on a repeated uv grid
+        MixTexture::new(
+            ConstantTexture::new(0.1, 0.2, 0.7),
+            ConstantTexture::new(0.7, 0.1, 0.1),
+            UvTransformTexture::new(dots, Vec2::new(16., 8.), 0., Vec2::ZERO),
+        ),
+        // Scalar noise turned into a colour
+        RampTexture::new(
+            grayscale_noise,
+            Channel::Luminance,
+            ColorRamp::new(vec![
+                (0.35, Vec3::new(0.05, 0., 0.)),
+                (0.5, Vec3::new(0.8, 0.2, 0.)),
+                (0.65, Vec3::new(1., 0.9, 0.3)),
+            ]),
+        ),
+        MixTexture::lerp(InvertTexture::new(earth), clouds, 0.3),
+    ];
+
+    let mut hittables: Vec<Hittables> = textures
+        .into_iter()
+        .enumerate()
+        .map(|(i, texture)| {
+            Hittables::from(Sphere {
+                center: Vec3::new(-3.3 + 2.2 * i as f32, 1., 0.),
+                radius: 1.,
+                mat: Lambertian::new(texture),
+            })
+        })
+        .collect();
+
+    // Bricks projected on the box and the ground, which have no matching uv
+    let triplanar_bricks = TriplanarTexture::new(bricks, 2., 4.);
+    hittables.push(Translate::new(
+        RotateY::new(
+            BoxRect::new(
+                Vec3::ZERO,
+                Vec3::newi(2, 2, 2),
+                Lambertian::new(triplanar_bricks.clone()),
+            ),
+            30.,
+        ),
+        Vec3::new(1., 0., -4.),
+    ));
+    hittables.push(Hittables::from(Sphere {
+        center: Vec3::newi(0, -1000, 0),
+        radius: 1000.,
+        mat: Lambertian::new(triplanar_bricks),
+    }));
+    hittables.push(Rect::new(
+        -4.0..4.0,
+        -2.0..4.0,
+        6.,
+        StaticAxis::Y,
+        DiffuseLight::new(ConstantTexture::new(4., 4., 4.)),
+    ));
+
+    let mut config = default_config();
+    config.lookfrom = Vec3::newi(0, 3, 12);
+    config.lookat = Vec3::newi(0, 1, 0);
+    config.vfov = 35.0;
+
+    Scene {
+        camera: Camera::new(config),
+        hittables: HittableList::new(hittables),
+    }
+}
+
+pub fn primitives() -> Scene {
+    let wood = || Lambertian::new(TextureType::from(NoiseTexture::wood(8., 7)));
+    let red = Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
+    let gold = MaterialType::from(Metal {
+        albedo: ConstantTexture::new(0.8, 0.6, 0.2),
+        fuzz: 0.2,
+    });
+
+    // A small table with a cylinder leg, a disk foot and a flat cylinder top
+    let table = vec![
+        Cylinder::new(Vec3::ZERO, 0.15, 1.2, wood()),
+        Cylinder::new(Vec3::new(0., 1.2, 0.), 1.2, 0.1, wood()),
+        Disk::new(Vec3::new(0., 0.01, 0.), Vec3::Y, 0.6, wood()),
+    ];
+
+    let objects = vec![
+        Translate::new(BvhNode::new(table, 0., 1., 0), Vec3::new(-2.5, 0., 0.)),
+        Cone::new(Vec3::new(-2.5, 1.3, 0.), 0.4, 0.9, red.clone()),
+        Translate::new(
+            RotateY::new(Torus::new(Vec3::ZERO, 0.8, 0.25, gold), 20.),
+            Vec3::new(0.5, 0.25, 0.5),
+        ),
+        Cone::new(Vec3::new(2.5, 0., -0.5), 0.7, 1.8, red),
+        // A tilted panel
+        Quad::new(
+            Vec3::new(1.5, 0., -2.5),
+            Vec3::new(2., 0., 0.5),
+            Vec3::new(0., 2., -0.6),
+            Lambertian::new(default_checker()),
+        ),
+    ];
+
+    let hittables = HittableList::new(vec![
+        BvhNode::new(objects, 0., 1., 0),
+        Hittables::from(Sphere {
+            center: Vec3::newi(0, -1000, 0),
+            radius: 1000.,
+            mat: Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+        }),
+        Disk::new(
+            Vec3::new(0., 6., 2.),
+            -Vec3::Y,
+            3.,
+            DiffuseLight::new(ConstantTexture::new(4., 4., 4.)),
+        ),
+    ]);
+
+    let mut config = default_config();
+    config.lookfrom = Vec3::newi(0, 4, 10);
+    config.lookat = Vec3::newi(0, 1, 0);
+    config.vfov = 40.0;
+
+    Scene {
+        camera: Camera::new(config),
+        hittables,
+    }
+}
+
+/// Isometric orthographic view of the primitives
+pub fn blueprint() -> Scene {
+    let mut config = default_config();
+    config.lookfrom = Vec3::newi(10, 9, 10);
+    config.lookat = Vec3::newi(0, 1, 0);
+    config.camera_type = CameraType::Orthographic { height: 8. };
+
+    Scene {
+        camera: Camera::new(config),
+        ..primitives()
+    }
+}
+
+pub fn csg() -> Scene {
+    let red = || Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
+    let white = || Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73));
+    let glass = || MaterialType::from(Dielectric { ref_idx: 1.5 });
+    let sphere = |center: Vec3, radius: f32, mat: MaterialType| {
+        Hittables::from(Sphere {
+            center,
+            radius,
+            mat,
+        })
+    };
+
+    let objects = vec![
+        // A sphere with a box carved out of its corner, the cut faces take the box material
+        Csg::difference(
+            sphere(Vec3::new(-2.5, 1., 0.), 1., red()),
+            BoxRect::new(Vec3::new(-2.5, 1., 0.), Vec3::new(-1., 2.5, 1.5), white()),
+        ),
+        // A die, the intersection of a cube and a sphere with the dots hollowed out
+        Csg::difference(
+            Csg::intersection(
+                BoxRect::new(
+                    Vec3::new(-0.8, 0.2, -0.8),
+                    Vec3::new(0.8, 1.8, 0.8),
+                    white(),
+                ),
+                sphere(Vec3::newi(0, 1, 0), 1.1, white()),
+            ),
+            Csg::union(
+                sphere(Vec3::new(0., 1., 0.95), 0.25, red()),
+                sphere(Vec3::new(0., 1.95, 0.), 0.25, red()),
+            ),
+        ),
+        // Two merged glass spheres with a hole drilled through
+        Csg::difference(
+            Csg::union(
+                sphere(Vec3::new(2.2, 1., 0.), 0.8, glass()),
+                sphere(Vec3::new(3., 1., 0.), 0.8, glass()),
+            ),
+            Cylinder::new(Vec3::new(2.6, 0., 0.), 0.3, 2., glass()),
+        ),
+    ];
+
+    let hittables = HittableList::new(vec![
+        BvhNode::new(objects, 0., 1., 0),
+        sphere(
+            Vec3::newi(0, -1000, 0),
+            1000.,
+            Lambertian::new(default_checker()),
+        ),
+        Disk::new(
+            Vec3::new(0., 6., 3.),
+            -Vec3::Y,
+            3.,
+            DiffuseLight::new(ConstantTexture::new(4., 4., 4.)),
+        ),
+    ]);
+
+    let mut config = default_config();
+    config.lookfrom = Vec3::newi(2, 4, 9);
+    config.lookat = Vec3::newi(0, 1, 0);
+    config.vfov = 40.0;
+
+    Scene {
+        camera: Camera::new(config),
+        hittables,
+    }
+}
+
+pub fn sdf() -> Scene {
+    let shape = |sdf: Sdf, center: Vec3, half_size: f32, mat: MaterialType, step_scale: f32| {
+        SdfShape::with_step_scale(
+            sdf.translate(center),
+            AABB {
+                min: center - Vec3::splat(half_size),
+                max: center + Vec3::splat(half_size),
+            },
+            mat,
+            step_scale,
+        )
+    };
+    let red = Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
+    let blue = Lambertian::new(ConstantTexture::new(0.1, 0.2, 0.6));
+    let gold = MaterialType::from(Metal {
+        albedo: ConstantTexture::new(0.8, 0.6, 0.2),
+        fuzz: 0.1,
+    });
+
+    let rounded_box = shape(
+        Sdf::cube(Vec3::splat(0.6)).round(0.2),
+        Vec3::new(-3., 1., 0.),
+        1.,
+        red,
+        1.,
+    );
+    let blob = shape(
+        Sdf::sphere(0.6)
+            .translate(Vec3::new(-0.4, 0., 0.))
+            .smooth_union(Sdf::sphere(0.5).translate(Vec3::new(0.45, 0.2, 0.)), 0.4)
+            .smooth_union(
+                Sdf::torus(0.6, 0.12).translate(Vec3::new(0., -0.5, 0.)),
+                0.3,
+            ),
+        Vec3::new(-1., 1., 0.),
+        1.,
+        blue,
+        1.,
+    );
+    // The twist stretches distances, so the field overestimates them
+    let twisted = shape(
+        Sdf::cube(Vec3::new(0.4, 0.9, 0.4)).twist(1.5),
+        Vec3::new(1., 1., 0.),
+        1.,
+        gold.clone(),
+        0.5,
+    );
+    let bulb = shape(
+        Sdf::mandelbulb(8., 10).scale(0.8),
+        Vec3::new(3., 1., 0.),
+        1.,
+        gold,
+        1.,
+    );
+
+    // A field of small spheres repeated inside a thin box
+    let pebbles = SdfShape::new(
+        Sdf::sphere(0.1).repeat(Vec3::new(0.3, 0., 0.3)),
+        AABB {
+            min: Vec3::new(-4., -0.1, 1.),
+            max: Vec3::new(4., 0.1, 2.),
+        },
+        Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73)),
+    );
+
+    let objects = vec![rounded_box, blob, twisted, bulb, pebbles];
+
+    let hittables = HittableList::new(vec![
+        BvhNode::new(objects, 0., 1., 0),
+        Hittables::from(Sphere {
+            center: Vec3::newi(0, -1000, 0),
+            radius: 1000.,
+            mat: Lambertian::new(default_checker()),
+        }),
+        Disk::new(
+            Vec3::new(0., 6., 3.),
+            -Vec3::Y,
+            3.,
+            DiffuseLight::new(ConstantTexture::new(4., 4., 4.)),
+        ),
+    ]);
+
+    let mut config = default_config();
+    config.lookfrom = Vec3::newi(0, 4, 10);
+    config.lookat = Vec3::newi(0, 1, 0);
+    config.vfov = 40.0;
+
+    Scene {
+        camera: Camera::new(config),
+        hittables,
+    }
+}
+
+pub fn terrain() -> Scene {
+    let noise = NoiseTexture {
+        octaves: 6,
+        scale: 4.,
+        ..NoiseTexture::new(11)
+    };
+    let grass_and_rock = NoiseTexture {
+        scale: 3.,
+        ramp: ColorRamp::new(vec![
+            (0.35, Vec3::new(0.15, 0.35, 0.1)),
+            (0.6, Vec3::new(0.4, 0.35, 0.25)),
+            (0.75, Vec3::new(0.55, 0.52, 0.5)),
+        ]),
+        ..NoiseTexture::new(12)
+    };
+
+    let terrain = Heightfield::from_fn(
+        256,
+        256,
+        Vec3::new(-10., -1., -10.),
+        Vec3::new(20., 4., 20.),
+        |u, v| 2. * noise.fractal_noise(Vec3::new(u, 0., v) * noise.scale) - 0.6,
+        Lambertian::new(TextureType::from(grass_and_rock)),
+    );
+
+    let water = Hittables::from(Sphere {
+        center: Vec3::newi(0, -1000, 0),
+        radius: 1000.,
+        mat: MaterialType::from(Metal {
+            albedo: ConstantTexture::new(0.2, 0.3, 0.5),
+            fuzz: 0.05,
+        }),
+    });
+    let sun = Hittables::from(Sphere {
+        center: Vec3::newi(-30, 40, 30),
+        radius: 25.,
+        mat: DiffuseLight::new(ConstantTexture::new(4., 3.8, 3.5)),
+    });
+
+    let mut config = default_config();
+    config.lookfrom = Vec3::newi(0, 6, 14);
+    config.lookat = Vec3::newi(0, 0, 0);
+    config.vfov = 50.0;
+
+    Scene {
+        camera: Camera::new(config),
+        hittables: HittableList::new(vec![terrain, water, sun]),
+    }
+}
+
+pub fn hair(rng: &mut impl Rng) -> Scene {
+    let mut strands = Vec::new();
+
+    // Fur ball, strands leave the surface along the normal and droop under their weight
+    let fur = Hair::new(ConstantTexture::new(0.55, 0.3, 0.12));
+    let center = Vec3::new(0., 1.2, 0.);
+    for _ in 0..4000 {
+        let normal = loop {
+            let p = 2. * Vec3::new(random_double(rng), random_double(rng), random_double(rng))
+                - Vec3::ONE;
+            if p.length_squared() < 1. && p.length_squared() > 0.01 {
+                break p.normalize();
+            }
+        };
+        let length = 0.35 + 0.15 * random_double(rng);
+        let root = center + normal * 0.8;
+        let droop = Vec3::new(0., -0.6 * length, 0.);
+        strands.push(Curve::new(
+            [
+                root,
+                root + normal * length * 0.4,
+                root + normal * length * 0.8 + droop * 0.5,
+                root + normal * length + droop,
+            ],
+            0.012,
+            0.002,
+            fur.clone(),
+        ));
+    }
+    strands.push(Hittables::from(Sphere {
+        center,
+        radius: 0.8,
+        mat: Lambertian::new(ConstantTexture::new(0.3, 0.15, 0.05)),
+    }));
+
+    // Grass blades bent by the wind
+    let grass = MaterialType::from(Hair {
+        color: ConstantTexture::new(0.2, 0.5, 0.1),
+        specular: 0.05,
+        longitudinal_roughness: 0.3,
+        azimuthal_roughness: 0.8,
+        shift: 0.,
+    });
+    for _ in 0..3000 {
+        let root = Vec3::new(
+            -4. + 8. * random_double(rng),
+            0.,
+            -3. + 5. * random_double(rng),
+        );
+        let height = 0.3 + 0.4 * random_double(rng);
+        let bend = Vec3::new(0.2 + 0.2 * random_double(rng), 0., 0.1 * random_double(rng));
+        strands.push(Curve::new(
+            [
+                root,
+                root + Vec3::new(0., height * 0.4, 0.),
+                root + Vec3::new(0., height * 0.8, 0.) + bend * 0.4,
+                root + Vec3::new(0., height, 0.) + bend,
+            ],
+            0.03,
+            0.0,
+            grass.clone(),
+        ));
+    }
+
+    // Copper cable hanging between two posts, split so the BVH bounds it tightly
+    let copper = MaterialType::from(Metal {
+        albedo: ConstantTexture::new(0.85, 0.45, 0.3),
+        fuzz: 0.2,
+    });
+    strands.extend(Curve::split(
+        [
+            Vec3::new(-3.5, 2.5, -1.5),
+            Vec3::new(-1.5, 0.8, -1.5),
+            Vec3::new(1.5, 0.8, -1.5),
+            Vec3::new(3.5, 2.5, -1.5),
+        ],
+        0.05,
+        0.05,
+        16,
+        copper,
+    ));
+
+    let ground = Hittables::from(Sphere {
+        center: Vec3::newi(0, -1000, 0),
+        radius: 1000.,
+        mat: Lambertian::new(ConstantTexture::new(0.35, 0.25, 0.15)),
+    });
+    let light = Hittables::from(Sphere {
+        center: Vec3::newi(-5, 10, 8),
+        radius: 4.,
+        mat: DiffuseLight::new(ConstantTexture::new(6., 6., 5.5)),
+    });
+    let sky = Hittables::from(Sphere {
+        center: Vec3::ZERO,
+        radius: 100.,
+        mat: DiffuseLight::new(ConstantTexture::new(0.3, 0.35, 0.45)),
+    });
+
+    let mut config = default_config();
+    config.lookfrom = Vec3::new(0., 2.5, 7.);
+    config.lookat = Vec3::new(0., 1., 0.);
+    config.vfov = 45.0;
+
+    Scene {
+        camera: Camera::new(config),
+        hittables: HittableList::new(vec![BvhNode::new(strands, 0., 1., 0), ground, light, sky]),
+    }
+}
+
+pub fn particles(rng: &mut impl Rng) -> Scene {
+    use std::f32::consts::PI;
+
+    // Spiral galaxy, particles are bluer and sparser away from the bulge
+    let mut particles = Vec::new();
+    for _ in 0..500_000 {
+        let arm = (random_double(rng) * 2.).floor();
+        let distance = random_double(rng).powf(1.5) * 4.;
+        let angle = arm * PI + distance * 1.3 + 0.4 * (random_double(rng) - 0.5);
+        let spread = 0.3 * (random_double(rng) - 0.5) * (1. + distance * 0.2);
+        let height = 0.3 * (random_double(rng) - 0.5) * (-distance).exp();
+        let position = Vec3::new(
+            (distance + spread) * angle.cos(),
+            height,
+            (distance + spread) * angle.sin(),
+        );
+
+        let warmth = (-distance * 0.8).exp();
+        let color = Vec3::new(0.6, 0.7, 1.) * (1. - warmth) + Vec3::new(1., 0.8, 0.5) * warmth;
+        particles.push(Particle::new(
+            position,
+            0.008 + 0.01 * random_double(rng),
+            color,
+        ));
+    }
+    let galaxy = Particles::new(
+        particles,
+        true,
+        Lambertian::new(VertexColorTexture::new(ConstantTexture::new(1., 1., 1.))),
+    );
+    let light = Hittables::from(Sphere {
+        center: Vec3::newi(0, 0, 0),
+        radius: 50.,
+        mat: DiffuseLight::new(ConstantTexture::new(1., 1., 1.)),
+    });
+
+    let mut config = default_config();
+    config.lookfrom = Vec3::new(0., 4., 6.);
+    config.lookat = Vec3::new(0., 0., 0.);
+    config.vfov = 50.0;
+
+    Scene {
+        camera: Camera::new(config),
+        hittables: HittableList::new(vec![galaxy, light]),
+    }
+}
+
 pub fn cornell_box() -> Hittables {
     let red = Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
     let green = Lambertian::new(ConstantTexture::new(0.12, 0.45, 0.15));
@@ -267,28 +1156,31 @@ pub fn cornell_box() -> Hittables {
     HittableList::new(vec![
         Rect::new(113.0..443.0, 127.0..432.0, 554.0, StaticAxis::Y, light),
         Rect::new(0.0..555.0, 0.0..555.0, 0.0, StaticAxis::Y, white.clone()), //floor
-        FlipNormals::new(Rect::new(
+        Rect::new_facing(
             0.0..555.0,
             0.0..555.0,
             555.0,
             StaticAxis::Y,
+            Facing::Negative,
             white.clone(),
-        )), //ceiling
-        FlipNormals::new(Rect::new(
+        ), //ceiling
+        Rect::new_facing(
             0.0..555.0,
             0.0..555.0,
             555.0,
             StaticAxis::Z,
+            Facing::Negative,
             white,
-        )), // rear wall
+        ), // rear wall
         Rect::new(0.0..555.0, 0.0..555.0, 0.0, StaticAxis::X, red),
-        FlipNormals::new(Rect::new(
+        Rect::new_facing(
             0.0..555.0,
             0.0..555.0,
             555.0,
             StaticAxis::X,
+            Facing::Negative,
             green,
-        )),
+        ),
     ])
 }
 
@@ -336,3 +1228,19 @@ pub fn cornell_box_scene() -> Scene {
         hittables: BvhNode::new(vec![cornell_box(), box1, box2], 0.0, 1.0, 0),
     }
 }
+
+/// Cornell box seen from its middle, for the projections covering more than a hemisphere
+pub fn cornell_box_inside(camera_type: CameraType, stereo: Option<Stereo>) -> Scene {
+    let mut cam_config = default_config();
+    cam_config.lookfrom = Vec3::newi(278, 278, 200);
+    cam_config.lookat = Vec3::newi(278, 278, 555);
+    cam_config.camera_type = camera_type;
+    cam_config.stereo = stereo;
+
+    let (box1, box2) = cornell_boxes();
+
+    Scene {
+        camera: Camera::new(cam_config),
+        hittables: BvhNode::new(vec![cornell_box(), box1, box2], 0.0, 1.0, 0),
+    }
+}
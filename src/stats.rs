@@ -0,0 +1,292 @@
+//! Counters of the work done by a render and the report made from them
+//!
+//! The counters are kept per thread so the traversal code only bumps a local value, the
+//! renderer reads them before and after each tile to attribute the work to it.
+
+use std::{cell::Cell, fmt::Write, path::Path, time::Duration, time::Instant};
+
+use crate::{texture::color_ramp::ColorRamp, vec3::Vec3};
+
+thread_local! {
+    static COUNTERS: Cell<Counters> = Cell::new(Counters::default());
+    static BVH_BUILD_TIME: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Counters {
+    /// Rays leaving the camera
+    pub camera_rays: u64,
+    /// Rays scattered by a surface or a medium
+    pub bounce_rays: u64,
+    /// Bounding boxes tested while going down a BVH
+    pub bvh_nodes: u64,
+    /// Intersection tests against a single primitive, like a sphere or a triangle
+    pub primitive_tests: u64,
+}
+
+impl Counters {
+    pub fn rays(&self) -> u64 {
+        self.camera_rays + self.bounce_rays
+    }
+
+    pub fn add(&mut self, other: &Counters) {
+        self.camera_rays += other.camera_rays;
+        self.bounce_rays += other.bounce_rays;
+        self.bvh_nodes += other.bvh_nodes;
+        self.primitive_tests += other.primitive_tests;
+    }
+
+    /// Work done since `earlier` was read
+    pub fn since(&self, earlier: &Counters) -> Counters {
+        Counters {
+            camera_rays: self.camera_rays - earlier.camera_rays,
+            bounce_rays: self.bounce_rays - earlier.bounce_rays,
+            bvh_nodes: self.bvh_nodes - earlier.bvh_nodes,
+            primitive_tests: self.primitive_tests - earlier.primitive_tests,
+        }
+    }
+}
+
+/// Adds to the counters of the current thread
+#[inline]
+pub fn count(update: impl FnOnce(&mut Counters)) {
+    COUNTERS.with(|counters| {
+        let mut value = counters.get();
+        update(&mut value);
+        counters.set(value);
+    })
+}
+
+/// Everything counted on the current thread so far
+pub fn thread_counters() -> Counters {
+    COUNTERS.with(Cell::get)
+}
+
+/// Runs `build` as part of the time spent building BVHs on the current thread
+pub fn time_bvh_build<T>(build: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = build();
+    BVH_BUILD_TIME.with(|time| time.set(time.get() + start.elapsed()));
+    result
+}
+
+/// Time spent building BVHs on the current thread so far
+pub fn bvh_build_time() -> Duration {
+    BVH_BUILD_TIME.with(Cell::get)
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadStats {
+    pub rays: u64,
+    /// Time spent rendering tiles
+    pub time: Duration,
+}
+
+/// Work done by a render, summed over its tiles
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    pub counters: Counters,
+    /// Work of each thread of the pool, by index
+    pub threads: Vec<ThreadStats>,
+}
+
+impl RenderStats {
+    /// Work of a single tile rendered by the thread `thread`
+    pub fn new(thread: usize, counters: Counters, time: Duration) -> Self {
+        let mut threads = vec![ThreadStats::default(); thread + 1];
+        threads[thread] = ThreadStats {
+            rays: counters.rays(),
+            time,
+        };
+        RenderStats { counters, threads }
+    }
+
+    pub fn add(&mut self, other: &RenderStats) {
+        self.counters.add(&other.counters);
+        if self.threads.len() < other.threads.len() {
+            self.threads
+                .resize(other.threads.len(), ThreadStats::default());
+        }
+        for (thread, other) in self.threads.iter_mut().zip(&other.threads) {
+            thread.rays += other.rays;
+            thread.time += other.time;
+        }
+    }
+
+    /// Human readable summary of the counters and of the time taken by each phase
+    pub fn report(&self, phases: &Phases) -> String {
+        let counters = &self.counters;
+        let rays = counters.rays();
+        let per_ray = |count: u64| count as f64 / rays.max(1) as f64;
+        let mut report = String::new();
+
+        // Writing to a string never fails
+        let _ = writeln!(report, "Phases");
+        for (name, time) in [
+            ("scene build", phases.scene_build),
+            ("BVH build", phases.bvh_build),
+            ("render", phases.render),
+            ("post-process", phases.post_process),
+        ]
+        .iter()
+        {
+            let _ = writeln!(report, "  {:<20}{:>12.3?}", name, time);
+        }
+
+        let _ = writeln!(report, "{:<22}{:>12}", "Rays", rays);
+        let _ = writeln!(report, "  {:<20}{:>12}", "camera", counters.camera_rays);
+        let _ = writeln!(report, "  {:<20}{:>12}", "bounce", counters.bounce_rays);
+        // Lights are only found by hitting them, no ray is ever traced toward them
+        let _ = writeln!(report, "  {:<20}{:>12}", "shadow", 0);
+        let _ = writeln!(
+            report,
+            "{:<22}{:>12.2} rays",
+            "Average path length",
+            rays as f64 / counters.camera_rays.max(1) as f64
+        );
+        let _ = writeln!(
+            report,
+            "{:<22}{:>12.2} BVH nodes, {:.2} primitive tests",
+            "Per ray",
+            per_ray(counters.bvh_nodes),
+            per_ray(counters.primitive_tests)
+        );
+
+        let _ = writeln!(report, "Threads");
+        for (index, thread) in self.threads.iter().enumerate() {
+            let seconds = thread.time.as_secs_f64();
+            let rate = if seconds > 0. {
+                thread.rays as f64 / seconds
+            } else {
+                0.
+            };
+            let _ = writeln!(
+                report,
+                "  #{:<19}{:>12} rays in {:.3?}, {:.3} Mrays/s",
+                index,
+                thread.rays,
+                thread.time,
+                rate / 1e6
+            );
+        }
+        report
+    }
+}
+
+/// Time taken by each step of making an image
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Phases {
+    /// Building the scene without its BVHs, including loading its files
+    pub scene_build: Duration,
+    pub bvh_build: Duration,
+    pub render: Duration,
+    /// Denoising and saving the image
+    pub post_process: Duration,
+}
+
+/// Saves the time spent on each pixel as a heatmap, going from black for the cheapest pixels
+/// through red to white for the ones at or above the 99th percentile
+pub fn save_heatmap(
+    cost: &[f32],
+    width: u32,
+    height: u32,
+    path: impl AsRef<Path>,
+) -> Result<(), String> {
+    let ramp = ColorRamp::new(vec![
+        (0., Vec3::ZERO),
+        (0.3, Vec3::new(0.3, 0., 0.6)),
+        (0.6, Vec3::new(0.9, 0.1, 0.)),
+        (0.85, Vec3::new(1., 0.7, 0.)),
+        (1., Vec3::ONE),
+    ]);
+
+    // A few pathological pixels would otherwise squash all the others to black
+    let mut sorted = cost.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let min = sorted.first().copied().unwrap_or(0.);
+    let max = sorted.get(sorted.len() * 99 / 100).copied().unwrap_or(0.);
+    let range = (max - min).max(f32::MIN_POSITIVE);
+
+    let pixels: Vec<u8> = cost
+        .iter()
+        .flat_map(|c| {
+            let color = 255.99 * ramp.sample((c - min) / range);
+            vec![color.x as u8, color.y as u8, color.z as u8]
+        })
+        .collect();
+    image::save_buffer(
+        path.as_ref(),
+        &pixels,
+        width,
+        height,
+        image::ColorType::Rgb8,
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hittable::{
+            bvh_node::BvhNode, object::number_objects, sphere::Sphere, Hittable, Hittables,
+        },
+        material::Lambertian,
+        ray::Ray,
+        texture::constant_texture::ConstantTexture,
+    };
+
+    fn spheres() -> Hittables {
+        let sphere = |x: f32| {
+            Hittables::from(Sphere {
+                center: Vec3::new(x, 0., 0.),
+                radius: 0.5,
+                mat: Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+            })
+        };
+        BvhNode::new((0..4).map(|i| sphere(2. * i as f32)).collect(), 0., 1., 0)
+    }
+
+    /// Work counted on this thread while hitting `world` with a ray through `x`
+    fn counted(world: &Hittables, x: f32) -> Counters {
+        let r = Ray::new(Vec3::new(x, 0.2, 5.), Vec3::new(0., 0., -1.), 0.);
+        let before = thread_counters();
+        assert!(world.hit(&r, 0.001, f32::MAX).is_some());
+        thread_counters().since(&before)
+    }
+
+    #[test]
+    fn bvh_walks_are_counted() {
+        let work = counted(&spheres(), 2.);
+        assert!(work.bvh_nodes >= 3, "{:?}", work);
+        assert!(
+            work.primitive_tests >= 1 && work.primitive_tests <= 4,
+            "{:?}",
+            work
+        );
+    }
+
+    #[test]
+    fn object_ids_do_not_change_the_counts() {
+        for x in [0., 2., 4., 6.].iter() {
+            assert_eq!(
+                counted(&spheres(), *x),
+                counted(&number_objects(spheres()), *x)
+            );
+        }
+    }
+
+    #[test]
+    fn counters_add_up() {
+        let a = Counters {
+            camera_rays: 3,
+            bounce_rays: 5,
+            bvh_nodes: 7,
+            primitive_tests: 11,
+        };
+        let mut b = a;
+        b.add(&a);
+        assert_eq!(b.rays(), 16);
+        assert_eq!(b.since(&a), a);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    texture::{Texture, TextureType},
+    texture::{Texture, TextureContext, TextureType},
     vec3::Vec3,
 };
 
@@ -11,10 +11,16 @@ pub struct CheckerTexture {
 
 impl Texture for CheckerTexture {
     fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        // Without a context the lookup is a sample with no footprint
+        self.sample(&TextureContext::new(u, v, p))
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        let p = ctx.point;
         let sines = (10. * p.x).sin() * (10. * p.y).sin() * (10. * p.z).sin();
         match sines {
-            s if s < 0. => self.odd.value(u, v, p),
-            _ => self.even.value(u, v, p),
+            s if s < 0. => self.odd.sample(ctx),
+            _ => self.even.sample(ctx),
         }
     }
 }
@@ -0,0 +1,43 @@
+use crate::vec3::Vec3;
+
+/// Maps a scalar in `[0, 1]` to a colour by interpolating between sorted stops
+#[derive(Clone, Debug)]
+pub struct ColorRamp {
+    stops: Vec<(f32, Vec3)>,
+}
+
+impl ColorRamp {
+    pub fn new(mut stops: Vec<(f32, Vec3)>) -> Self {
+        assert!(!stops.is_empty(), "a color ramp needs at least one stop");
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        ColorRamp { stops }
+    }
+
+    pub fn grayscale() -> Self {
+        ColorRamp::new(vec![(0., Vec3::ZERO), (1., Vec3::ONE)])
+    }
+
+    pub fn sample(&self, t: f32) -> Vec3 {
+        let first = self.stops[0];
+        if t <= first.0 {
+            return first.1;
+        }
+
+        for window in self.stops.windows(2) {
+            let (t0, c0) = window[0];
+            let (t1, c1) = window[1];
+            if t <= t1 {
+                let s = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1. };
+                return c0.lerp(c1, s);
+            }
+        }
+
+        self.stops[self.stops.len() - 1].1
+    }
+}
+
+impl Default for ColorRamp {
+    fn default() -> Self {
+        ColorRamp::grayscale()
+    }
+}
@@ -1,30 +1,338 @@
-use super::Texture;
-use crate::{utils::clamp, vec3::Vec3};
+use super::{Texture, TextureContext};
+use crate::{
+    utils::clamp,
+    vec3::{Vec3, Vec3Wrapper},
+};
+use glam::{Vec2, Vec4};
+use image::{
+    error::{ParameterError, ParameterErrorKind},
+    hdr::HdrDecoder,
+    DynamicImage, ImageError, ImageResult, Pixel,
+};
+use lazy_static::lazy_static;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+lazy_static! {
+    /// Every image loaded with `ImageTexture::open`, so a file used by several materials is only
+    /// decoded once
+    static ref TEXTURE_CACHE: Mutex<HashMap<(PathBuf, ColorSpace), Arc<MipMap>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// How the stored values of an image should be interpreted
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Colour images, decoded to linear on load
+    Srgb,
+    /// Data images like normal maps or heights, used as is
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum FilterMode {
+    Nearest,
+    Bilinear,
+    /// Bilinear lookups in the two closest mip levels
+    Trilinear,
+}
+
+/// What happens to uv coordinates outside of `[0, 1]`
+#[derive(Clone, Copy, Debug)]
+pub enum WrapMode {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+pub struct MipLevel {
+    pub width: u32,
+    pub height: u32,
+    pub texels: Vec<Vec4>,
+}
+
+/// Linear RGBA image with its chain of downsampled levels, the first level is the full image
+pub struct MipMap {
+    pub levels: Vec<MipLevel>,
+}
 
 #[derive(Clone)]
 pub struct ImageTexture {
-    pub data: Vec<u8>,
-    pub nx: u32,
-    pub ny: u32,
+    pub image: Arc<MipMap>,
+    pub filter: FilterMode,
+    pub wrap: WrapMode,
+    pub uv_scale: Vec2,
+    pub uv_offset: Vec2,
+}
+
+impl ImageTexture {
+    /// Loads an image through the texture cache with trilinear filtering and repeat wrapping
+    pub fn open(path: impl AsRef<Path>, color_space: ColorSpace) -> ImageResult<ImageTexture> {
+        let key = (path.as_ref().to_path_buf(), color_space);
+        if let Some(image) = TEXTURE_CACHE.lock().unwrap().get(&key) {
+            return Ok(ImageTexture::new(image.clone()));
+        }
+
+        let image = Arc::new(MipMap::open(path.as_ref(), color_space)?);
+        TEXTURE_CACHE.lock().unwrap().insert(key, image.clone());
+        Ok(ImageTexture::new(image))
+    }
+
+    pub fn from_image(image: &DynamicImage, color_space: ColorSpace) -> ImageResult<ImageTexture> {
+        Ok(ImageTexture::new(Arc::new(MipMap::from_image(
+            image,
+            color_space,
+        )?)))
+    }
+
+    pub fn new(image: Arc<MipMap>) -> ImageTexture {
+        ImageTexture {
+            image,
+            filter: FilterMode::Trilinear,
+            wrap: WrapMode::Repeat,
+            uv_scale: Vec2::ONE,
+            uv_offset: Vec2::ZERO,
+        }
+    }
+
+    /// Filtered RGBA lookup, `footprint` is the width covered by the lookup in uv units
+    pub fn sample_rgba(&self, u: f32, v: f32, footprint: f32) -> Vec4 {
+        let u = u * self.uv_scale.x + self.uv_offset.x;
+        let v = v * self.uv_scale.y + self.uv_offset.y;
+
+        match self.filter {
+            FilterMode::Nearest => self.nearest(0, u, v),
+            FilterMode::Bilinear => self.bilinear(0, u, v),
+            FilterMode::Trilinear => {
+                let base = &self.image.levels[0];
+                let scaled = footprint * self.uv_scale.max_element();
+                let texels = scaled * base.width.max(base.height) as f32;
+                let max_level = (self.image.levels.len() - 1) as f32;
+                let lod = clamp(texels.max(f32::MIN_POSITIVE).log2(), 0., max_level);
+
+                let level = lod.floor() as usize;
+                let t = lod - level as f32;
+                if t == 0. {
+                    self.bilinear(level, u, v)
+                } else {
+                    self.bilinear(level, u, v)
+                        .lerp(self.bilinear(level + 1, u, v), t)
+                }
+            }
+        }
+    }
+
+    fn texel(&self, level: usize, x: i32, y: i32) -> Vec4 {
+        let level = &self.image.levels[level];
+        let x = wrap(x, level.width as i32, self.wrap);
+        let y = wrap(y, level.height as i32, self.wrap);
+        level.texels[(x + y * level.width as i32) as usize]
+    }
+
+    fn nearest(&self, level: usize, u: f32, v: f32) -> Vec4 {
+        let MipLevel { width, height, .. } = self.image.levels[level];
+        let x = (u * width as f32).floor() as i32;
+        let y = ((1. - v) * height as f32).floor() as i32;
+        self.texel(level, x, y)
+    }
+
+    fn bilinear(&self, level: usize, u: f32, v: f32) -> Vec4 {
+        let MipLevel { width, height, .. } = self.image.levels[level];
+        let x = u * width as f32 - 0.5;
+        let y = (1. - v) * height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let top = self
+            .texel(level, x0, y0)
+            .lerp(self.texel(level, x0 + 1, y0), dx);
+        let bottom = self
+            .texel(level, x0, y0 + 1)
+            .lerp(self.texel(level, x0 + 1, y0 + 1), dx);
+        top.lerp(bottom, dy)
+    }
 }
 
 impl Texture for ImageTexture {
-    #[allow(clippy::many_single_char_names)]
     fn value(&self, u: f32, v: f32, _p: Vec3) -> Vec3 {
-        let nx = self.nx as f32;
-        let ny = self.ny as f32;
+        self.sample_rgba(u, v, 0.).truncate()
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        self.sample_rgba(ctx.u, ctx.v, ctx.footprint).truncate()
+    }
+}
+
+fn wrap(i: i32, size: i32, mode: WrapMode) -> i32 {
+    match mode {
+        WrapMode::Repeat => i.rem_euclid(size),
+        WrapMode::Mirror => {
+            let i = i.rem_euclid(2 * size);
+            if i >= size {
+                2 * size - 1 - i
+            } else {
+                i
+            }
+        }
+        WrapMode::Clamp => clamp(i, 0, size - 1),
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl MipMap {
+    /// Radiance `.hdr` files are read as floats, anything else goes through `image::open`
+    pub fn open(path: &Path, color_space: ColorSpace) -> ImageResult<MipMap> {
+        let is_hdr = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some(ext) if ext.eq_ignore_ascii_case("hdr")
+        );
+
+        if is_hdr {
+            let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+            let metadata = decoder.metadata();
+            let texels = decoder
+                .read_image_hdr()?
+                .into_iter()
+                .map(|p| Vec4::new(p[0], p[1], p[2], 1.))
+                .collect();
+            MipMap::new(metadata.width, metadata.height, texels)
+        } else {
+            MipMap::from_image(&image::open(path)?, color_space)
+        }
+    }
+
+    /// Converts 8 and 16 bit images of any channel layout to linear RGBA
+    pub fn from_image(image: &DynamicImage, color_space: ColorSpace) -> ImageResult<MipMap> {
+        let decode = |rgba: [f32; 4]| -> Vec4 {
+            let rgb = Vec3::new(rgba[0], rgba[1], rgba[2]);
+            let rgb = match color_space {
+                ColorSpace::Srgb => rgb.map(srgb_to_linear),
+                ColorSpace::Linear => rgb,
+            };
+            rgb.extend(rgba[3])
+        };
+        let from_u16 = |p: image::Rgba<u16>| decode(p.0.map(|c| c as f32 / 65535.));
+
+        let (width, height, texels) = match image {
+            DynamicImage::ImageLuma16(img) => {
+                let texels = img.pixels().map(|p| from_u16(p.to_rgba())).collect();
+                (img.width(), img.height(), texels)
+            }
+            DynamicImage::ImageLumaA16(img) => {
+                let texels = img.pixels().map(|p| from_u16(p.to_rgba())).collect();
+                (img.width(), img.height(), texels)
+            }
+            DynamicImage::ImageRgb16(img) => {
+                let texels = img.pixels().map(|p| from_u16(p.to_rgba())).collect();
+                (img.width(), img.height(), texels)
+            }
+            DynamicImage::ImageRgba16(img) => {
+                let texels = img.pixels().map(|p| from_u16(*p)).collect();
+                (img.width(), img.height(), texels)
+            }
+            image => {
+                let img = image.to_rgba();
+                let texels = img
+                    .pixels()
+                    .map(|p| decode(p.0.map(|c| c as f32 / 255.)))
+                    .collect();
+                (img.width(), img.height(), texels)
+            }
+        };
+
+        MipMap::new(width, height, texels)
+    }
+
+    /// Builds the mip chain by averaging 2x2 blocks down to a single texel, empty images have
+    /// no texel to start from
+    pub fn new(width: u32, height: u32, texels: Vec<Vec4>) -> ImageResult<MipMap> {
+        if width == 0 || height == 0 || texels.len() != width as usize * height as usize {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            )));
+        }
+
+        let mut levels = vec![MipLevel {
+            width,
+            height,
+            texels,
+        }];
 
-        let mut i = (u * nx) as usize;
-        let mut j = ((1. - v) * ny - 0.001) as usize;
+        loop {
+            let previous = levels.last().unwrap();
+            if previous.width == 1 && previous.height == 1 {
+                break;
+            }
 
-        i = clamp(i, 0, nx as usize - 1);
-        j = clamp(j, 0, ny as usize - 1);
+            let width = (previous.width / 2).max(1);
+            let height = (previous.height / 2).max(1);
+            let fetch = |x: u32, y: u32| -> Vec4 {
+                let x = x.min(previous.width - 1);
+                let y = y.min(previous.height - 1);
+                previous.texels[(x + y * previous.width) as usize]
+            };
 
-        let index = 3 * i + 3 * nx as usize * j;
-        let r = self.data[index] as f32 / 255.;
-        let g = self.data[index + 1] as f32 / 255.;
-        let b = self.data[index + 2] as f32 / 255.;
+            let mut texels = Vec::with_capacity((width * height) as usize);
+            for y in 0..height {
+                for x in 0..width {
+                    let sum = fetch(2 * x, 2 * y)
+                        + fetch(2 * x + 1, 2 * y)
+                        + fetch(2 * x, 2 * y + 1)
+                        + fetch(2 * x + 1, 2 * y + 1);
+                    texels.push(sum * 0.25);
+                }
+            }
+
+            levels.push(MipLevel {
+                width,
+                height,
+                texels,
+            });
+        }
+
+        Ok(MipMap { levels })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_chain_averages_down_to_one_texel() {
+        let texels = vec![
+            Vec4::splat(0.),
+            Vec4::splat(1.),
+            Vec4::splat(2.),
+            Vec4::splat(3.),
+            Vec4::splat(4.),
+            Vec4::splat(5.),
+        ];
+        let mip = MipMap::new(3, 2, texels).unwrap();
+        let sizes: Vec<_> = mip.levels.iter().map(|l| (l.width, l.height)).collect();
+        assert_eq!(sizes, vec![(3, 2), (1, 1)]);
+        assert_eq!(mip.levels[1].texels, vec![Vec4::splat(2.)]);
+    }
 
-        Vec3::new(r, g, b)
+    #[test]
+    fn empty_images_are_errors() {
+        assert!(MipMap::new(0, 4, Vec::new()).is_err());
+        assert!(MipMap::new(4, 0, Vec::new()).is_err());
+        assert!(MipMap::new(2, 2, vec![Vec4::ZERO; 3]).is_err());
+        let empty = DynamicImage::new_rgb8(0, 0);
+        assert!(MipMap::from_image(&empty, ColorSpace::Srgb).is_err());
     }
 }
@@ -1,17 +1,29 @@
 use crate::{
     texture::{
-        checker_texture::CheckerTexture, constant_texture::ConstantTexture,
-        image_texture::ImageTexture, noise_texture::NoiseTexture,
+        checker_texture::CheckerTexture,
+        constant_texture::ConstantTexture,
+        image_texture::ImageTexture,
+        node_texture::{
+            InvertTexture, MathTexture, MixTexture, RampTexture, RemapTexture, TriplanarTexture,
+            UvTransformTexture,
+        },
+        noise_texture::NoiseTexture,
+        pattern_texture::PatternTexture,
+        vertex_color_texture::VertexColorTexture,
     },
     vec3::Vec3,
 };
 use enum_dispatch::enum_dispatch;
 
 pub mod checker_texture;
+pub mod color_ramp;
 pub mod constant_texture;
 pub mod image_texture;
+pub mod node_texture;
 pub mod noise_texture;
+pub mod pattern_texture;
 pub mod perlin;
+pub mod vertex_color_texture;
 
 #[enum_dispatch(Texture)]
 #[derive(Clone)]
@@ -21,9 +33,54 @@ pub enum TextureType {
     CheckerTexture,
     NoiseTexture,
     ImageTexture,
+    PatternTexture,
+    MathTexture,
+    MixTexture,
+    InvertTexture,
+    RemapTexture,
+    RampTexture,
+    UvTransformTexture,
+    TriplanarTexture,
+    VertexColorTexture,
+}
+
+/// Everything known about a surface point when looking up a texture for shading
+#[derive(Clone, Copy, Debug)]
+pub struct TextureContext {
+    pub u: f32,
+    pub v: f32,
+    pub point: Vec3,
+    /// Approximate width of the pixel footprint in uv units, 0 when unknown
+    pub footprint: f32,
+    /// Same footprint in world units, for textures that do not use the surface uv
+    pub world_footprint: f32,
+    /// Shading normal at the point, zero when unknown
+    pub normal: Vec3,
+    /// Colour given by the primitive that was hit, if any
+    pub color: Option<Vec3>,
+}
+
+impl TextureContext {
+    /// Context for a plain `value` lookup, without any filtering information
+    pub fn new(u: f32, v: f32, point: Vec3) -> Self {
+        TextureContext {
+            u,
+            v,
+            point,
+            footprint: 0.,
+            world_footprint: 0.,
+            normal: Vec3::ZERO,
+            color: None,
+        }
+    }
 }
 
 #[enum_dispatch]
 pub trait Texture: Clone {
     fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3;
+
+    /// Lookup with the full shading context, textures that can filter override this
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        self.value(ctx.u, ctx.v, ctx.point)
+    }
 }
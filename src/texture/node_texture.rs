@@ -0,0 +1,332 @@
+use crate::{
+    texture::{
+        color_ramp::ColorRamp, constant_texture::ConstantTexture, Texture, TextureContext,
+        TextureType,
+    },
+    utils::clamp,
+    vec3::{Vec3, Vec3Wrapper},
+};
+use glam::Vec2;
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Clone, Copy, Debug)]
+pub enum MathOp {
+    Add,
+    Subtract,
+    Multiply,
+}
+
+/// Component-wise arithmetic between two textures, also available through the `+`, `-` and `*`
+/// operators on `TextureType`
+#[derive(Clone)]
+pub struct MathTexture {
+    pub op: MathOp,
+    pub a: Box<TextureType>,
+    pub b: Box<TextureType>,
+}
+
+impl MathTexture {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(op: MathOp, a: TextureType, b: TextureType) -> TextureType {
+        TextureType::from(MathTexture {
+            op,
+            a: Box::new(a),
+            b: Box::new(b),
+        })
+    }
+}
+
+impl Texture for MathTexture {
+    fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        self.sample(&TextureContext::new(u, v, p))
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        let a = self.a.sample(ctx);
+        let b = self.b.sample(ctx);
+        match self.op {
+            MathOp::Add => a + b,
+            MathOp::Subtract => a - b,
+            MathOp::Multiply => a * b,
+        }
+    }
+}
+
+impl Add for TextureType {
+    type Output = TextureType;
+
+    fn add(self, other: TextureType) -> TextureType {
+        MathTexture::new(MathOp::Add, self, other)
+    }
+}
+
+impl Sub for TextureType {
+    type Output = TextureType;
+
+    fn sub(self, other: TextureType) -> TextureType {
+        MathTexture::new(MathOp::Subtract, self, other)
+    }
+}
+
+impl Mul for TextureType {
+    type Output = TextureType;
+
+    fn mul(self, other: TextureType) -> TextureType {
+        MathTexture::new(MathOp::Multiply, self, other)
+    }
+}
+
+/// Per channel interpolation from `a` where the mask is black to `b` where it is white
+#[derive(Clone)]
+pub struct MixTexture {
+    pub a: Box<TextureType>,
+    pub b: Box<TextureType>,
+    pub mask: Box<TextureType>,
+}
+
+impl MixTexture {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(a: TextureType, b: TextureType, mask: TextureType) -> TextureType {
+        TextureType::from(MixTexture {
+            a: Box::new(a),
+            b: Box::new(b),
+            mask: Box::new(mask),
+        })
+    }
+
+    /// Mix with the same factor everywhere
+    pub fn lerp(a: TextureType, b: TextureType, t: f32) -> TextureType {
+        MixTexture::new(a, b, ConstantTexture::new(t, t, t))
+    }
+}
+
+impl Texture for MixTexture {
+    fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        self.sample(&TextureContext::new(u, v, p))
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        let mask = self.mask.sample(ctx);
+        let a = self.a.sample(ctx);
+        let b = self.b.sample(ctx);
+        a + (b - a) * mask
+    }
+}
+
+/// `1 - input` on every channel
+#[derive(Clone)]
+pub struct InvertTexture {
+    pub input: Box<TextureType>,
+}
+
+impl InvertTexture {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(input: TextureType) -> TextureType {
+        TextureType::from(InvertTexture {
+            input: Box::new(input),
+        })
+    }
+}
+
+impl Texture for InvertTexture {
+    fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        self.sample(&TextureContext::new(u, v, p))
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        Vec3::ONE - self.input.sample(ctx)
+    }
+}
+
+/// Linearly maps every channel from `from` to `to`, values outside of `from` are extrapolated
+/// unless `clamp` is set
+#[derive(Clone)]
+pub struct RemapTexture {
+    pub input: Box<TextureType>,
+    pub from: (f32, f32),
+    pub to: (f32, f32),
+    pub clamp: bool,
+}
+
+impl RemapTexture {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(input: TextureType, from: (f32, f32), to: (f32, f32), clamp: bool) -> TextureType {
+        TextureType::from(RemapTexture {
+            input: Box::new(input),
+            from,
+            to,
+            clamp,
+        })
+    }
+}
+
+impl Texture for RemapTexture {
+    fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        self.sample(&TextureContext::new(u, v, p))
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        let (from_min, from_max) = self.from;
+        let (to_min, to_max) = self.to;
+        let range = from_max - from_min;
+
+        self.input.sample(ctx).map(|x| {
+            let t = if range != 0. {
+                (x - from_min) / range
+            } else {
+                0.
+            };
+            let t = if self.clamp { clamp(t, 0., 1.) } else { t };
+            to_min + t * (to_max - to_min)
+        })
+    }
+}
+
+/// Which scalar is read from a colour
+#[derive(Clone, Copy, Debug)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    /// Rec. 709 luminance
+    Luminance,
+}
+
+impl Channel {
+    pub fn extract(self, color: Vec3) -> f32 {
+        match self {
+            Channel::Red => color.x,
+            Channel::Green => color.y,
+            Channel::Blue => color.z,
+            Channel::Luminance => color.dot(Vec3::new(0.2126, 0.7152, 0.0722)),
+        }
+    }
+}
+
+/// Turns a scalar texture into a colour, one channel of the input is looked up in the ramp
+#[derive(Clone)]
+pub struct RampTexture {
+    pub input: Box<TextureType>,
+    pub channel: Channel,
+    pub ramp: ColorRamp,
+}
+
+impl RampTexture {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(input: TextureType, channel: Channel, ramp: ColorRamp) -> TextureType {
+        TextureType::from(RampTexture {
+            input: Box::new(input),
+            channel,
+            ramp,
+        })
+    }
+}
+
+impl Texture for RampTexture {
+    fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        self.sample(&TextureContext::new(u, v, p))
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        let t = self.channel.extract(self.input.sample(ctx));
+        self.ramp.sample(t)
+    }
+}
+
+/// Scales, rotates (in degrees, around the uv origin) and then offsets the uv coordinates seen
+/// by the input
+#[derive(Clone)]
+pub struct UvTransformTexture {
+    pub input: Box<TextureType>,
+    pub scale: Vec2,
+    pub rotation: f32,
+    pub offset: Vec2,
+}
+
+impl UvTransformTexture {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(input: TextureType, scale: Vec2, rotation: f32, offset: Vec2) -> TextureType {
+        TextureType::from(UvTransformTexture {
+            input: Box::new(input),
+            scale,
+            rotation,
+            offset,
+        })
+    }
+}
+
+impl Texture for UvTransformTexture {
+    fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        self.sample(&TextureContext::new(u, v, p))
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let u = ctx.u * self.scale.x;
+        let v = ctx.v * self.scale.y;
+
+        self.input.sample(&TextureContext {
+            u: cos * u - sin * v + self.offset.x,
+            v: sin * u + cos * v + self.offset.y,
+            footprint: ctx.footprint * self.scale.abs().max_element(),
+            ..*ctx
+        })
+    }
+}
+
+/// Projects the input along the three world axes and blends the projections with the surface
+/// normal, for surfaces without usable uv coordinates
+#[derive(Clone)]
+pub struct TriplanarTexture {
+    pub input: Box<TextureType>,
+    /// Number of texture repeats per world unit
+    pub scale: f32,
+    /// Exponent applied to the normal before blending, higher values give narrower transitions
+    pub sharpness: f32,
+}
+
+impl TriplanarTexture {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(input: TextureType, scale: f32, sharpness: f32) -> TextureType {
+        TextureType::from(TriplanarTexture {
+            input: Box::new(input),
+            scale,
+            sharpness,
+        })
+    }
+}
+
+impl Texture for TriplanarTexture {
+    fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        self.sample(&TextureContext::new(u, v, p))
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        let weights = ctx.normal.abs().map(|x| x.powf(self.sharpness));
+        let total = weights.x + weights.y + weights.z;
+        // Without a normal every projection gets the same weight
+        let weights = if total > 0. {
+            weights / total
+        } else {
+            Vec3::splat(1. / 3.)
+        };
+
+        let p = self.scale * ctx.point;
+        let footprint = self.scale * ctx.world_footprint;
+        let planes = [(p.z, p.y), (p.x, p.z), (p.x, p.y)];
+
+        let mut color = Vec3::ZERO;
+        for (axis, (u, v)) in planes.iter().enumerate() {
+            if weights[axis] > 0. {
+                let projected = TextureContext {
+                    u: *u,
+                    v: *v,
+                    footprint,
+                    ..*ctx
+                };
+                color += weights[axis] * self.input.sample(&projected);
+            }
+        }
+        color
+    }
+}
@@ -1,19 +1,184 @@
 use crate::{
-    texture::{perlin::Perlin, Texture},
+    texture::{color_ramp::ColorRamp, perlin::Perlin, Texture},
     vec3::Vec3,
 };
 
+#[derive(Clone, Copy, Debug)]
+pub enum NoiseBasis {
+    Perlin,
+    Simplex,
+    /// Cellular noise, distance to the closest feature point
+    Worley,
+}
+
+/// How octaves of the basis are combined
+#[derive(Clone, Copy, Debug)]
+pub enum FractalMode {
+    /// Fractional Brownian motion, the plain sum of the octaves
+    Fbm,
+    /// Sum of the absolute value of the octaves
+    Turbulence,
+    /// Sum of inverted absolute octaves, gives sharp crests
+    Ridged,
+}
+
+/// How the fractal noise is turned into the value looked up in the ramp
+#[derive(Clone, Copy, Debug)]
+pub enum NoisePattern {
+    Plain,
+    /// Veins along z, `sin(frequency * p.z + distortion * noise)`
+    Marble {
+        frequency: f32,
+        distortion: f32,
+    },
+    /// Rings around the y axis, `rings` per world unit
+    Wood {
+        rings: f32,
+        distortion: f32,
+    },
+}
+
 #[derive(Clone)]
 pub struct NoiseTexture {
     pub perlin: Perlin,
+    pub basis: NoiseBasis,
+    pub fractal: FractalMode,
+    pub octaves: u32,
+    /// Frequency multiplier between octaves
+    pub lacunarity: f32,
+    /// Amplitude multiplier between octaves
+    pub gain: f32,
+    /// Frequency of the first octave
     pub scale: f32,
+    pub pattern: NoisePattern,
+    pub ramp: ColorRamp,
+}
+
+impl NoiseTexture {
+    /// Grayscale Perlin fBm with 5 octaves
+    pub fn new(seed: u64) -> Self {
+        NoiseTexture {
+            perlin: Perlin::new(seed),
+            basis: NoiseBasis::Perlin,
+            fractal: FractalMode::Fbm,
+            octaves: 5,
+            lacunarity: 2.,
+            gain: 0.5,
+            scale: 1.,
+            pattern: NoisePattern::Plain,
+            ramp: ColorRamp::grayscale(),
+        }
+    }
+
+    pub fn marble(frequency: f32, seed: u64) -> Self {
+        NoiseTexture {
+            fractal: FractalMode::Turbulence,
+            octaves: 7,
+            pattern: NoisePattern::Marble {
+                frequency,
+                distortion: 20.,
+            },
+            ..NoiseTexture::new(seed)
+        }
+    }
+
+    pub fn wood(rings: f32, seed: u64) -> Self {
+        NoiseTexture {
+            octaves: 3,
+            scale: 2.,
+            pattern: NoisePattern::Wood {
+                rings,
+                distortion: 1.5,
+            },
+            ramp: ColorRamp::new(vec![
+                (0., Vec3::new(0.45, 0.26, 0.12)),
+                (0.7, Vec3::new(0.65, 0.42, 0.2)),
+                (1., Vec3::new(0.3, 0.16, 0.07)),
+            ]),
+            ..NoiseTexture::new(seed)
+        }
+    }
+
+    pub fn clouds(scale: f32, seed: u64) -> Self {
+        NoiseTexture {
+            basis: NoiseBasis::Simplex,
+            octaves: 6,
+            scale,
+            ramp: ColorRamp::new(vec![
+                (0.45, Vec3::new(0.3, 0.5, 0.9)),
+                (0.7, Vec3::new(1., 1., 1.)),
+            ]),
+            ..NoiseTexture::new(seed)
+        }
+    }
+
+    pub fn stone(scale: f32, seed: u64) -> Self {
+        NoiseTexture {
+            basis: NoiseBasis::Worley,
+            octaves: 3,
+            scale,
+            ramp: ColorRamp::new(vec![
+                (0.2, Vec3::new(0.15, 0.14, 0.13)),
+                (0.5, Vec3::new(0.45, 0.43, 0.4)),
+                (0.8, Vec3::new(0.6, 0.58, 0.55)),
+            ]),
+            ..NoiseTexture::new(seed)
+        }
+    }
+
+    /// Single octave of the basis in `[-1, 1]`
+    fn basis_noise(&self, p: Vec3) -> f32 {
+        match self.basis {
+            NoiseBasis::Perlin => self.perlin.noise(p),
+            NoiseBasis::Simplex => self.perlin.simplex(p),
+            NoiseBasis::Worley => 2. * self.perlin.worley(p) - 1.,
+        }
+    }
+
+    /// Octaves combined according to the fractal mode, normalized to `[0, 1]`
+    pub fn fractal_noise(&self, p: Vec3) -> f32 {
+        let mut accum = 0.;
+        let mut total_amplitude = 0.;
+        let mut amplitude = 1.;
+        let mut p = p;
+
+        for _ in 0..self.octaves.max(1) {
+            let n = self.basis_noise(p);
+            accum += amplitude
+                * match self.fractal {
+                    FractalMode::Fbm => n,
+                    FractalMode::Turbulence => n.abs(),
+                    FractalMode::Ridged => (1. - n.abs()).powi(2),
+                };
+            total_amplitude += amplitude;
+            amplitude *= self.gain;
+            p *= self.lacunarity;
+        }
+
+        let accum = accum / total_amplitude;
+        match self.fractal {
+            FractalMode::Fbm => 0.5 * (accum + 1.),
+            FractalMode::Turbulence | FractalMode::Ridged => accum,
+        }
+    }
 }
 
 impl Texture for NoiseTexture {
     fn value(&self, _u: f32, _v: f32, p: Vec3) -> Vec3 {
-        Vec3::ONE * 0.5 * (1. + (self.scale * p.z + 10. * self.perlin.turbulence(p, 7)).sin())
-        // Vec3::one() * self.perlin.noise(self.scale * p)
-        // Vec3::one() * self.perlin.turbulence(self.scale * p, 7)
-        // Vec3::one() * self.perlin.noise(p)
+        let noise = self.fractal_noise(self.scale * p);
+        let t = match self.pattern {
+            NoisePattern::Plain => noise,
+            NoisePattern::Marble {
+                frequency,
+                distortion,
+            } => 0.5 * (1. + (frequency * p.z + distortion * noise).sin()),
+            NoisePattern::Wood { rings, distortion } => {
+                let radius = (p.x * p.x + p.z * p.z).sqrt();
+                let ring = rings * radius + distortion * noise;
+                ring - ring.floor()
+            }
+        };
+
+        self.ramp.sample(t)
     }
 }
@@ -0,0 +1,130 @@
+use crate::{
+    texture::{Texture, TextureContext, TextureType},
+    vec3::{Vec3, Vec3Wrapper},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Pattern {
+    Checker,
+    /// Stripes perpendicular to the first axis
+    Stripes,
+    /// Lines along every axis, `line_width` is a fraction of a cell
+    Grid {
+        line_width: f32,
+    },
+    /// One dot per cell, `radius` is a fraction of a cell
+    PolkaDot {
+        radius: f32,
+    },
+    /// Running bond bricks twice as wide as they are tall, `mortar` is a fraction of a brick
+    Brick {
+        mortar: f32,
+    },
+}
+
+/// Which coordinates a pattern is laid out in
+#[derive(Clone, Copy, Debug)]
+pub enum PatternSpace {
+    /// Surface `(u, v)`, tiles evenly on rects and follows the surface parameterisation
+    Uv,
+    /// Hit point, the pattern is solid and cuts through objects
+    World,
+}
+
+/// Two textures arranged in a pattern, `a` is used for the checker cells with an odd index, the
+/// stripes, the lines of the grid, the dots and the bricks, `b` everywhere else
+#[derive(Clone)]
+pub struct PatternTexture {
+    pub pattern: Pattern,
+    pub space: PatternSpace,
+    /// Number of cells per uv unit or per world unit
+    pub scale: f32,
+    /// Rotation in degrees, around the uv origin or around the world y axis
+    pub rotation: f32,
+    pub a: Box<TextureType>,
+    pub b: Box<TextureType>,
+}
+
+impl PatternTexture {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        pattern: Pattern,
+        space: PatternSpace,
+        scale: f32,
+        rotation: f32,
+        a: TextureType,
+        b: TextureType,
+    ) -> TextureType {
+        TextureType::from(PatternTexture {
+            pattern,
+            space,
+            scale,
+            rotation,
+            a: Box::new(a),
+            b: Box::new(b),
+        })
+    }
+
+    /// Pattern coordinates and how many of their axes are used
+    fn coordinates(&self, u: f32, v: f32, p: Vec3) -> (Vec3, usize) {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        match self.space {
+            PatternSpace::Uv => {
+                let q = Vec3::new(cos * u - sin * v, sin * u + cos * v, 0.);
+                (self.scale * q, 2)
+            }
+            PatternSpace::World => {
+                let q = Vec3::new(cos * p.x + sin * p.z, p.y, -sin * p.x + cos * p.z);
+                (self.scale * q, 3)
+            }
+        }
+    }
+
+    fn is_a(&self, u: f32, v: f32, p: Vec3) -> bool {
+        let (q, dims) = self.coordinates(u, v, p);
+        let cell = q.map(f32::floor);
+        let local = q - cell;
+
+        match self.pattern {
+            Pattern::Checker => {
+                let sum: f32 = (0..dims).map(|i| cell[i]).sum();
+                sum.rem_euclid(2.) == 1.
+            }
+            Pattern::Stripes => cell.x.rem_euclid(2.) == 1.,
+            Pattern::Grid { line_width } => (0..dims).any(|i| local[i] < line_width),
+            Pattern::PolkaDot { radius } => {
+                let distance_squared: f32 = (0..dims).map(|i| (local[i] - 0.5).powi(2)).sum();
+                distance_squared < radius * radius
+            }
+            Pattern::Brick { mortar } => {
+                let row = (2. * q.y).floor();
+                let offset = 0.5 * row.rem_euclid(2.);
+                let x = q.x + offset;
+                let z = q.z + offset;
+                let (x, y, z) = (x - x.floor(), 2. * q.y - row, z - z.floor());
+
+                // Rows are half as tall so the mortar is doubled along y to keep a constant width
+                let in_mortar = x < mortar || y < 2. * mortar || (dims == 3 && z < mortar);
+                !in_mortar
+            }
+        }
+    }
+}
+
+impl Texture for PatternTexture {
+    fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        if self.is_a(u, v, p) {
+            self.a.value(u, v, p)
+        } else {
+            self.b.value(u, v, p)
+        }
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        if self.is_a(ctx.u, ctx.v, ctx.point) {
+            self.a.sample(ctx)
+        } else {
+            self.b.sample(ctx)
+        }
+    }
+}
@@ -2,20 +2,41 @@ use crate::{
     random::random_double,
     vec3::{Vec3, Vec3Wrapper},
 };
-use lazy_static::lazy_static;
 use rand::{prelude::SliceRandom, rngs::SmallRng, SeedableRng};
 
+/// Random gradients and permutation tables shared by the noise functions, two instances built
+/// from the same seed produce the same noise
 #[derive(Clone)]
-pub struct Perlin;
-
-lazy_static! {
-    pub static ref VECS: Vec<Vec3> = perlin_generate();
-    pub static ref PERM_X: Vec<u8> = perlin_generate_perm();
-    pub static ref PERM_Y: Vec<u8> = perlin_generate_perm();
-    pub static ref PERM_Z: Vec<u8> = perlin_generate_perm();
+pub struct Perlin {
+    vecs: Vec<Vec3>,
+    perm_x: Vec<u8>,
+    perm_y: Vec<u8>,
+    perm_z: Vec<u8>,
+    /// Feature points of the Worley noise, uniform inside a unit cell
+    points: Vec<Vec3>,
 }
 
 impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let rng = &mut SmallRng::seed_from_u64(seed);
+        Perlin {
+            vecs: perlin_generate(rng),
+            perm_x: perlin_generate_perm(rng),
+            perm_y: perlin_generate_perm(rng),
+            perm_z: perlin_generate_perm(rng),
+            points: worley_generate(rng),
+        }
+    }
+
+    fn hash(&self, i: i32, j: i32, k: i32) -> usize {
+        let ix = self.perm_x[(i & 255) as usize];
+        let iy = self.perm_y[(j & 255) as usize];
+        let iz = self.perm_z[(k & 255) as usize];
+        (ix ^ iy ^ iz) as usize
+    }
+
+    /// Gradient noise in `[-1, 1]`
+    #[allow(clippy::needless_range_loop)]
     pub fn noise(&self, p: Vec3) -> f32 {
         let ijk = p.map(f32::floor);
         let uvw = p - ijk;
@@ -23,10 +44,12 @@ impl Perlin {
         for di in 0..2 {
             for dj in 0..2 {
                 for dk in 0..2 {
-                    let ix = PERM_X[((ijk.x as i32 + di as i32) & 255) as usize];
-                    let iy = PERM_Y[((ijk.y as i32 + dj as i32) & 255) as usize];
-                    let iz = PERM_Z[((ijk.z as i32 + dk as i32) & 255) as usize];
-                    corners[di][dj][dk] = VECS[(ix ^ iy ^ iz) as usize]
+                    let hash = self.hash(
+                        ijk.x as i32 + di as i32,
+                        ijk.y as i32 + dj as i32,
+                        ijk.z as i32 + dk as i32,
+                    );
+                    corners[di][dj][dk] = self.vecs[hash]
                 }
             }
         }
@@ -46,10 +69,80 @@ impl Perlin {
         }
         accum.abs()
     }
+
+    /// Simplex noise in roughly `[-1, 1]`, cheaper than `noise` and without its axis aligned
+    /// artifacts
+    #[allow(clippy::many_single_char_names)]
+    pub fn simplex(&self, p: Vec3) -> f32 {
+        const F3: f32 = 1. / 3.;
+        const G3: f32 = 1. / 6.;
+
+        // Skew the input space to find which simplex cell we are in
+        let s = (p.x + p.y + p.z) * F3;
+        let ijk = (p + Vec3::splat(s)).map(f32::floor);
+        let t = (ijk.x + ijk.y + ijk.z) * G3;
+        let d0 = p - (ijk - Vec3::splat(t));
+
+        // The corners of the simplex are found by ordering the offsets
+        let (o1, o2) = if d0.x >= d0.y {
+            if d0.y >= d0.z {
+                (Vec3::newi(1, 0, 0), Vec3::newi(1, 1, 0))
+            } else if d0.x >= d0.z {
+                (Vec3::newi(1, 0, 0), Vec3::newi(1, 0, 1))
+            } else {
+                (Vec3::newi(0, 0, 1), Vec3::newi(1, 0, 1))
+            }
+        } else if d0.y < d0.z {
+            (Vec3::newi(0, 0, 1), Vec3::newi(0, 1, 1))
+        } else if d0.x < d0.z {
+            (Vec3::newi(0, 1, 0), Vec3::newi(0, 1, 1))
+        } else {
+            (Vec3::newi(0, 1, 0), Vec3::newi(1, 1, 0))
+        };
+
+        let corners = [
+            (Vec3::ZERO, d0),
+            (o1, d0 - o1 + Vec3::splat(G3)),
+            (o2, d0 - o2 + Vec3::splat(2. * G3)),
+            (Vec3::ONE, d0 - Vec3::ONE + Vec3::splat(3. * G3)),
+        ];
+
+        let mut accum = 0.;
+        for (offset, d) in corners.iter() {
+            let falloff = 0.6 - d.length_squared();
+            if falloff > 0. {
+                let corner = ijk + *offset;
+                let gradient =
+                    self.vecs[self.hash(corner.x as i32, corner.y as i32, corner.z as i32)];
+                accum += falloff.powi(4) * gradient.dot(*d);
+            }
+        }
+
+        32. * accum
+    }
+
+    /// Distance to the closest of one random feature point per cell, in `[0, 1]`
+    pub fn worley(&self, p: Vec3) -> f32 {
+        let cell = p.map(f32::floor);
+        let mut closest = f32::MAX;
+
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let neighbour = cell + Vec3::newi(di, dj, dk);
+                    let hash =
+                        self.hash(neighbour.x as i32, neighbour.y as i32, neighbour.z as i32);
+                    let distance = (neighbour + self.points[hash] - p).length_squared();
+                    closest = closest.min(distance);
+                }
+            }
+        }
+
+        closest.sqrt().min(1.)
+    }
 }
 
-fn perlin_generate() -> Vec<Vec3> {
-    let rng = &mut SmallRng::from_entropy();
+fn perlin_generate(rng: &mut SmallRng) -> Vec<Vec3> {
     let mut result = Vec::with_capacity(256);
     for _ in 0..=255 {
         result.push(
@@ -64,8 +157,13 @@ fn perlin_generate() -> Vec<Vec3> {
     result
 }
 
-fn perlin_generate_perm() -> Vec<u8> {
-    let rng = &mut SmallRng::from_entropy();
+fn worley_generate(rng: &mut SmallRng) -> Vec<Vec3> {
+    (0..=255)
+        .map(|_| Vec3::new(random_double(rng), random_double(rng), random_double(rng)))
+        .collect()
+}
+
+fn perlin_generate_perm(rng: &mut SmallRng) -> Vec<u8> {
     let mut p: Vec<u8> = (0..=255).collect();
     p.shuffle(rng);
     p
@@ -0,0 +1,31 @@
+use super::{Texture, TextureContext, TextureType};
+use crate::vec3::Vec3;
+
+/// Colour carried by the primitive that was hit, like the colour of a particle or of a mesh
+/// vertex, and `fallback` on primitives without one
+#[derive(Clone)]
+pub struct VertexColorTexture {
+    pub fallback: Box<TextureType>,
+}
+
+impl VertexColorTexture {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(fallback: TextureType) -> TextureType {
+        TextureType::from(VertexColorTexture {
+            fallback: Box::new(fallback),
+        })
+    }
+}
+
+impl Texture for VertexColorTexture {
+    fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        self.fallback.value(u, v, p)
+    }
+
+    fn sample(&self, ctx: &TextureContext) -> Vec3 {
+        match ctx.color {
+            Some(color) => color,
+            None => self.fallback.sample(ctx),
+        }
+    }
+}
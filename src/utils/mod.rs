@@ -1,3 +1,5 @@
+pub mod ply;
+
 pub fn clamp<T: PartialOrd>(value: T, min: T, max: T) -> T {
     match value {
         value if value < min => min,
@@ -0,0 +1,356 @@
+//! Minimal reader for the PLY format, ascii and binary, keeping every property as numbers
+
+use std::{
+    fs,
+    io::{self, Error, ErrorKind},
+    path::Path,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlyType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> io::Result<Self> {
+        Ok(match name {
+            "char" | "int8" => PlyType::Int8,
+            "uchar" | "uint8" => PlyType::UInt8,
+            "short" | "int16" => PlyType::Int16,
+            "ushort" | "uint16" => PlyType::UInt16,
+            "int" | "int32" => PlyType::Int32,
+            "uint" | "uint32" => PlyType::UInt32,
+            "float" | "float32" => PlyType::Float32,
+            "double" | "float64" => PlyType::Float64,
+            _ => return Err(invalid(format!("unknown PLY type {}", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::Int8 | PlyType::UInt8 => 1,
+            PlyType::Int16 | PlyType::UInt16 => 2,
+            PlyType::Int32 | PlyType::UInt32 | PlyType::Float32 => 4,
+            PlyType::Float64 => 8,
+        }
+    }
+
+    /// Largest value of integer types, used to bring colours to `[0, 1]`
+    pub fn max_value(self) -> f64 {
+        match self {
+            PlyType::Int8 => i8::MAX as f64,
+            PlyType::UInt8 => u8::MAX as f64,
+            PlyType::Int16 => i16::MAX as f64,
+            PlyType::UInt16 => u16::MAX as f64,
+            PlyType::Int32 => i32::MAX as f64,
+            PlyType::UInt32 => u32::MAX as f64,
+            PlyType::Float32 | PlyType::Float64 => 1.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Debug)]
+pub enum PlyData {
+    Scalar(Vec<f64>),
+    List(Vec<Vec<u32>>),
+}
+
+#[derive(Clone, Debug)]
+pub struct PlyProperty {
+    pub name: String,
+    /// Type of the values, for lists the type of the items
+    pub ty: PlyType,
+    /// Type of the length of lists
+    count_ty: Option<PlyType>,
+    pub data: PlyData,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlyElement {
+    pub name: String,
+    pub count: usize,
+    pub properties: Vec<PlyProperty>,
+}
+
+impl PlyElement {
+    pub fn property(&self, name: &str) -> Option<&PlyProperty> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    /// Values of a scalar property
+    pub fn scalars(&self, name: &str) -> Option<&[f64]> {
+        match &self.property(name)?.data {
+            PlyData::Scalar(values) => Some(values),
+            PlyData::List(_) => None,
+        }
+    }
+
+    /// Values of a list property
+    pub fn lists(&self, name: &str) -> Option<&[Vec<u32>]> {
+        match &self.property(name)?.data {
+            PlyData::List(values) => Some(values),
+            PlyData::Scalar(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Ply {
+    pub elements: Vec<PlyElement>,
+}
+
+impl Ply {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ply::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> io::Result<Self> {
+        const END_HEADER: &[u8] = b"end_header";
+        let end = bytes
+            .windows(END_HEADER.len())
+            .position(|w| w == END_HEADER)
+            .ok_or_else(|| invalid("missing PLY end_header"))?;
+        let body_start = match bytes[end + END_HEADER.len()..]
+            .iter()
+            .position(|b| *b == b'\n')
+        {
+            Some(newline) => end + END_HEADER.len() + newline + 1,
+            None => bytes.len(),
+        };
+
+        let header = String::from_utf8_lossy(&bytes[..end]);
+        let mut lines = header.lines().map(str::trim);
+        if lines.next() != Some("ply") {
+            return Err(invalid("not a PLY file"));
+        }
+
+        let mut format = None;
+        let mut elements: Vec<PlyElement> = Vec::new();
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["format", name, _] => {
+                    format = Some(match *name {
+                        "ascii" => Format::Ascii,
+                        "binary_little_endian" => Format::BinaryLittleEndian,
+                        "binary_big_endian" => Format::BinaryBigEndian,
+                        _ => return Err(invalid(format!("unknown PLY format {}", name))),
+                    })
+                }
+                ["element", name, count] => elements.push(PlyElement {
+                    name: name.to_string(),
+                    count: count
+                        .parse()
+                        .map_err(|_| invalid(format!("bad element count {}", count)))?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", count_ty, ty, name] => {
+                    let element = elements
+                        .last_mut()
+                        .ok_or_else(|| invalid("PLY property before any element"))?;
+                    element.properties.push(PlyProperty {
+                        name: name.to_string(),
+                        ty: PlyType::parse(ty)?,
+                        count_ty: Some(PlyType::parse(count_ty)?),
+                        data: PlyData::List(Vec::new()),
+                    });
+                }
+                ["property", ty, name] => {
+                    let element = elements
+                        .last_mut()
+                        .ok_or_else(|| invalid("PLY property before any element"))?;
+                    element.properties.push(PlyProperty {
+                        name: name.to_string(),
+                        ty: PlyType::parse(ty)?,
+                        count_ty: None,
+                        data: PlyData::Scalar(Vec::new()),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let format = format.ok_or_else(|| invalid("missing PLY format"))?;
+        let mut reader = Reader {
+            bytes: &bytes[body_start..],
+            position: 0,
+            format,
+        };
+        for element in elements.iter_mut() {
+            for _ in 0..element.count {
+                for property in element.properties.iter_mut() {
+                    match (&mut property.data, property.count_ty) {
+                        (PlyData::List(lists), Some(count_ty)) => {
+                            let count = reader.read(count_ty)? as usize;
+                            // Counts come from the file, each value takes at least a byte
+                            let mut list = Vec::with_capacity(count.min(reader.remaining()));
+                            for _ in 0..count {
+                                list.push(reader.read(property.ty)? as u32);
+                            }
+                            lists.push(list);
+                        }
+                        (PlyData::Scalar(values), _) => values.push(reader.read(property.ty)?),
+                        (PlyData::List(_), None) => unreachable!(),
+                    }
+                }
+            }
+        }
+
+        Ok(Ply { elements })
+    }
+
+    pub fn element(&self, name: &str) -> Option<&PlyElement> {
+        self.elements.iter().find(|e| e.name == name)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    format: Format,
+}
+
+impl<'a> Reader<'a> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    fn read(&mut self, ty: PlyType) -> io::Result<f64> {
+        match self.format {
+            Format::Ascii => {
+                let rest = &self.bytes[self.position..];
+                let start = rest
+                    .iter()
+                    .position(|b| !b.is_ascii_whitespace())
+                    .ok_or_else(|| invalid("unexpected end of PLY data"))?;
+                let len = rest[start..]
+                    .iter()
+                    .position(|b| b.is_ascii_whitespace())
+                    .unwrap_or(rest.len() - start);
+                self.position += start + len;
+
+                let word = std::str::from_utf8(&rest[start..start + len])
+                    .map_err(|_| invalid("invalid PLY value"))?;
+                word.parse()
+                    .map_err(|_| invalid(format!("invalid PLY value {}", word)))
+            }
+            Format::BinaryLittleEndian | Format::BinaryBigEndian => {
+                let size = ty.size();
+                let raw = self
+                    .bytes
+                    .get(self.position..self.position + size)
+                    .ok_or_else(|| invalid("unexpected end of PLY data"))?;
+                self.position += size;
+
+                let mut buffer = [0u8; 8];
+                buffer[..size].copy_from_slice(raw);
+                if self.format == Format::BinaryBigEndian {
+                    buffer[..size].reverse();
+                }
+                let b = buffer;
+                Ok(match ty {
+                    PlyType::Int8 => b[0] as i8 as f64,
+                    PlyType::UInt8 => b[0] as f64,
+                    PlyType::Int16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    PlyType::UInt16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    PlyType::Int32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    PlyType::UInt32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    PlyType::Float32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    PlyType::Float64 => f64::from_le_bytes(b),
+                })
+            }
+        }
+    }
+}
+
+pub(crate) fn invalid(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "ply\nformat {}\nelement vertex 2\nproperty float x\nproperty short y\n\
+                          element face 1\nproperty list uchar int vertex_indices\nend_header\n";
+
+    fn header(format: &str) -> Vec<u8> {
+        HEADER.replace("{}", format).into_bytes()
+    }
+
+    fn check(ply: &Ply) {
+        let vertices = ply.element("vertex").unwrap();
+        assert_eq!(vertices.scalars("x").unwrap(), &[1.5, -2.][..]);
+        assert_eq!(vertices.scalars("y").unwrap(), &[-3., 400.][..]);
+        assert!(vertices.lists("x").is_none());
+        let faces = ply.element("face").unwrap();
+        assert_eq!(faces.lists("vertex_indices").unwrap(), &[vec![0, 1, 1]][..]);
+    }
+
+    #[test]
+    fn ascii() {
+        let mut bytes = header("ascii 1.0");
+        bytes.extend_from_slice(b"1.5 -3\n-2 400\n3 0 1 1\n");
+        check(&Ply::parse(&bytes).unwrap());
+    }
+
+    #[test]
+    fn binary_both_endians() {
+        let mut little = header("binary_little_endian 1.0");
+        let mut big = header("binary_big_endian 1.0");
+        for &(x, y) in [(1.5f32, -3i16), (-2., 400)].iter() {
+            little.extend_from_slice(&x.to_le_bytes());
+            little.extend_from_slice(&y.to_le_bytes());
+            big.extend_from_slice(&x.to_be_bytes());
+            big.extend_from_slice(&y.to_be_bytes());
+        }
+        little.push(3);
+        big.push(3);
+        for &i in [0i32, 1, 1].iter() {
+            little.extend_from_slice(&i.to_le_bytes());
+            big.extend_from_slice(&i.to_be_bytes());
+        }
+
+        check(&Ply::parse(&little).unwrap());
+        check(&Ply::parse(&big).unwrap());
+    }
+
+    #[test]
+    fn truncated_or_invalid_data() {
+        let mut bytes = header("ascii 1.0");
+        bytes.extend_from_slice(b"1.5 -3\n-2 400\n3 0 1\n");
+        assert!(Ply::parse(&bytes).is_err());
+
+        let mut bytes = header("ascii 1.0");
+        bytes.extend_from_slice(b"1.5 -3\n-2 x\n3 0 1 1\n");
+        assert!(Ply::parse(&bytes).is_err());
+
+        assert!(Ply::parse(b"ply\nformat ascii 1.0\nelement vertex 1\n").is_err());
+        assert!(Ply::parse(b"obj\nend_header\n").is_err());
+        assert!(Ply::parse(b"ply\nformat text 1.0\nend_header\n").is_err());
+    }
+
+    #[test]
+    fn huge_counts_fail_without_allocating() {
+        let mut bytes = b"ply\nformat binary_little_endian 1.0\nelement vertex 4000000000\n\
+                          property list uint uint indices\nend_header\n"
+            .to_vec();
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&[0; 8]);
+        assert!(Ply::parse(&bytes).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! Polling of the files a scene is read from, to reload it while it is edited
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Time between two checks of the files
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Files with the last time they were seen modified, `None` for missing files
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl FileWatcher {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        FileWatcher {
+            files: files
+                .into_iter()
+                .map(|file| {
+                    let modified = modified(&file);
+                    (file, modified)
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether any file was modified, created or deleted since the last call
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (file, last_modified) in &mut self.files {
+            let modified = modified(file);
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Watches `files` instead, keeping the times already seen for the files watched before
+    pub fn set_files(&mut self, files: Vec<PathBuf>) {
+        let previous = std::mem::take(&mut self.files);
+        self.files = files
+            .into_iter()
+            .map(|file| {
+                let modified = match previous.iter().find(|(f, _)| *f == file) {
+                    Some((_, modified)) => *modified,
+                    None => modified(&file),
+                };
+                (file, modified)
+            })
+            .collect();
+    }
+}
+
+fn modified(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}